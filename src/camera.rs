@@ -0,0 +1,930 @@
+//! The cameras, what they follow, and how they frame and shake
+
+use crate::{
+    car::{CarComponents, CarNumber, PlayerControlled, Tire, WeightMarker},
+    effects::RngResource,
+    from_real,
+    hud::{DebugText, DebugTextSettings, HudFont, HudItem},
+    physics::{CarState, WallHit, WorldBounds},
+    telemetry::Replay,
+    track::AiDriver,
+    Z_LETTERBOX, Z_SECOND_READOUT,
+};
+use bevy::{
+    prelude::*,
+    render::{
+        camera::{ActiveCameras, Camera, CameraProjection, RenderLayers},
+        pass::{
+            LoadOp, Operations, PassDescriptor, RenderPassDepthStencilAttachmentDescriptor,
+            TextureAttachment,
+        },
+        render_graph::{
+            base::MainPass, CameraNode, PassNode, RenderGraph, WindowSwapChainNode,
+            WindowTextureNode,
+        },
+        texture::{Extent3d, TextureDescriptor, TextureDimension, TextureFormat, TextureUsage},
+    },
+    window::{CreateWindow, WindowId, WindowResized},
+};
+
+pub(crate) struct PixelsPerMeter(pub(crate) f32);
+
+impl Default for PixelsPerMeter {
+    fn default() -> Self {
+        Self(16.0)
+    }
+}
+
+pub(crate) struct MainCamera;
+
+/// How the view fits a window of a different shape to the one it was designed for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AspectPolicy {
+    /// Show more or less of the world as the window changes shape
+    Stretch,
+    /// Always show as much of the world as a window of this size would, scaled to fit, with black
+    /// bars filling the rest of the window
+    Letterbox { width: f32, height: f32 },
+}
+
+impl Default for AspectPolicy {
+    fn default() -> Self {
+        Self::Stretch
+    }
+}
+
+/// A bar filling the window either side of a letterboxed view
+pub(crate) struct LetterboxBar {
+    pub(crate) side: f32,
+}
+
+/// The second player's own window, with a camera following their car and their readout in its
+/// corner, so that the two players can each have a screen to themselves
+pub(crate) struct SecondWindow {
+    pub(crate) id: WindowId,
+    pub(crate) car: CarNumber,
+    is_ready: bool,
+}
+
+impl Default for SecondWindow {
+    fn default() -> Self {
+        Self {
+            id: WindowId::new(),
+            car: CarNumber(1),
+            is_ready: false,
+        }
+    }
+}
+
+const SECOND_CAMERA: &str = "SecondWindow";
+
+/// Draws the second window's readout, and is seen by its camera alone
+const SECOND_HUD_LAYER: u8 = 1;
+
+struct SecondCamera;
+
+/// The second window's readout, placed in its camera's coordinates
+struct SecondReadout;
+
+pub(crate) fn open_second_window(
+    second_window: Res<SecondWindow>,
+    windows: Res<Windows>,
+    mut create_window: EventWriter<CreateWindow>,
+) {
+    let primary = windows.get_primary();
+
+    create_window.send(CreateWindow {
+        id: second_window.id,
+        descriptor: WindowDescriptor {
+            title: "Driving Test: second player".to_string(),
+            width: primary.map_or(800.0, |window| window.width()),
+            height: primary.map_or(600.0, |window| window.height()),
+            resizable: true,
+            ..Default::default()
+        },
+    });
+}
+
+/// Once the second window is open, renders the second camera's view into it, and moves the second
+/// player's readout over to it
+pub(crate) fn set_up_second_window(
+    mut commands: Commands,
+    mut second_window: ResMut<SecondWindow>,
+    windows: Res<Windows>,
+    mut render_graph: ResMut<RenderGraph>,
+    mut active_cameras: ResMut<ActiveCameras>,
+    msaa: Res<Msaa>,
+    clear_color: Res<ClearColor>,
+    pixels_per_meter: Res<PixelsPerMeter>,
+    hud_font: Option<Res<HudFont>>,
+    settings: Res<DebugTextSettings>,
+    readouts: Query<(Entity, &DebugText), With<HudItem>>,
+) {
+    // The window is only opened at the end of the frame in which it's asked for
+    if second_window.is_ready || windows.get(second_window.id).is_none() {
+        return;
+    }
+
+    second_window.is_ready = true;
+
+    let id = second_window.id;
+
+    // The second window has its own swap chain, depth buffer and pass, which draws everything in
+    // the main pass, as seen by the second camera
+    render_graph.add_node("second_window_swap_chain", WindowSwapChainNode::new(id));
+    render_graph.add_node(
+        "second_window_depth_texture",
+        WindowTextureNode::new(
+            id,
+            TextureDescriptor {
+                format: TextureFormat::Depth32Float,
+                usage: TextureUsage::OUTPUT_ATTACHMENT,
+                sample_count: msaa.samples,
+                ..Default::default()
+            },
+        ),
+    );
+    render_graph.add_system_node("second_camera", CameraNode::new(SECOND_CAMERA));
+
+    let mut pass = PassNode::<&MainPass>::new(PassDescriptor {
+        color_attachments: vec![msaa.color_attachment_descriptor(
+            TextureAttachment::Input("color_attachment".to_string()),
+            TextureAttachment::Input("color_resolve_target".to_string()),
+            Operations {
+                load: LoadOp::Clear(clear_color.0),
+                store: true,
+            },
+        )],
+        depth_stencil_attachment: Some(RenderPassDepthStencilAttachmentDescriptor {
+            attachment: TextureAttachment::Input("depth".to_string()),
+            depth_ops: Some(Operations {
+                load: LoadOp::Clear(1.0),
+                store: true,
+            }),
+            stencil_ops: None,
+        }),
+        sample_count: msaa.samples,
+    });
+    pass.add_camera(SECOND_CAMERA);
+    active_cameras.add(SECOND_CAMERA);
+    render_graph.add_node("second_window_pass", pass);
+
+    render_graph
+        .add_slot_edge(
+            "second_window_swap_chain",
+            WindowSwapChainNode::OUT_TEXTURE,
+            "second_window_pass",
+            if msaa.samples > 1 {
+                "color_resolve_target"
+            } else {
+                "color_attachment"
+            },
+        )
+        .unwrap();
+    render_graph
+        .add_slot_edge(
+            "second_window_depth_texture",
+            WindowTextureNode::OUT_TEXTURE,
+            "second_window_pass",
+            "depth",
+        )
+        .unwrap();
+    render_graph
+        .add_node_edge("second_camera", "second_window_pass")
+        .unwrap();
+
+    if msaa.samples > 1 {
+        render_graph.add_node(
+            "second_window_multi_sampled_color_attachment",
+            WindowTextureNode::new(
+                id,
+                TextureDescriptor {
+                    size: Extent3d {
+                        depth: 1,
+                        width: 1,
+                        height: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: msaa.samples,
+                    dimension: TextureDimension::D2,
+                    format: TextureFormat::default(),
+                    usage: TextureUsage::OUTPUT_ATTACHMENT,
+                },
+            ),
+        );
+        render_graph
+            .add_slot_edge(
+                "second_window_multi_sampled_color_attachment",
+                WindowSwapChainNode::OUT_TEXTURE,
+                "second_window_pass",
+                "color_attachment",
+            )
+            .unwrap();
+    }
+
+    let camera = commands
+        .spawn_bundle({
+            let mut camera = OrthographicCameraBundle::new_2d();
+
+            camera.camera.name = Some(SECOND_CAMERA.to_string());
+            camera.camera.window = id;
+            camera.orthographic_projection.scale = 1.0 / pixels_per_meter.0;
+
+            camera
+        })
+        .insert(RenderLayers::from_layers(&[0, SECOND_HUD_LAYER]))
+        .insert(SecondCamera)
+        .id();
+
+    // The UI is only drawn in the primary window, so the readout is drawn as part of the world
+    // instead, on a layer of its own so that the main camera doesn't see it
+    for (readout, &DebugText(number)) in readouts.iter() {
+        if number == second_window.car {
+            commands.entity(readout).despawn();
+        }
+    }
+
+    if let Some(hud_font) = hud_font {
+        commands.entity(camera).with_children(|camera| {
+            camera
+                .spawn_bundle(Text2dBundle {
+                    text: Text::with_section(
+                        "Debug Info",
+                        TextStyle {
+                            font: hud_font.handle.clone(),
+                            font_size: 16.0,
+                            color: Color::WHITE,
+                        },
+                        TextAlignment {
+                            vertical: VerticalAlign::Top,
+                            horizontal: HorizontalAlign::Left,
+                        },
+                    ),
+                    visible: Visible {
+                        is_visible: settings.visible,
+                        is_transparent: true,
+                    },
+                    ..Default::default()
+                })
+                .insert(RenderLayers::layer(SECOND_HUD_LAYER))
+                .insert(DebugText(second_window.car))
+                .insert(SecondReadout);
+        });
+    }
+}
+
+/// The margin, in pixels, between the second window's readout and its corner
+const SECOND_READOUT_MARGIN: f32 = 10.0;
+
+/// Keeps the second window's view on the second player's car, north up, at the main view's zoom
+fn follow_second_car(
+    second_window: Res<SecondWindow>,
+    pixels_per_meter: Res<PixelsPerMeter>,
+    cars: Query<(&CarNumber, &Transform), With<PlayerControlled>>,
+    mut cameras: Query<
+        (&mut Camera, &mut OrthographicProjection, &mut Transform),
+        (With<SecondCamera>, Without<PlayerControlled>),
+    >,
+    mut readouts: Query<
+        &mut Transform,
+        (
+            With<SecondReadout>,
+            Without<SecondCamera>,
+            Without<PlayerControlled>,
+        ),
+    >,
+) {
+    let car = cars
+        .iter()
+        .find(|(&number, _)| number == second_window.car)
+        .map(|(_, car)| car.translation.truncate());
+
+    for (mut camera, mut projection, mut transform) in cameras.iter_mut() {
+        if let Some(car) = car {
+            transform.translation = car.extend(transform.translation.z);
+        }
+
+        let scale = 1.0 / pixels_per_meter.0;
+
+        if projection.scale != scale {
+            projection.scale = scale;
+            camera.projection_matrix = projection.get_projection_matrix();
+        }
+
+        // The readout is a child of the camera, so it's placed in meters, and scaled back to
+        // pixels, from the top left corner of the view
+        let corner = Vec2::new(
+            projection.left + SECOND_READOUT_MARGIN,
+            projection.top - SECOND_READOUT_MARGIN,
+        );
+
+        for mut readout in readouts.iter_mut() {
+            readout.translation = (scale * corner).extend(Z_SECOND_READOUT);
+            readout.scale = Vec3::splat(scale);
+        }
+    }
+}
+
+/// How the main camera tracks the car
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CameraMode {
+    /// Looks at the origin, with north up
+    Fixed,
+    /// Centred on the car, with north up
+    Chase,
+    /// Centred on the car, turning with it so that it always points up the screen
+    HeadingUp,
+}
+
+impl Default for CameraMode {
+    fn default() -> Self {
+        Self::Fixed
+    }
+}
+
+impl CameraMode {
+    pub(crate) fn next(self) -> Self {
+        match self {
+            Self::Fixed => Self::Chase,
+            Self::Chase => Self::HeadingUp,
+            Self::HeadingUp => Self::Fixed,
+        }
+    }
+}
+
+/// Where the camera is looking from, and how far it is zoomed in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct CameraPose {
+    pub(crate) position: Vec2,
+    pub(crate) rotation: Quat,
+    pub(crate) pixels_per_meter: f32,
+}
+
+/// Eases the camera from its old view to its new one when switching between camera modes or
+/// resetting the camera, rather than snapping straight there
+pub struct CameraTransition {
+    /// How long each transition takes, in seconds; zero switches at once
+    pub duration: f32,
+    pub(crate) from: Option<CameraPose>,
+    pub(crate) elapsed: f32,
+}
+
+impl Default for CameraTransition {
+    fn default() -> Self {
+        Self {
+            duration: 0.4,
+            from: None,
+            elapsed: 0.0,
+        }
+    }
+}
+
+impl CameraTransition {
+    /// Starts afresh from wherever the camera is, even part of the way through another
+    /// transition, so that switching again quickly doesn't jump
+    pub(crate) fn start(&mut self, from: CameraPose) {
+        self.from = if self.duration > f32::EPSILON {
+            Some(from)
+        } else {
+            None
+        };
+        self.elapsed = 0.0;
+    }
+
+    pub(crate) fn advance(&mut self, dt_seconds: f32) {
+        self.elapsed += dt_seconds;
+
+        if self.elapsed >= self.duration {
+            self.from = None;
+        }
+    }
+
+    /// How far through the transition the camera is, easing in and out
+    fn progress(&self) -> f32 {
+        let t = (self.elapsed / self.duration.max(f32::EPSILON)).clamp(0.0, 1.0);
+
+        t * t * (3.0 - 2.0 * t)
+    }
+
+    pub(crate) fn blend(&self, to: CameraPose) -> CameraPose {
+        let from = match self.from {
+            Some(from) => from,
+            None => return to,
+        };
+
+        let progress = self.progress();
+
+        CameraPose {
+            position: from.position.lerp(to.position, progress),
+            rotation: from.rotation.slerp(to.rotation, progress),
+            pixels_per_meter: self.blend_pixels_per_meter(to.pixels_per_meter),
+        }
+    }
+
+    fn blend_pixels_per_meter(&self, to: f32) -> f32 {
+        self.from.map_or(to, |from| {
+            from.pixels_per_meter + (to - from.pixels_per_meter) * self.progress()
+        })
+    }
+}
+
+/// Keeps the camera's view within the world's bounds, when it has them, so that the space beyond
+/// the edges doesn't fill the screen near them
+pub struct CameraBounds {
+    pub enabled: bool,
+}
+
+impl Default for CameraBounds {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+impl CameraBounds {
+    /// Moves `position` so that a view of the given half size, in meters, turned by `rotation`,
+    /// stays within the bounds, or centres it if the view is wider than the world
+    pub(crate) fn clamp(
+        &self,
+        world_bounds: &WorldBounds,
+        position: Vec2,
+        rotation: Quat,
+        half_view: Vec2,
+    ) -> Vec2 {
+        let half_size = match world_bounds.half_size() {
+            Some(half_size) if self.enabled => half_size,
+            _ => return position,
+        };
+
+        // The turned view's extent along the world's axes
+        let across = (rotation * Vec3::new(half_view.x, 0.0, 0.0))
+            .truncate()
+            .abs();
+        let up = (rotation * Vec3::new(0.0, half_view.y, 0.0))
+            .truncate()
+            .abs();
+        let room = (half_size - (across + up)).max(Vec2::ZERO);
+
+        position.max(-room).min(room)
+    }
+}
+
+/// A tuning view for the following camera, which leads the car by its weight marker's offset,
+/// magnified, so that the car sits off centre towards wherever its load has moved
+pub struct WeightCamera {
+    pub enabled: bool,
+    pub toggle_key: KeyCode,
+    /// How many times further the car sits from the centre of the screen than its weight marker
+    /// sits from the centre of the car
+    pub gain: f32,
+}
+
+impl Default for WeightCamera {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            toggle_key: KeyCode::F6,
+            gain: 10.0,
+        }
+    }
+}
+
+/// A rectangle around the middle of the screen which the car can move about in without the
+/// following camera moving
+pub struct CameraDeadzone {
+    /// Half of the rectangle's size, in pixels
+    pub half_size: Vec2,
+    /// The time constant, in seconds, of the camera catching up once the car leaves the rectangle
+    pub catch_up_time: f32,
+    pub(crate) position: Option<Vec2>,
+}
+
+impl Default for CameraDeadzone {
+    fn default() -> Self {
+        Self {
+            half_size: Vec2::new(40.0, 30.0),
+            catch_up_time: 0.1,
+            position: None,
+        }
+    }
+}
+
+impl CameraDeadzone {
+    /// Where the camera should be to keep a car at `target` within the deadzone
+    fn follow(
+        &mut self,
+        dt_seconds: f32,
+        target: Vec2,
+        rotation: Quat,
+        pixels_per_meter: f32,
+    ) -> Vec2 {
+        let position = *self.position.get_or_insert(target);
+
+        // The deadzone is on screen, so measure the car's offset in the camera's own frame
+        let offset = (rotation.inverse() * (target - position).extend(0.0)).truncate();
+        let half_size = self.half_size / pixels_per_meter;
+        let excess = offset - offset.max(-half_size).min(half_size);
+        let excess = (rotation * excess.extend(0.0)).truncate();
+
+        let blend = if self.catch_up_time > f32::EPSILON {
+            dt_seconds / (self.catch_up_time + dt_seconds)
+        } else {
+            1.0
+        };
+
+        let position = position + excess * blend;
+
+        self.position = Some(position);
+
+        position
+    }
+}
+
+/// Moves the following camera's target ahead of the car, into the corner it's steering into and
+/// along the way it's moving, so that more of the road ahead is in view
+#[derive(Default)]
+pub struct CameraLookAhead {
+    /// How far to the side, in meters, at full steering lock
+    pub steer_distance: f32,
+    /// How far ahead along the car's velocity, in seconds of travel at its current speed
+    pub velocity_time: f32,
+}
+
+impl CameraLookAhead {
+    /// The offset from the car to the camera's target, no longer than `max_distance`
+    pub(crate) fn offset(&self, state: &CarState, max_distance: f32) -> Vec2 {
+        let heading = state.heading as f32;
+        let sideways = Vec2::new(-heading.sin(), heading.cos());
+
+        let offset = self.steer_distance * state.steer as f32 * sideways
+            + self.velocity_time * from_real(state.velocity);
+
+        if offset.length() > max_distance {
+            offset.normalize_or_zero() * max_distance.max(0.0)
+        } else {
+            offset
+        }
+    }
+}
+
+/// How the camera keeps several players' cars in view together, as Bevy 0.5 has no viewports to
+/// split the screen between them with
+pub struct CameraFraming {
+    /// The space, in meters, kept between the outermost cars and the edge of the view
+    pub margin: f32,
+    /// How far the view is pulled back beyond `PixelsPerMeter`, never less than 1
+    pub(crate) zoom_out: f32,
+}
+
+impl Default for CameraFraming {
+    fn default() -> Self {
+        Self {
+            margin: 5.0,
+            zoom_out: 1.0,
+        }
+    }
+}
+
+impl CameraFraming {
+    /// How far the view must be pulled back for it to reach `half_extent` around its centre,
+    /// from the `half_view` it reaches at the current zoom
+    pub(crate) fn zoom_out_to_fit(&self, half_extent: Vec2, half_view: Vec2) -> f32 {
+        let unzoomed_half_view = half_view / self.zoom_out;
+
+        ((half_extent + Vec2::splat(self.margin.max(0.0))) / unzoomed_half_view)
+            .max_element()
+            .max(1.0)
+    }
+}
+
+pub(crate) fn follow_car(
+    time: Res<Time>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut mode: ResMut<CameraMode>,
+    mut pixels_per_meter: ResMut<PixelsPerMeter>,
+    mut transition: ResMut<CameraTransition>,
+    mut last_pose: Local<Option<CameraPose>>,
+    mut deadzone: ResMut<CameraDeadzone>,
+    mut weight_camera: ResMut<WeightCamera>,
+    look_ahead: Res<CameraLookAhead>,
+    camera_bounds: Res<CameraBounds>,
+    world_bounds: Res<WorldBounds>,
+    replay: Res<Replay>,
+    mut framing: ResMut<CameraFraming>,
+    second_window: Option<Res<SecondWindow>>,
+    // The camera stays with the players, rather than the computer's cars
+    cars: Query<(&Transform, &CarComponents, &CarState, Option<&CarNumber>), Without<AiDriver>>,
+    weight_markers: Query<&WeightMarker>,
+    mut cameras: Query<
+        (&mut Transform, &OrthographicProjection),
+        (With<MainCamera>, Without<CarState>),
+    >,
+) {
+    let previous_view = (*mode, weight_camera.enabled, pixels_per_meter.0);
+
+    if keyboard_input.just_pressed(KeyCode::V) {
+        *mode = mode.next();
+    }
+
+    if keyboard_input.just_pressed(weight_camera.toggle_key) {
+        weight_camera.enabled = !weight_camera.enabled;
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Home) {
+        *mode = CameraMode::default();
+        *pixels_per_meter = PixelsPerMeter::default();
+    }
+
+    if (*mode, weight_camera.enabled, pixels_per_meter.0) != previous_view {
+        if let Some(pose) = *last_pose {
+            transition.start(pose);
+        }
+    } else if transition.from.is_some() {
+        transition.advance(time.delta_seconds());
+    }
+
+    // The replay flies the camera itself
+    if replay.active {
+        return;
+    }
+
+    // A player with a window of their own isn't followed in the main one
+    let players = cars
+        .iter()
+        .filter(|&(_, _, _, number)| match (&second_window, number) {
+            (Some(second_window), Some(&number)) => number != second_window.car,
+            _ => true,
+        })
+        .map(|(car, components, state, _)| (car, components, state))
+        .collect::<Vec<_>>();
+
+    let half_views = cameras
+        .iter_mut()
+        .map(|(_, projection)| {
+            0.5 * projection.scale
+                * Vec2::new(
+                    projection.right - projection.left,
+                    projection.top - projection.bottom,
+                )
+        })
+        .collect::<Vec<_>>();
+
+    // The look-ahead stops short of the edge of the smallest view, allowing for the deadzone, so
+    // that the car never leaves the frame
+    let max_look_ahead = half_views
+        .iter()
+        .map(|half_view| half_view.min_element())
+        .fold(f32::INFINITY, f32::min)
+        - deadzone.half_size.max_element() / pixels_per_meter.0;
+
+    let mut follow = |(car, components, state): (&Transform, &CarComponents, &CarState),
+                      rotation| {
+        let target = car.translation.truncate() + look_ahead.offset(state, max_look_ahead);
+
+        if !weight_camera.enabled {
+            return deadzone.follow(time.delta_seconds(), target, rotation, pixels_per_meter.0);
+        }
+
+        // The deadzone would swallow the very movements this view is for, so bypass it, but keep
+        // it up to date so that it carries on smoothly once the view is turned off
+        let marker = weight_markers
+            .get(components.weight_marker)
+            .map_or(Vec2::ZERO, |marker| marker.position);
+        let lead = (car.rotation * marker.extend(0.0)).truncate();
+        let position = target - weight_camera.gain * lead;
+
+        deadzone.position = Some(position);
+
+        position
+    };
+
+    let mut zoom_out = 1.0;
+
+    let (position, rotation) = match (*mode, players.as_slice()) {
+        // Several players are framed together, with north up, as no one car's heading suits them
+        // all
+        (CameraMode::Chase, [_, _, ..]) | (CameraMode::HeadingUp, [_, _, ..]) => {
+            let (min, max) = players.iter().fold(
+                (Vec2::splat(f32::INFINITY), Vec2::splat(f32::NEG_INFINITY)),
+                |(min, max), (car, _, _)| {
+                    let position = car.translation.truncate();
+
+                    (min.min(position), max.max(position))
+                },
+            );
+            let smallest_half_view = half_views
+                .iter()
+                .fold(Vec2::splat(f32::INFINITY), |smallest, &half_view| {
+                    smallest.min(half_view)
+                });
+
+            if smallest_half_view.min_element().is_finite() {
+                zoom_out = framing.zoom_out_to_fit(0.5 * (max - min), smallest_half_view);
+            }
+
+            let position = 0.5 * (min + max);
+
+            deadzone.position = Some(position);
+
+            (position, Quat::IDENTITY)
+        }
+        (CameraMode::Chase, [car, ..]) => {
+            let rotation = Quat::IDENTITY;
+
+            (follow(*car, rotation), rotation)
+        }
+        // The car points along its local x axis, so turn that to the screen's y axis
+        (CameraMode::HeadingUp, [car, ..]) => {
+            let rotation = car.0.rotation * Quat::from_rotation_z(-std::f32::consts::FRAC_PI_2);
+
+            (follow(*car, rotation), rotation)
+        }
+        _ => {
+            deadzone.position = None;
+
+            (Vec2::ZERO, Quat::IDENTITY)
+        }
+    };
+
+    // Only changed when it moves, as changing it rescales the cameras
+    if framing.zoom_out != zoom_out {
+        framing.zoom_out = zoom_out;
+    }
+
+    for ((mut camera, _), &half_view) in cameras.iter_mut().zip(half_views.iter()) {
+        let pose = transition.blend(CameraPose {
+            position: camera_bounds.clamp(&world_bounds, position, rotation, half_view),
+            rotation,
+            pixels_per_meter: pixels_per_meter.0,
+        });
+
+        camera.translation = pose.position.extend(camera.translation.z);
+        camera.rotation = pose.rotation;
+
+        *last_pose = Some(pose);
+    }
+}
+
+/// Shakes the camera while the tires skid, and for jolts such as impacts, dying down once the car
+/// settles
+pub struct CameraShake {
+    pub enabled: bool,
+    pub toggle_key: KeyCode,
+    /// The largest offset, in meters
+    pub max_offset: f32,
+    /// The intensity built up per second for each m/s of skidding tire slip
+    pub skid_gain: f32,
+    /// The time constant, in seconds, of the shake dying down
+    pub decay_time: f32,
+    /// The jolt for each m/s of speed lost against a wall
+    pub impact_gain: f32,
+    pub(crate) intensity: f32,
+}
+
+impl Default for CameraShake {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            toggle_key: KeyCode::F4,
+            max_offset: 0.15,
+            skid_gain: 0.05,
+            decay_time: 0.2,
+            impact_gain: 0.05,
+            intensity: 0.0,
+        }
+    }
+}
+
+impl CameraShake {
+    /// Adds a sudden jolt, from 0 to 1 for the strongest shake
+    pub fn jolt(&mut self, amount: f32) {
+        self.intensity = (self.intensity + amount).clamp(0.0, 1.0);
+    }
+
+    pub(crate) fn offset(&self, rng: &mut RngResource) -> Vec2 {
+        if self.intensity <= 0.0 {
+            return Vec2::ZERO;
+        }
+
+        // Drawn from the shared generator, so that a seeded run shakes the same way each time
+        let wobble = Vec2::new(rng.range(-1.0, 1.0), rng.range(-1.0, 1.0));
+
+        // Squaring keeps light skids subtle while heavy ones still shake noticeably
+        self.max_offset * self.intensity * self.intensity * wobble
+    }
+}
+
+pub(crate) fn shake_camera(
+    time: Res<Time>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut shake: ResMut<CameraShake>,
+    mut rng: ResMut<RngResource>,
+    replay: Res<Replay>,
+    mut wall_hits: EventReader<WallHit>,
+    tires: Query<&Tire>,
+    mut cameras: Query<&mut Transform, With<MainCamera>>,
+) {
+    if keyboard_input.just_pressed(shake.toggle_key) {
+        shake.enabled = !shake.enabled;
+    }
+
+    let dt_seconds = time.delta_seconds();
+
+    let skid_slip: f32 = tires
+        .iter()
+        .filter(|tire| tire.is_skidding)
+        .map(|tire| tire.slip_velocity.length())
+        .sum();
+
+    let blend = dt_seconds / (shake.decay_time.max(f32::EPSILON) + dt_seconds);
+    let built_up = shake.skid_gain * skid_slip * dt_seconds;
+
+    shake.intensity = (shake.intensity * (1.0 - blend) + built_up).clamp(0.0, 1.0);
+
+    for wall_hit in wall_hits.iter() {
+        let jolt = shake.impact_gain * wall_hit.speed;
+        shake.jolt(jolt);
+    }
+
+    // The replay flies the camera itself
+    if !shake.enabled || replay.active {
+        return;
+    }
+
+    let offset = shake.offset(&mut rng);
+
+    for mut camera in cameras.iter_mut() {
+        camera.translation += offset.extend(0.0);
+    }
+}
+
+pub(crate) fn apply_pixels_per_meter(
+    pixels_per_meter: Res<PixelsPerMeter>,
+    transition: Res<CameraTransition>,
+    framing: Res<CameraFraming>,
+    aspect_policy: Res<AspectPolicy>,
+    windows: Res<Windows>,
+    mut resized: EventReader<WindowResized>,
+    mut cameras: Query<(&mut Camera, &mut OrthographicProjection), With<MainCamera>>,
+    mut bars: Query<(&LetterboxBar, &mut Sprite, &mut Transform, &mut Visible)>,
+) {
+    let was_resized = resized.iter().count() > 0;
+
+    if !pixels_per_meter.is_changed()
+        && !transition.is_changed()
+        && !framing.is_changed()
+        && !aspect_policy.is_changed()
+        && !was_resized
+    {
+        return;
+    }
+
+    let window_size = match windows.get_primary() {
+        Some(window) => Vec2::new(window.width(), window.height()),
+        None => return,
+    };
+
+    let (zoom, view_size) = match *aspect_policy {
+        AspectPolicy::Stretch => (1.0, window_size),
+        AspectPolicy::Letterbox { width, height } => {
+            let zoom = (window_size.x / width).min(window_size.y / height);
+
+            (zoom, zoom * Vec2::new(width, height))
+        }
+    };
+
+    let pixels_per_meter = transition.blend_pixels_per_meter(pixels_per_meter.0);
+
+    let scale = framing.zoom_out / (pixels_per_meter * zoom);
+
+    for (mut camera, mut projection) in cameras.iter_mut() {
+        projection.scale = scale;
+        camera.projection_matrix = projection.get_projection_matrix();
+    }
+
+    // The bars are children of the camera, so they are placed in its own coordinates, in meters
+    let margin = window_size - view_size;
+    let is_pillarbox = margin.x > margin.y;
+
+    let (bar_size, bar_offset) = if is_pillarbox {
+        (
+            Vec2::new(0.5 * margin.x, window_size.y),
+            Vec2::new(0.5 * (view_size.x + 0.5 * margin.x), 0.0),
+        )
+    } else {
+        (
+            Vec2::new(window_size.x, 0.5 * margin.y),
+            Vec2::new(0.0, 0.5 * (view_size.y + 0.5 * margin.y)),
+        )
+    };
+
+    let show_bars = bar_size.min_element() >= 1.0;
+
+    for (bar, mut sprite, mut transform, mut visible) in bars.iter_mut() {
+        sprite.size = scale * bar_size;
+        transform.translation = (scale * bar.side * bar_offset).extend(Z_LETTERBOX);
+
+        if visible.is_visible != show_bars {
+            visible.is_visible = show_bars;
+        }
+    }
+}
@@ -0,0 +1,1708 @@
+//! Spawning cars with their parts and lights, reading the players' controls, and the scores,
+//! tests and aids each car carries
+
+use crate::{
+    effects::SmokeEmitter,
+    from_real,
+    hud::car_drift_angle,
+    physics::{
+        CarConfig, CarInputs, CarState, CarStats, DisplayedStats, LatestStats, LaunchControlPhase,
+        SimTime, SpunOut,
+    },
+    precision::{Real, RealMat2, RealVec2},
+    telemetry::Replay,
+    track::{AiDifficulty, AiDriver},
+    Z_CAR, Z_CAR_DEBUG, Z_CAR_LIGHTS, Z_CAR_PARTS,
+};
+use bevy::prelude::*;
+
+#[derive(Default)]
+pub(crate) struct WeightMarker {
+    pub(crate) position: Vec2,
+}
+
+/// The keys which drive a player's car
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlayerKeys {
+    pub throttle: KeyCode,
+    pub brake: KeyCode,
+    pub e_brake: KeyCode,
+    pub left: KeyCode,
+    pub right: KeyCode,
+}
+
+impl PlayerKeys {
+    pub fn arrows() -> Self {
+        Self {
+            throttle: KeyCode::Up,
+            brake: KeyCode::Down,
+            e_brake: KeyCode::Space,
+            left: KeyCode::Left,
+            right: KeyCode::Right,
+        }
+    }
+
+    pub fn ijkl() -> Self {
+        Self {
+            throttle: KeyCode::I,
+            brake: KeyCode::K,
+            e_brake: KeyCode::RShift,
+            left: KeyCode::J,
+            right: KeyCode::L,
+        }
+    }
+}
+
+/// Marks a car driven by the keyboard; any other car is driven by whatever sets its inputs
+pub struct PlayerControlled {
+    pub keys: PlayerKeys,
+}
+
+impl Default for PlayerControlled {
+    fn default() -> Self {
+        Self {
+            keys: PlayerKeys::arrows(),
+        }
+    }
+}
+
+/// Identifies a car from one run to the next, by which of the plugin's cars it is, so that a
+/// recording of its inputs finds it again
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct CarNumber(pub u32);
+
+/// Watches a car's heading turn away from the way it's going, so that a spin is noticed however it
+/// starts, whether or not it began as a scored drift
+#[derive(Debug)]
+pub(crate) struct SpinDetector {
+    /// How far, in radians, the car must turn away from the way it's going to have spun
+    spin_angle: f32,
+    /// Below this speed, in m/s, the car isn't going anywhere to turn away from
+    pub(crate) min_speed: f32,
+    /// Once the car points within this angle of the way it's going, it has recovered
+    recover_angle: f32,
+    last_drift_angle: Option<f32>,
+    pub(crate) turned: f32,
+    has_spun: bool,
+}
+
+impl Default for SpinDetector {
+    fn default() -> Self {
+        Self {
+            spin_angle: 0.75 * std::f32::consts::PI,
+            min_speed: 3.0,
+            recover_angle: 0.2,
+            last_drift_angle: None,
+            turned: 0.0,
+            has_spun: false,
+        }
+    }
+}
+
+impl SpinDetector {
+    /// Returns true if the car has just spun
+    pub(crate) fn update(&mut self, state: &CarState) -> bool {
+        let drift_angle = match car_drift_angle(state, self.min_speed) {
+            Some(drift_angle) => drift_angle,
+            None => {
+                *self = Self {
+                    last_drift_angle: None,
+                    turned: 0.0,
+                    has_spun: false,
+                    ..*self
+                };
+
+                return false;
+            }
+        };
+
+        // Followed through each step, so that turning on past a half turn keeps counting
+        if let Some(last_drift_angle) = self.last_drift_angle {
+            let pi = std::f32::consts::PI;
+
+            self.turned += (drift_angle - last_drift_angle + pi).rem_euclid(2.0 * pi) - pi;
+        }
+
+        self.last_drift_angle = Some(drift_angle);
+
+        if drift_angle.abs() < self.recover_angle {
+            self.turned = 0.0;
+            self.has_spun = false;
+        }
+
+        if !self.has_spun && self.turned.abs() > self.spin_angle {
+            self.has_spun = true;
+
+            return true;
+        }
+
+        false
+    }
+}
+
+/// A spin ends any drift the car was in
+pub(crate) fn score_spins(mut spun_out: EventReader<SpunOut>, mut cars: Query<&mut DriftScore>) {
+    for spin in spun_out.iter() {
+        if let Ok(mut drift_score) = cars.get_mut(spin.car) {
+            drift_score.spin_out();
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DriftState {
+    Idle,
+    Drifting,
+    Failed,
+}
+
+/// Distances travelled, in meters: in total, and since the trip meter was last reset
+#[derive(Debug, Default)]
+pub(crate) struct Odometer {
+    pub(crate) total: f32,
+    pub(crate) trip: f32,
+}
+
+impl Odometer {
+    pub(crate) fn update(&mut self, dt_seconds: f32, state: &CarState) {
+        let distance = state.velocity.length() as f32 * dt_seconds;
+
+        self.total += distance;
+        self.trip += distance;
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct DriftScore {
+    pub(crate) state: DriftState,
+    pub(crate) chain: f32,
+    pub(crate) total: f32,
+    pub(crate) min_speed: f32,
+    min_slip_angle: f32,
+    stop_speed: f32,
+}
+
+impl Default for DriftScore {
+    fn default() -> Self {
+        Self {
+            state: DriftState::Idle,
+            chain: 0.0,
+            total: 0.0,
+            min_speed: 8.0,
+            min_slip_angle: 0.15,
+            stop_speed: 1.0,
+        }
+    }
+}
+
+impl DriftScore {
+    /// Builds the chain while the car drifts, banking it once the drift ends cleanly, and losing
+    /// it if the car stops dead mid-drift
+    pub(crate) fn update(&mut self, dt_seconds: f32, stats: &CarStats) {
+        let speed = stats.speed_mps as f32;
+
+        let is_drifting = speed > self.min_speed
+            && stats.tires.iter().any(|tire| {
+                !tire.steered
+                    && tire.is_skidding
+                    && tire.slip_angle.abs() as f32 > self.min_slip_angle
+            });
+
+        match self.state {
+            DriftState::Idle => {
+                if is_drifting {
+                    self.state = DriftState::Drifting;
+                    self.chain = 0.0;
+                }
+            }
+            DriftState::Drifting => {
+                if speed < self.stop_speed {
+                    self.state = DriftState::Failed;
+                    self.chain = 0.0;
+                } else if is_drifting {
+                    self.chain += speed * dt_seconds;
+                } else {
+                    self.state = DriftState::Idle;
+                    self.total += self.chain;
+                    self.chain = 0.0;
+                }
+            }
+            DriftState::Failed => {
+                if !is_drifting {
+                    self.state = DriftState::Idle;
+                }
+            }
+        }
+    }
+
+    /// Loses the chain of a drift which ended in a spin
+    fn spin_out(&mut self) {
+        if self.state == DriftState::Drifting {
+            self.state = DriftState::Failed;
+            self.chain = 0.0;
+        }
+    }
+
+    fn reset(&mut self) {
+        self.state = DriftState::Idle;
+        self.chain = 0.0;
+        self.total = 0.0;
+    }
+}
+
+pub(crate) const PERF_TEST_SPEED_MPS: Real = 100.0 / 3.6;
+const PERF_TEST_TIMEOUT_SECONDS: f32 = 60.0;
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum PerfTestPhase {
+    Idle,
+    /// Full throttle from a standstill, timing how long it takes to reach the test speed
+    Accelerating {
+        elapsed: f32,
+    },
+    /// Full throttle from a standstill up to the test speed, before braking
+    RunUp {
+        elapsed: f32,
+    },
+    /// Full brakes from the test speed, measuring how far the car goes before stopping
+    Braking {
+        start: RealVec2,
+        elapsed: f32,
+    },
+}
+
+/// Scripted 0-100 kph and 100-0 kph runs
+#[derive(Debug)]
+pub(crate) struct PerfTest {
+    phase: PerfTestPhase,
+    pub(crate) acceleration_time: Option<f32>,
+    pub(crate) braking_distance: Option<f32>,
+}
+
+impl Default for PerfTest {
+    fn default() -> Self {
+        Self {
+            phase: PerfTestPhase::Idle,
+            acceleration_time: None,
+            braking_distance: None,
+        }
+    }
+}
+
+impl PerfTest {
+    fn is_running(&self) -> bool {
+        !matches!(self.phase, PerfTestPhase::Idle)
+    }
+
+    pub(crate) fn start(&mut self, phase: PerfTestPhase, state: &CarState) {
+        if !self.is_running() && state.velocity.length() < Real::EPSILON {
+            self.phase = phase;
+        }
+    }
+
+    pub(crate) fn inputs(&self) -> Option<CarInputs> {
+        match self.phase {
+            PerfTestPhase::Idle => None,
+            PerfTestPhase::Accelerating { .. } | PerfTestPhase::RunUp { .. } => Some(CarInputs {
+                throttle: 1.0,
+                ..CarInputs::default()
+            }),
+            PerfTestPhase::Braking { .. } => Some(CarInputs {
+                brake: 1.0,
+                ..CarInputs::default()
+            }),
+        }
+    }
+
+    pub(crate) fn update(&mut self, dt_seconds: f32, state: &CarState) {
+        let speed = state.velocity.length();
+
+        self.phase = match self.phase {
+            PerfTestPhase::Idle => PerfTestPhase::Idle,
+            PerfTestPhase::Accelerating { elapsed }
+            | PerfTestPhase::RunUp { elapsed }
+            | PerfTestPhase::Braking { elapsed, .. }
+                if elapsed > PERF_TEST_TIMEOUT_SECONDS =>
+            {
+                info!("Performance test timed out");
+                PerfTestPhase::Idle
+            }
+            PerfTestPhase::Accelerating { elapsed } => {
+                let elapsed = elapsed + dt_seconds;
+
+                if speed >= PERF_TEST_SPEED_MPS {
+                    info!("0-100 kph in {:.2} s", elapsed);
+                    self.acceleration_time = Some(elapsed);
+                    PerfTestPhase::Idle
+                } else {
+                    PerfTestPhase::Accelerating { elapsed }
+                }
+            }
+            PerfTestPhase::RunUp { elapsed } => {
+                if speed >= PERF_TEST_SPEED_MPS {
+                    PerfTestPhase::Braking {
+                        start: state.position,
+                        elapsed: 0.0,
+                    }
+                } else {
+                    PerfTestPhase::RunUp {
+                        elapsed: elapsed + dt_seconds,
+                    }
+                }
+            }
+            PerfTestPhase::Braking { start, elapsed } => {
+                if speed < Real::EPSILON {
+                    let distance = (state.position - start).length() as f32;
+                    info!("100-0 kph in {:.1} m", distance);
+                    self.braking_distance = Some(distance);
+                    PerfTestPhase::Idle
+                } else {
+                    PerfTestPhase::Braking {
+                        start,
+                        elapsed: elapsed + dt_seconds,
+                    }
+                }
+            }
+        };
+    }
+}
+
+/// The throttle, and past `CRUISE_CONTROL_BRAKE_MARGIN` the brake, applied per m/s off the target
+const CRUISE_CONTROL_GAIN: Real = 0.5;
+/// How far over the target speed, in m/s, the car may run before cruise control brakes, so that it
+/// doesn't brake against its own throttle
+const CRUISE_CONTROL_BRAKE_MARGIN: Real = 1.0;
+
+/// Holds the speed the car was going when it was engaged, until the driver brakes or resets
+#[derive(Debug, Default)]
+pub(crate) struct CruiseControl {
+    pub(crate) target: Option<Real>,
+}
+
+impl CruiseControl {
+    fn toggle(&mut self, state: &CarState) {
+        self.target = match self.target {
+            Some(_) => None,
+            None => Some(state.velocity.length()),
+        };
+    }
+
+    pub(crate) fn inputs(&self, inputs: CarInputs, state: &CarState) -> CarInputs {
+        let target = match self.target {
+            Some(target) => target,
+            None => return inputs,
+        };
+
+        let shortfall = target - state.velocity.length();
+        let throttle = (CRUISE_CONTROL_GAIN * shortfall).clamp(0.0, 1.0) as f32;
+        let brake = (CRUISE_CONTROL_GAIN * (-shortfall - CRUISE_CONTROL_BRAKE_MARGIN))
+            .clamp(0.0, 1.0) as f32;
+
+        CarInputs {
+            throttle: inputs.throttle.max(throttle),
+            brake: inputs.brake.max(brake),
+            ..inputs
+        }
+    }
+}
+
+pub(crate) struct PreviousGlobalTransform(pub(crate) GlobalTransform);
+
+/// Marks a tire which jumped this frame, so that it isn't joined to where it came from
+pub(crate) struct Teleported;
+
+pub(crate) struct Tire {
+    pub(crate) is_skidding: bool,
+    /// Locked wheels are drawn pointing straight ahead
+    pub(crate) is_locked: bool,
+    pub(crate) driven: bool,
+    pub(crate) slip: f32,
+    pub(crate) slip_velocity: Vec2,
+    pub(crate) load_ratio: f32,
+    /// The car's speed, in m/s
+    pub(crate) speed: f32,
+}
+
+pub(crate) struct SlipArrow {
+    tire: Entity,
+}
+
+/// Seconds of slip that each slip arrow's length represents
+const SLIP_ARROW_SCALE: f32 = 0.2;
+
+#[derive(Default)]
+pub(crate) struct SlipArrows {
+    pub(crate) visible: bool,
+}
+
+/// The colours of everything which shows the car's state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Palette {
+    Standard,
+    /// Colours which stay distinct with the common forms of colour blindness, and skidding tires
+    /// which stand out from the rest
+    HighContrast,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self::Standard
+    }
+}
+
+pub(crate) struct PaletteColors {
+    tire: Color,
+    skidding_tire: Color,
+    bumper: Color,
+    weight_marker: Color,
+    brake_light_off: Color,
+    brake_light_on: Color,
+    reverse_light_off: Color,
+    reverse_light_on: Color,
+    slip_arrow: Color,
+    obstacle: Color,
+}
+
+impl Palette {
+    pub(crate) fn colors(self) -> PaletteColors {
+        match self {
+            Self::Standard => PaletteColors {
+                tire: Color::BLACK,
+                skidding_tire: Color::BLACK,
+                bumper: Color::DARK_GRAY,
+                weight_marker: Color::PURPLE,
+                brake_light_off: Color::MAROON,
+                brake_light_on: Color::RED,
+                reverse_light_off: Color::GRAY,
+                reverse_light_on: Color::WHITE,
+                slip_arrow: Color::ORANGE,
+                obstacle: Color::ORANGE_RED,
+            },
+            Self::HighContrast => PaletteColors {
+                tire: Color::BLACK,
+                skidding_tire: Color::rgb(0.94, 0.89, 0.26),
+                bumper: Color::WHITE,
+                weight_marker: Color::rgb(0.0, 0.45, 0.7),
+                brake_light_off: Color::rgb(0.2, 0.2, 0.2),
+                brake_light_on: Color::rgb(0.9, 0.6, 0.0),
+                reverse_light_off: Color::rgb(0.2, 0.2, 0.2),
+                reverse_light_on: Color::WHITE,
+                slip_arrow: Color::rgb(0.34, 0.71, 0.91),
+                obstacle: Color::rgb(0.8, 0.47, 0.65),
+            },
+        }
+    }
+}
+
+/// A car's own colours, so that several cars can be told apart. Cars without one are drawn in the
+/// palette's colours.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Livery {
+    /// The colour of the bumpers
+    pub body: Color,
+    /// The colour of the tires, while they aren't skidding
+    pub accent: Color,
+}
+
+impl Livery {
+    fn from_palette(palette: Palette) -> Self {
+        let colors = palette.colors();
+
+        Self {
+            body: colors.bumper,
+            accent: colors.tire,
+        }
+    }
+
+    pub(crate) fn apply(&self, components: &CarComponents, materials: &mut Assets<ColorMaterial>) {
+        for &(handle, color) in &[
+            (&components.bumper_material, self.body),
+            (&components.tire_material, self.accent),
+        ] {
+            if let Some(material) = materials.get_mut(handle) {
+                material.color = color;
+            }
+        }
+    }
+}
+
+/// Materials shared by everything drawn in a palette colour, recoloured in place when the
+/// palette changes
+pub(crate) struct PaletteMaterials {
+    pub(crate) tire: Handle<ColorMaterial>,
+    skidding_tire: Handle<ColorMaterial>,
+    pub(crate) weight_marker: Handle<ColorMaterial>,
+    brake_light_off: Handle<ColorMaterial>,
+    brake_light_on: Handle<ColorMaterial>,
+    reverse_light_off: Handle<ColorMaterial>,
+    reverse_light_on: Handle<ColorMaterial>,
+    slip_arrow: Handle<ColorMaterial>,
+    pub(crate) obstacle: Handle<ColorMaterial>,
+}
+
+impl FromWorld for PaletteMaterials {
+    fn from_world(world: &mut World) -> Self {
+        let colors = world
+            .get_resource::<Palette>()
+            .copied()
+            .unwrap_or_default()
+            .colors();
+
+        let mut materials = world.get_resource_mut::<Assets<ColorMaterial>>().unwrap();
+        let mut add = |color| materials.add(ColorMaterial::color(color));
+
+        Self {
+            tire: add(colors.tire),
+            skidding_tire: add(colors.skidding_tire),
+            weight_marker: add(colors.weight_marker),
+            brake_light_off: add(colors.brake_light_off),
+            brake_light_on: add(colors.brake_light_on),
+            reverse_light_off: add(colors.reverse_light_off),
+            reverse_light_on: add(colors.reverse_light_on),
+            slip_arrow: add(colors.slip_arrow),
+            obstacle: add(colors.obstacle),
+        }
+    }
+}
+
+pub(crate) fn apply_palette(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut palette: ResMut<Palette>,
+    palette_materials: Res<PaletteMaterials>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    cars: Query<(&CarComponents, Option<&Livery>)>,
+    repainted_cars: Query<(&CarComponents, &Livery), Changed<Livery>>,
+    mut tires: Query<(&Tire, &mut Handle<ColorMaterial>)>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F2) {
+        *palette = match *palette {
+            Palette::Standard => Palette::HighContrast,
+            Palette::HighContrast => Palette::Standard,
+        };
+    }
+
+    if palette.is_changed() {
+        let colors = palette.colors();
+
+        for (handle, color) in vec![
+            (&palette_materials.tire, colors.tire),
+            (&palette_materials.skidding_tire, colors.skidding_tire),
+            (&palette_materials.weight_marker, colors.weight_marker),
+            (&palette_materials.brake_light_off, colors.brake_light_off),
+            (&palette_materials.brake_light_on, colors.brake_light_on),
+            (
+                &palette_materials.reverse_light_off,
+                colors.reverse_light_off,
+            ),
+            (&palette_materials.reverse_light_on, colors.reverse_light_on),
+            (&palette_materials.slip_arrow, colors.slip_arrow),
+            (&palette_materials.obstacle, colors.obstacle),
+        ] {
+            if let Some(material) = materials.get_mut(handle) {
+                material.color = color;
+            }
+        }
+
+        let livery = Livery::from_palette(*palette);
+
+        for (components, _) in cars.iter().filter(|(_, livery)| livery.is_none()) {
+            livery.apply(components, &mut materials);
+        }
+    }
+
+    for (components, livery) in repainted_cars.iter() {
+        livery.apply(components, &mut materials);
+    }
+
+    for (components, _) in cars.iter() {
+        for &tire in &components.tires {
+            if let Ok((tire, mut material)) = tires.get_mut(tire) {
+                let target = if tire.is_skidding {
+                    &palette_materials.skidding_tire
+                } else {
+                    &components.tire_material
+                };
+
+                if *material != *target {
+                    *material = target.clone();
+                }
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct SprayEmitter {
+    pub(crate) accumulator: f32,
+}
+
+pub(crate) struct CurrentSkid {
+    pub(crate) material: Handle<ColorMaterial>,
+    pub(crate) mesh: Option<Handle<Mesh>>,
+    pub(crate) length: f32,
+}
+
+#[derive(Bundle)]
+struct TireBundle {
+    #[bundle]
+    sprite: SpriteBundle,
+    tire: Tire,
+    spray: SprayEmitter,
+    smoke: SmokeEmitter,
+    skid: CurrentSkid,
+    previous_global_transform: PreviousGlobalTransform,
+}
+
+impl TireBundle {
+    pub(crate) fn new(
+        material: Handle<ColorMaterial>,
+        skid_material: Handle<ColorMaterial>,
+    ) -> Self {
+        Self {
+            sprite: SpriteBundle {
+                sprite: Sprite {
+                    size: Vec2::ONE,
+                    ..Default::default()
+                },
+                material,
+                ..Default::default()
+            },
+            tire: Tire {
+                is_skidding: false,
+                is_locked: false,
+                driven: false,
+                slip: 0.0,
+                slip_velocity: Vec2::ZERO,
+                load_ratio: 1.0,
+                speed: 0.0,
+            },
+            spray: SprayEmitter::default(),
+            smoke: SmokeEmitter::default(),
+            skid: CurrentSkid {
+                material: skid_material,
+                mesh: None,
+                length: 0.0,
+            },
+            previous_global_transform: PreviousGlobalTransform(GlobalTransform::default()),
+        }
+    }
+}
+
+pub(crate) struct Bumper;
+
+#[derive(Bundle)]
+struct BumperBundle {
+    #[bundle]
+    sprite: SpriteBundle,
+    bumper: Bumper,
+}
+
+impl BumperBundle {
+    pub(crate) fn new(material: Handle<ColorMaterial>) -> Self {
+        Self {
+            sprite: SpriteBundle {
+                sprite: Sprite {
+                    size: Vec2::ONE,
+                    ..Default::default()
+                },
+                material,
+                ..Default::default()
+            },
+            bumper: Bumper,
+        }
+    }
+}
+
+struct Bumpers {
+    front: Entity,
+    rear: Entity,
+}
+
+/// A light at the back of the car, either a brake light or a reverse light
+pub(crate) struct BrakeLight {
+    off: Handle<ColorMaterial>,
+    on: Handle<ColorMaterial>,
+}
+
+#[derive(Bundle)]
+struct BrakeLightBundle {
+    #[bundle]
+    sprite: SpriteBundle,
+    brake_light: BrakeLight,
+}
+
+impl BrakeLightBundle {
+    pub(crate) fn new(off: Handle<ColorMaterial>, on: Handle<ColorMaterial>) -> Self {
+        Self {
+            sprite: SpriteBundle {
+                sprite: Sprite {
+                    size: Vec2::new(0.15, 0.3),
+                    ..Default::default()
+                },
+                material: off.clone(),
+                ..Default::default()
+            },
+            brake_light: BrakeLight { off, on },
+        }
+    }
+}
+
+pub(crate) struct CarComponents {
+    /// The car's own materials, so that recolouring one car leaves the others alone
+    tire_material: Handle<ColorMaterial>,
+    bumper_material: Handle<ColorMaterial>,
+    pub(crate) tires: Vec<Entity>,
+    slip_arrows: Vec<Entity>,
+    bumpers: Bumpers,
+    brake_lights: [Entity; 2],
+    reverse_lights: [Entity; 2],
+    pub(crate) weight_marker: Entity,
+}
+
+#[derive(Bundle)]
+struct CarBundle {
+    config: Handle<CarConfig>,
+    config_source: ConfigSource,
+    components: CarComponents,
+    state: CarState,
+    inputs: CarInputs,
+    spawn_point: SpawnPoint,
+    pending_spawn: PendingSpawn,
+    drift_score: DriftScore,
+    spin_detector: SpinDetector,
+    perf_test: PerfTest,
+    cruise_control: CruiseControl,
+    odometer: Odometer,
+    latest_stats: LatestStats,
+    displayed_stats: DisplayedStats,
+    transform: Transform,
+    global_transform: GlobalTransform,
+}
+
+pub(crate) fn spawn_car(
+    mut commands: Commands,
+    palette: Res<Palette>,
+    palette_materials: Res<PaletteMaterials>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    asset_server: Res<AssetServer>,
+) {
+    spawn_player_car(
+        &mut commands,
+        CarPaints {
+            palette: *palette,
+            palette_materials: &palette_materials,
+            materials: &mut materials,
+        },
+        asset_server.load("config.car"),
+        PlayerKeys::arrows(),
+        CarNumber(0),
+        None,
+        Vec2::ZERO,
+    );
+}
+
+/// A second car, alongside the first, driven by "I", "J", "K", "L" and right shift
+pub(crate) fn spawn_second_car(
+    mut commands: Commands,
+    palette: Res<Palette>,
+    palette_materials: Res<PaletteMaterials>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    asset_server: Res<AssetServer>,
+) {
+    spawn_player_car(
+        &mut commands,
+        CarPaints {
+            palette: *palette,
+            palette_materials: &palette_materials,
+            materials: &mut materials,
+        },
+        asset_server.load("config.car"),
+        PlayerKeys::ijkl(),
+        CarNumber(1),
+        Some(Livery {
+            body: Color::rgb(0.15, 0.35, 0.85),
+            accent: Color::rgb(0.1, 0.15, 0.35),
+        }),
+        Vec2::new(0.0, 5.0),
+    );
+}
+
+/// A car driven round the gates by the computer
+pub(crate) fn spawn_ai_car(
+    mut commands: Commands,
+    palette: Res<Palette>,
+    palette_materials: Res<PaletteMaterials>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    asset_server: Res<AssetServer>,
+) {
+    let car = spawn_car_entity(
+        &mut commands,
+        CarPaints {
+            palette: *palette,
+            palette_materials: &palette_materials,
+            materials: &mut materials,
+        },
+        asset_server.load("config.car"),
+        CarNumber(2),
+        Some(Livery {
+            body: Color::rgb(0.85, 0.25, 0.15),
+            accent: Color::rgb(0.35, 0.1, 0.05),
+        }),
+        Vec2::new(0.0, -5.0),
+    );
+
+    commands
+        .entity(car)
+        .insert(AiDriver::new(AiDifficulty::default()));
+}
+
+/// What a newly spawned car is drawn with
+struct CarPaints<'a> {
+    palette: Palette,
+    palette_materials: &'a PaletteMaterials,
+    materials: &'a mut Assets<ColorMaterial>,
+}
+
+/// Spawns a car driven by `keys`, offset from its config's initial position by `offset`, in its
+/// own frame
+fn spawn_player_car(
+    commands: &mut Commands,
+    paints: CarPaints,
+    config: Handle<CarConfig>,
+    keys: PlayerKeys,
+    number: CarNumber,
+    livery: Option<Livery>,
+    offset: Vec2,
+) {
+    let car = spawn_car_entity(commands, paints, config, number, livery, offset);
+
+    commands.entity(car).insert(PlayerControlled { keys });
+}
+
+/// Spawns a car, with nothing yet driving it, in its `livery` or else the palette's colours
+fn spawn_car_entity(
+    commands: &mut Commands,
+    paints: CarPaints,
+    config: Handle<CarConfig>,
+    number: CarNumber,
+    livery: Option<Livery>,
+    offset: Vec2,
+) -> Entity {
+    let CarPaints {
+        palette,
+        palette_materials,
+        materials,
+    } = paints;
+
+    let colors = livery.unwrap_or_else(|| Livery::from_palette(palette));
+
+    let tire_material = materials.add(ColorMaterial::color(colors.accent));
+
+    let bumper_material = materials.add(ColorMaterial::color(colors.body));
+
+    let front_bumper = commands
+        .spawn_bundle(BumperBundle::new(bumper_material.clone()))
+        .id();
+
+    let rear_bumper = commands
+        .spawn_bundle(BumperBundle::new(bumper_material.clone()))
+        .id();
+
+    let bumpers = Bumpers {
+        front: front_bumper,
+        rear: rear_bumper,
+    };
+
+    let mut spawn_light = |off: &Handle<ColorMaterial>, on: &Handle<ColorMaterial>| {
+        commands
+            .spawn_bundle(BrakeLightBundle::new(off.clone(), on.clone()))
+            .id()
+    };
+
+    let brake_lights = [
+        spawn_light(
+            &palette_materials.brake_light_off,
+            &palette_materials.brake_light_on,
+        ),
+        spawn_light(
+            &palette_materials.brake_light_off,
+            &palette_materials.brake_light_on,
+        ),
+    ];
+
+    let reverse_lights = [
+        spawn_light(
+            &palette_materials.reverse_light_off,
+            &palette_materials.reverse_light_on,
+        ),
+        spawn_light(
+            &palette_materials.reverse_light_off,
+            &palette_materials.reverse_light_on,
+        ),
+    ];
+
+    let weight_marker = commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                size: 0.5 * Vec2::ONE,
+                ..Default::default()
+            },
+            material: palette_materials.weight_marker.clone(),
+            ..Default::default()
+        })
+        .insert(WeightMarker::default())
+        .id();
+
+    let mut car = commands.spawn_bundle(CarBundle {
+        config: config.clone(),
+        config_source: ConfigSource::new(config),
+        components: CarComponents {
+            tire_material,
+            bumper_material,
+            tires: Vec::new(),
+            slip_arrows: Vec::new(),
+            bumpers,
+            brake_lights,
+            reverse_lights,
+            weight_marker,
+        },
+        state: CarState::default(),
+        inputs: CarInputs::default(),
+        spawn_point: SpawnPoint { offset },
+        pending_spawn: PendingSpawn,
+        drift_score: DriftScore::default(),
+        spin_detector: SpinDetector::default(),
+        perf_test: PerfTest::default(),
+        cruise_control: CruiseControl::default(),
+        odometer: Odometer::default(),
+        latest_stats: LatestStats::default(),
+        displayed_stats: DisplayedStats::default(),
+        transform: Transform::default(),
+        global_transform: GlobalTransform::default(),
+    });
+
+    car.insert(number)
+        .push_children(&[front_bumper, rear_bumper, weight_marker])
+        .push_children(&brake_lights)
+        .push_children(&reverse_lights);
+
+    if let Some(livery) = livery {
+        car.insert(livery);
+    }
+
+    car.id()
+}
+
+/// Where a car starts, and goes back to when it's reset: its config's initial position, moved by
+/// `offset` along its initial heading so that several cars don't start on top of each other
+pub(crate) struct SpawnPoint {
+    pub(crate) offset: Vec2,
+}
+
+/// Marks a car to be put at its spawn point once its config has loaded, when it's first spawned
+/// and whenever it's reset
+pub(crate) struct PendingSpawn;
+
+/// When a hot-reloaded car config reaches the car
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigReloadPolicy {
+    Immediate,
+    /// Hold a reload back until the car is slower than `max_speed`, in m/s, or is reset
+    WhenSlow {
+        max_speed: f32,
+    },
+}
+
+impl Default for ConfigReloadPolicy {
+    fn default() -> Self {
+        Self::Immediate
+    }
+}
+
+/// The config file a car was spawned from
+pub(crate) struct ConfigSource {
+    pub(crate) handle: Handle<CarConfig>,
+    pub(crate) pending: bool,
+    was_reset: bool,
+}
+
+impl ConfigSource {
+    pub(crate) fn new(handle: Handle<CarConfig>) -> Self {
+        Self {
+            handle,
+            pending: false,
+            was_reset: false,
+        }
+    }
+}
+
+pub(crate) fn apply_config_reloads(
+    policy: Res<ConfigReloadPolicy>,
+    mut events: EventReader<AssetEvent<CarConfig>>,
+    mut configs: ResMut<Assets<CarConfig>>,
+    mut cars: Query<(&mut Handle<CarConfig>, &mut ConfigSource, &CarState)>,
+) {
+    let modified = events
+        .iter()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { handle } => Some(handle.clone()),
+            AssetEvent::Created { .. } | AssetEvent::Removed { .. } => None,
+        })
+        .collect::<Vec<_>>();
+
+    for (mut config, mut source, state) in cars.iter_mut() {
+        let was_reset = std::mem::replace(&mut source.was_reset, false);
+
+        let max_speed = match *policy {
+            ConfigReloadPolicy::Immediate => {
+                if *config != source.handle {
+                    *config = source.handle.clone();
+                }
+
+                source.pending = false;
+
+                continue;
+            }
+            ConfigReloadPolicy::WhenSlow { max_speed } => max_speed,
+        };
+
+        // The car runs on its own copy of the file's config, which reloads don't reach until
+        // they are applied
+        if *config == source.handle {
+            let copy = match configs.get(source.handle.clone()) {
+                Some(copy) => copy.clone(),
+                None => continue,
+            };
+
+            *config = configs.add(copy);
+        }
+
+        if modified.contains(&source.handle) {
+            source.pending = true;
+        }
+
+        if !source.pending || !(was_reset || state.velocity.length() < max_speed as Real) {
+            continue;
+        }
+
+        if let Some(reloaded) = configs.get(source.handle.clone()).cloned() {
+            if let Some(config) = configs.get_mut(config.clone()) {
+                *config = reloaded;
+            }
+        }
+
+        source.pending = false;
+    }
+}
+
+/// Resolves the axles of configs added or changed elsewhere, such as by an embedding app
+pub(crate) fn resolve_car_configs(
+    mut events: EventReader<AssetEvent<CarConfig>>,
+    mut configs: ResMut<Assets<CarConfig>>,
+) {
+    for handle in events.iter().filter_map(|event| match event {
+        AssetEvent::Created { handle } | AssetEvent::Modified { handle } => Some(handle),
+        AssetEvent::Removed { .. } => None,
+    }) {
+        if configs
+            .get(handle)
+            .map_or(false, CarConfig::needs_resolving)
+        {
+            if let Some(config) = configs.get_mut(handle) {
+                config.resolve();
+            }
+        }
+    }
+}
+
+pub(crate) fn apply_initial_state(
+    mut commands: Commands,
+    configs: Res<Assets<CarConfig>>,
+    mut cars: Query<
+        (
+            Entity,
+            &Handle<CarConfig>,
+            &SpawnPoint,
+            &CarComponents,
+            &mut CarState,
+            &mut Transform,
+        ),
+        With<PendingSpawn>,
+    >,
+) {
+    for (car, config, spawn_point, components, mut state, mut transform) in cars.iter_mut() {
+        let config = match configs.get(config.clone()) {
+            Some(config) => config,
+            None => continue,
+        };
+
+        *state = CarState::spawned(config, spawn_point);
+
+        transform.translation = from_real(state.position).extend(Z_CAR);
+        transform.rotation = Quat::from_rotation_z(state.heading as f32);
+
+        // A car that's reset jumps back here, so its skids mustn't follow it
+        for &tire in components.tires.iter() {
+            commands.entity(tire).insert(Teleported);
+        }
+
+        commands.entity(car).remove::<PendingSpawn>();
+    }
+}
+
+pub(crate) fn spawn_tires(
+    mut commands: Commands,
+    configs: Res<Assets<CarConfig>>,
+    palette_materials: Res<PaletteMaterials>,
+    mut cars: Query<(Entity, &Handle<CarConfig>, &mut CarComponents)>,
+) {
+    for (car, config, mut components) in cars.iter_mut() {
+        let config = match configs.get(config.clone()) {
+            Some(config) => config,
+            None => continue,
+        };
+
+        let tire_count = config.tire_count();
+
+        if components.tires.len() == tire_count {
+            continue;
+        }
+
+        for tire in components.tires.drain(..) {
+            commands.entity(tire).despawn();
+        }
+
+        for arrow in components.slip_arrows.drain(..) {
+            commands.entity(arrow).despawn();
+        }
+
+        let tire_material = components.tire_material.clone();
+
+        components.tires = (0..tire_count)
+            .map(|_| {
+                commands
+                    .spawn_bundle(TireBundle::new(
+                        tire_material.clone(),
+                        palette_materials.tire.clone(),
+                    ))
+                    .id()
+            })
+            .collect();
+
+        components.slip_arrows = components
+            .tires
+            .iter()
+            .map(|&tire| {
+                commands
+                    .spawn_bundle(SpriteBundle {
+                        sprite: Sprite {
+                            size: Vec2::ONE,
+                            ..Default::default()
+                        },
+                        material: palette_materials.slip_arrow.clone(),
+                        visible: Visible {
+                            is_visible: false,
+                            is_transparent: false,
+                        },
+                        ..Default::default()
+                    })
+                    .insert(SlipArrow { tire })
+                    .id()
+            })
+            .collect();
+
+        commands
+            .entity(car)
+            .push_children(&components.tires)
+            .push_children(&components.slip_arrows);
+    }
+}
+
+/// Resets the cars, as if "R" had been pressed, once they have sat almost still for a while, such
+/// as when they are stuck against something
+pub struct StuckReset {
+    pub enabled: bool,
+    /// Below this speed, in m/s, a car counts as stuck
+    pub max_speed: f32,
+    /// How long, in seconds, the cars must be stuck for before they are reset
+    pub timeout: f32,
+    /// Whether any car has moved since the last reset, so that cars waiting where they were reset
+    /// aren't reset over and over
+    moved: bool,
+    stuck_seconds: f32,
+}
+
+impl Default for StuckReset {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_speed: 0.5,
+            timeout: 5.0,
+            moved: false,
+            stuck_seconds: 0.0,
+        }
+    }
+}
+
+impl StuckReset {
+    pub(crate) fn is_due(&self) -> bool {
+        self.enabled && self.stuck_seconds >= self.timeout
+    }
+
+    /// The seconds left before the reset, once the cars are stuck
+    fn countdown(&self) -> Option<f32> {
+        if self.enabled && self.stuck_seconds > 0.0 {
+            Some((self.timeout - self.stuck_seconds).max(0.0))
+        } else {
+            None
+        }
+    }
+}
+
+pub(crate) struct StuckText;
+
+/// Times how long every car has been stuck for; a reset that falls due is carried out by the input
+/// systems on the following frame, and the timer starts over after that
+pub(crate) fn track_stuck_cars(
+    time: Res<SimTime>,
+    replay: Res<Replay>,
+    mut stuck_reset: ResMut<StuckReset>,
+    cars: Query<&CarState>,
+    mut text: Query<&mut Text, With<StuckText>>,
+) {
+    if !stuck_reset.enabled || replay.active || stuck_reset.is_due() {
+        stuck_reset.moved = false;
+        stuck_reset.stuck_seconds = 0.0;
+    } else {
+        let max_speed = stuck_reset.max_speed as Real;
+        let is_stuck = cars.iter().all(|state| state.velocity.length() < max_speed);
+
+        if !is_stuck {
+            stuck_reset.moved = true;
+            stuck_reset.stuck_seconds = 0.0;
+        } else if stuck_reset.moved {
+            stuck_reset.stuck_seconds += time.delta_seconds();
+        }
+    }
+
+    for mut text in text.iter_mut() {
+        let value = stuck_reset
+            .countdown()
+            .map_or_else(String::new, |countdown| {
+                format!("Stuck: resetting in {:.0}", countdown.ceil())
+            });
+
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+}
+
+/// A driver's aid for repeatable standing starts: hold the arm key at a standstill to load up the
+/// clutch, then let it go to launch, with the drive kept within the tires' grip
+pub struct LaunchControl {
+    pub enabled: bool,
+    pub arm_key: KeyCode,
+    /// How far in the clutch is while armed, from nothing to fully in
+    pub preload: f32,
+    /// The fraction of the driven tires' grip that the drive is kept within while launching
+    pub grip_ratio: f32,
+    /// Below this speed, in m/s, the car counts as standing still and can be armed
+    pub standstill_speed: f32,
+    /// Above this speed, in m/s, the launch is over and the drive is no longer limited
+    pub until_speed: f32,
+    armed: bool,
+}
+
+impl LaunchControl {
+    /// Whether any car is armed, ready to launch
+    pub fn is_armed(&self) -> bool {
+        self.armed
+    }
+}
+
+impl Default for LaunchControl {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            arm_key: KeyCode::LShift,
+            preload: 0.8,
+            grip_ratio: 0.9,
+            standstill_speed: 0.5,
+            until_speed: 15.0,
+            armed: false,
+        }
+    }
+}
+
+pub(crate) fn launch_control(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut launch_control: ResMut<LaunchControl>,
+    mut cars: Query<&mut CarState, With<PlayerControlled>>,
+) {
+    let mut armed = false;
+
+    for mut state in cars.iter_mut() {
+        let is_held = launch_control.enabled && keyboard_input.pressed(launch_control.arm_key);
+        let is_standing = state.velocity.length() < launch_control.standstill_speed as Real;
+
+        state.launch_control = match (state.launch_control, is_held) {
+            (LaunchControlPhase::Armed { .. }, false) => LaunchControlPhase::Launching {
+                grip_ratio: launch_control.grip_ratio,
+                until_speed: launch_control.until_speed,
+            },
+            (LaunchControlPhase::Launching { .. }, _) if launch_control.enabled => {
+                state.launch_control
+            }
+            (_, true) if is_standing => LaunchControlPhase::Armed {
+                preload: launch_control.preload,
+            },
+            _ => LaunchControlPhase::Off,
+        };
+
+        armed |= matches!(state.launch_control, LaunchControlPhase::Armed { .. });
+    }
+
+    launch_control.armed = armed;
+}
+
+pub(crate) fn read_player_inputs(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    replay: Res<Replay>,
+    stuck_reset: Res<StuckReset>,
+    mut cars: Query<(
+        &PlayerControlled,
+        &mut CarInputs,
+        &mut CarState,
+        &mut DriftScore,
+        &mut PerfTest,
+        &mut CruiseControl,
+        &mut Odometer,
+        Option<&mut ConfigSource>,
+        Entity,
+    )>,
+) {
+    // The controls drive the replay instead while one is playing
+    if replay.active {
+        return;
+    }
+
+    let input = |code: KeyCode| {
+        if keyboard_input.pressed(code) {
+            1.0
+        } else {
+            0.0
+        }
+    };
+
+    for (
+        player,
+        mut inputs,
+        mut state,
+        mut drift_score,
+        mut perf_test,
+        mut cruise_control,
+        mut odometer,
+        config_source,
+        car,
+    ) in cars.iter_mut()
+    {
+        let keys = &player.keys;
+
+        *inputs = CarInputs {
+            throttle: input(keys.throttle),
+            brake: input(keys.brake),
+            e_brake: input(keys.e_brake),
+            steer: input(keys.left) - input(keys.right),
+        };
+
+        if keyboard_input.just_pressed(KeyCode::Q) {
+            let quarter_turn = std::f64::consts::FRAC_PI_2 as Real;
+            let heading = (state.heading / quarter_turn).round() * quarter_turn;
+
+            state.snap_heading(heading);
+        }
+
+        if keyboard_input.just_pressed(KeyCode::W) && state.velocity.length() > Real::EPSILON {
+            let heading = state.velocity.y.atan2(state.velocity.x);
+
+            state.snap_heading(heading);
+        }
+
+        if keyboard_input.just_pressed(KeyCode::X) {
+            cruise_control.toggle(&state);
+        }
+
+        if inputs.brake > 0.0 {
+            cruise_control.target = None;
+        }
+
+        if keyboard_input.just_pressed(KeyCode::Key1) {
+            perf_test.start(PerfTestPhase::Accelerating { elapsed: 0.0 }, &state);
+        }
+
+        if keyboard_input.just_pressed(KeyCode::Key2) {
+            perf_test.start(PerfTestPhase::RunUp { elapsed: 0.0 }, &state);
+        }
+
+        if keyboard_input.pressed(KeyCode::R) || stuck_reset.is_due() {
+            // Put back at its spawn point as soon as its config allows, just as it first was
+            commands.entity(car).insert(PendingSpawn);
+
+            drift_score.reset();
+            cruise_control.target = None;
+            odometer.trip = 0.0;
+
+            if let Some(mut config_source) = config_source {
+                config_source.was_reset = true;
+            }
+        }
+    }
+}
+
+pub(crate) fn place_weight_marker(mut query: Query<(&WeightMarker, &mut Transform)>) {
+    for (marker, mut transform) in query.iter_mut() {
+        transform.translation = marker.position.extend(Z_CAR_PARTS);
+    }
+}
+
+pub(crate) fn place_tires(
+    configs: ResMut<Assets<CarConfig>>,
+    car: Query<(&Handle<CarConfig>, &CarComponents, &CarState)>,
+    mut tires: Query<(&mut Transform, &Tire)>,
+) {
+    for (config, components, state) in car.iter() {
+        let config = match configs.get(config.clone()) {
+            Some(config) => config,
+            None => continue,
+        };
+
+        let axles = config.axles();
+        let tire_positions = axles.iter().flat_map(|axle| {
+            let steer_angle = config.axle_steer_angle(axle, state.steer_angle) as f32;
+            let position = axle.position;
+
+            axle.lateral_offsets()
+                .map(move |lateral_offset| (position, lateral_offset, steer_angle))
+        });
+
+        for (&tire, (position, lateral_offset, steer_angle)) in
+            components.tires.iter().zip(tire_positions)
+        {
+            let (mut tire, &Tire { is_locked, .. }) = match tires.get_mut(tire) {
+                Ok(tire) => tire,
+                Err(_) => continue,
+            };
+
+            tire.translation = Vec3::new(position, lateral_offset, Z_CAR_PARTS);
+
+            tire.rotation = Quat::from_rotation_z(if is_locked { 0.0 } else { steer_angle });
+
+            tire.scale = Vec3::new(2.0 * config.wheel_radius, config.wheel_width, 1.0);
+        }
+    }
+}
+
+pub(crate) fn place_bumpers(
+    configs: ResMut<Assets<CarConfig>>,
+    car: Query<(&Handle<CarConfig>, &CarComponents)>,
+    mut bumpers: Query<&mut Transform, With<Bumper>>,
+) {
+    for (config, components) in car.iter() {
+        let config = match configs.get(config.clone()) {
+            Some(config) => config,
+            None => continue,
+        };
+
+        {
+            let mut bumper = bumpers.get_mut(components.bumpers.front).unwrap();
+            bumper.translation = Vec3::new(config.centre_of_gravity_to_front, 0.0, Z_CAR_PARTS);
+            bumper.scale = Vec3::new(0.1, 2.0 * config.half_width, 1.0);
+        }
+
+        {
+            let mut bumper = bumpers.get_mut(components.bumpers.rear).unwrap();
+            bumper.translation = Vec3::new(-config.centre_of_gravity_to_rear, 0.0, Z_CAR_PARTS);
+            bumper.scale = Vec3::new(0.1, 2.0 * config.half_width, 1.0);
+        }
+    }
+}
+
+/// The backwards speed, in m/s, above which the reverse lights come on, so they don't flicker as
+/// the car settles
+const REVERSE_LIGHT_SPEED: Real = 0.1;
+
+fn is_reversing(state: &CarState) -> bool {
+    let local_velocity = RealMat2::from_angle(-state.heading) * state.velocity;
+
+    local_velocity.x < -REVERSE_LIGHT_SPEED
+}
+
+/// Beeps every so often while a player's car rolls backwards, along with its reverse lights
+pub struct ReversingBeep {
+    pub enabled: bool,
+    /// The beep's path within the assets folder
+    pub path: String,
+    /// The time, in seconds, from one beep to the next
+    pub interval: f32,
+    since_beep: Option<f32>,
+}
+
+impl Default for ReversingBeep {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: "sounds/reversing_beep.wav".to_string(),
+            interval: 0.8,
+            since_beep: None,
+        }
+    }
+}
+
+pub(crate) fn beep_while_reversing(
+    time: Res<SimTime>,
+    asset_server: Res<AssetServer>,
+    audio: Res<Audio>,
+    mut beep: ResMut<ReversingBeep>,
+    cars: Query<&CarState, With<PlayerControlled>>,
+) {
+    if !beep.enabled || !cars.iter().any(is_reversing) {
+        beep.since_beep = None;
+        return;
+    }
+
+    // The first beep sounds as soon as the car starts rolling backwards
+    let since_beep = beep.since_beep.map_or(f32::INFINITY, |since_beep| {
+        since_beep + time.delta_seconds()
+    });
+
+    if since_beep >= beep.interval {
+        audio.play(asset_server.load(beep.path.as_str()));
+        beep.since_beep = Some(0.0);
+    } else {
+        beep.since_beep = Some(since_beep);
+    }
+}
+
+pub(crate) fn place_brake_lights(
+    configs: ResMut<Assets<CarConfig>>,
+    car: Query<(&Handle<CarConfig>, &CarInputs, &CarState, &CarComponents)>,
+    mut brake_lights: Query<(&BrakeLight, &mut Transform, &mut Handle<ColorMaterial>)>,
+) {
+    for (config, inputs, state, components) in car.iter() {
+        let config = match configs.get(config.clone()) {
+            Some(config) => config,
+            None => continue,
+        };
+
+        let is_braking = inputs.brake > 0.0 || inputs.e_brake > 0.0;
+        let is_reversing = is_reversing(state);
+
+        // The brake lights are just inside the corners of the rear bumper, with the reverse
+        // lights inside them, all drawn over the bumper
+        let lights = components
+            .brake_lights
+            .iter()
+            .map(|light| (light, 0.2, is_braking))
+            .chain(
+                components
+                    .reverse_lights
+                    .iter()
+                    .map(|light| (light, 0.5, is_reversing)),
+            );
+
+        for ((&light, inset, is_on), side) in lights.zip([1.0, -1.0].iter().cycle()) {
+            let (brake_light, mut transform, mut material) = brake_lights.get_mut(light).unwrap();
+
+            transform.translation = Vec3::new(
+                -config.centre_of_gravity_to_rear,
+                side * (config.half_width - inset),
+                Z_CAR_LIGHTS,
+            );
+
+            let target = if is_on {
+                &brake_light.on
+            } else {
+                &brake_light.off
+            };
+
+            if *material != *target {
+                *material = target.clone();
+            }
+        }
+    }
+}
+
+pub(crate) fn place_slip_arrows(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut settings: ResMut<SlipArrows>,
+    tires: Query<(&Tire, &Transform)>,
+    mut arrows: Query<(&SlipArrow, &mut Transform, &mut Visible), Without<Tire>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::S) {
+        settings.visible = !settings.visible;
+    }
+
+    for (arrow, mut transform, mut visible) in arrows.iter_mut() {
+        if visible.is_visible != settings.visible {
+            visible.is_visible = settings.visible;
+        }
+
+        if !settings.visible {
+            continue;
+        }
+
+        let (tire, tire_transform) = match tires.get(arrow.tire) {
+            Ok(tire) => tire,
+            Err(_) => continue,
+        };
+
+        // A unit sprite stretched from the tire's centre along its slip
+        let arrow_vector = SLIP_ARROW_SCALE * tire.slip_velocity;
+
+        transform.translation =
+            (tire_transform.translation.truncate() + 0.5 * arrow_vector).extend(Z_CAR_DEBUG);
+        transform.rotation = Quat::from_rotation_z(arrow_vector.y.atan2(arrow_vector.x));
+        transform.scale = Vec3::new(arrow_vector.length(), 0.1, 1.0);
+    }
+}
+
+fn init_previous_global_transform(
+    mut query: Query<
+        (&mut PreviousGlobalTransform, &GlobalTransform),
+        Added<PreviousGlobalTransform>,
+    >,
+) {
+    for (mut previous, &current) in query.iter_mut() {
+        previous.0 = current;
+    }
+}
+
+fn reset_teleported_tires(
+    mut commands: Commands,
+    mut query: Query<
+        (
+            Entity,
+            &mut PreviousGlobalTransform,
+            &mut CurrentSkid,
+            &GlobalTransform,
+        ),
+        With<Teleported>,
+    >,
+) {
+    for (tire, mut previous, mut skid, &current) in query.iter_mut() {
+        previous.0 = current;
+        skid.mesh = None;
+
+        commands.entity(tire).remove::<Teleported>();
+    }
+}
+
+fn update_previous_global_transform(
+    mut query: Query<(&mut PreviousGlobalTransform, &GlobalTransform)>,
+) {
+    for (mut previous, &current) in query.iter_mut() {
+        previous.0 = current;
+    }
+}
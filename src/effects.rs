@@ -0,0 +1,389 @@
+//! Spray and tire smoke particles, and the random numbers behind them
+
+use crate::{
+    car::{PreviousGlobalTransform, SprayEmitter, StuckReset, Tire},
+    physics::SimTime,
+    track::{Surface, SurfaceZone},
+    Z_PARTICLE,
+};
+use bevy::{math::Mat2, prelude::*};
+
+pub(crate) struct SprayConfig {
+    max_particles: usize,
+    particles_per_metre: f32,
+    slip_factor: f32,
+    speed_ratio: f32,
+    spread: f32,
+    lifetime: f32,
+}
+
+impl Default for SprayConfig {
+    fn default() -> Self {
+        Self {
+            max_particles: 400,
+            particles_per_metre: 2.0,
+            slip_factor: 4.0,
+            speed_ratio: 0.3,
+            spread: 0.4,
+            lifetime: 0.6,
+        }
+    }
+}
+
+pub(crate) struct SprayMaterials {
+    pub(crate) grass: Handle<ColorMaterial>,
+    pub(crate) dirt: Handle<ColorMaterial>,
+}
+
+impl SprayMaterials {
+    pub(crate) fn get(&self, surface: Surface) -> Option<&Handle<ColorMaterial>> {
+        match surface {
+            Surface::Tarmac => None,
+            Surface::Grass => Some(&self.grass),
+            Surface::Dirt => Some(&self.dirt),
+        }
+    }
+}
+
+pub(crate) struct Particle {
+    pub(crate) velocity: Vec2,
+}
+
+/// White smoke puffed from tires skidding hard on tarmac, which drifts, spreads and fades away
+pub struct TireSmoke {
+    pub enabled: bool,
+    pub toggle_key: KeyCode,
+    /// The most puffs there can be at once
+    pub max_puffs: usize,
+    /// Puffs emitted for each meter that a skidding tire slides sideways
+    pub puffs_per_metre: f32,
+    /// Tires sliding slower than this, in m/s, don't smoke
+    pub min_slip_speed: f32,
+    pub lifetime: f32,
+    /// The puffs' width, in meters, as they're emitted and as they vanish
+    pub start_size: f32,
+    pub end_size: f32,
+    /// How fast, in m/s, the puffs drift away in a random direction
+    pub drift_speed: f32,
+}
+
+impl Default for TireSmoke {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            toggle_key: KeyCode::F8,
+            max_puffs: 150,
+            puffs_per_metre: 1.5,
+            min_slip_speed: 3.0,
+            lifetime: 1.2,
+            start_size: 0.4,
+            end_size: 1.6,
+            drift_speed: 0.6,
+        }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct SmokeEmitter {
+    pub(crate) accumulator: f32,
+}
+
+pub(crate) struct SmokePuff;
+
+/// The puffs fade through a few shared shades of smoke, rather than each having its own material
+pub(crate) struct SmokeMaterials(pub(crate) Vec<Handle<ColorMaterial>>);
+
+const SMOKE_SHADES: usize = 8;
+
+impl FromWorld for SmokeMaterials {
+    fn from_world(world: &mut World) -> Self {
+        let mut materials = world.get_resource_mut::<Assets<ColorMaterial>>().unwrap();
+
+        Self(
+            (0..SMOKE_SHADES)
+                .map(|shade| {
+                    let alpha = 0.5 * (1.0 - shade as f32 / SMOKE_SHADES as f32);
+
+                    materials.add(ColorMaterial::color(Color::rgba(0.95, 0.95, 0.95, alpha)))
+                })
+                .collect(),
+        )
+    }
+}
+
+pub(crate) struct DespawnTimer(pub(crate) Timer);
+
+#[derive(Bundle)]
+struct ParticleBundle {
+    #[bundle]
+    sprite: SpriteBundle,
+    particle: Particle,
+    despawn_timer: DespawnTimer,
+}
+
+impl ParticleBundle {
+    pub(crate) fn new(
+        material: Handle<ColorMaterial>,
+        position: Vec3,
+        velocity: Vec2,
+        lifetime: f32,
+    ) -> Self {
+        Self {
+            sprite: SpriteBundle {
+                sprite: Sprite {
+                    size: 0.15 * Vec2::ONE,
+                    ..Default::default()
+                },
+                material,
+                transform: Transform::from_translation(position),
+                ..Default::default()
+            },
+            particle: Particle { velocity },
+            despawn_timer: DespawnTimer(Timer::from_seconds(lifetime, false)),
+        }
+    }
+}
+
+/// The one source of randomness for every effect, so that a run can be reproduced from its seed
+pub struct RngResource {
+    pub(crate) seed: u64,
+    pub(crate) state: u64,
+}
+
+impl Default for RngResource {
+    fn default() -> Self {
+        let seed = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |since_epoch| since_epoch.as_nanos() as u64);
+
+        Self::new(seed)
+    }
+}
+
+impl RngResource {
+    pub fn new(seed: u64) -> Self {
+        Self { seed, state: seed }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Starts the sequence again from `seed`
+    pub fn reseed(&mut self, seed: u64) {
+        *self = Self::new(seed);
+    }
+
+    /// SplitMix64, which is tiny and plenty random enough for visual effects
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+
+        z ^ (z >> 31)
+    }
+
+    /// Uniformly distributed in `[0, 1)`
+    pub fn next_f32(&mut self) -> f32 {
+        // The top 24 bits fill an f32's mantissa exactly
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Uniformly distributed in `[min, max)`
+    pub fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+}
+
+pub(crate) fn log_rng_seed(rng: Res<RngResource>) {
+    info!("Random seed: {}", rng.seed());
+}
+
+/// A reset starts the random sequence over, so that it plays out the same way each time
+pub(crate) fn restart_rng(
+    keyboard_input: Res<Input<KeyCode>>,
+    stuck_reset: Res<StuckReset>,
+    mut rng: ResMut<RngResource>,
+) {
+    if keyboard_input.just_pressed(KeyCode::R) || stuck_reset.is_due() {
+        let seed = rng.seed();
+        rng.reseed(seed);
+    }
+}
+
+fn spray(
+    mut commands: Commands,
+    time: Res<SimTime>,
+    spray_config: Res<SprayConfig>,
+    spray_materials: Res<SprayMaterials>,
+    mut rng: ResMut<RngResource>,
+    zones: Query<(&SurfaceZone, &GlobalTransform)>,
+    particles: Query<Entity, With<Particle>>,
+    mut tires: Query<(
+        &Tire,
+        &mut SprayEmitter,
+        &GlobalTransform,
+        &PreviousGlobalTransform,
+    )>,
+) {
+    let dt_seconds = time.delta_seconds();
+
+    if dt_seconds <= f32::EPSILON {
+        return;
+    }
+
+    let mut particle_count = particles.iter().count();
+
+    for (tire, mut emitter, global_transform, PreviousGlobalTransform(previous_global_transform)) in
+        tires.iter_mut()
+    {
+        let position = global_transform.translation.truncate();
+
+        let surface = zones
+            .iter()
+            .find(|(zone, zone_transform)| zone.contains(zone_transform, position))
+            .map_or(Surface::Tarmac, |(zone, _)| zone.surface);
+
+        let material = match spray_materials.get(surface) {
+            Some(material) if tire.driven => material,
+            _ => {
+                emitter.accumulator = 0.0;
+                continue;
+            }
+        };
+
+        let offset =
+            (global_transform.translation - previous_global_transform.translation).truncate();
+
+        emitter.accumulator += offset.length()
+            * spray_config.particles_per_metre
+            * (1.0 + spray_config.slip_factor * tire.slip);
+
+        let velocity = -offset / dt_seconds * spray_config.speed_ratio;
+
+        while emitter.accumulator >= 1.0 && particle_count < spray_config.max_particles {
+            let angle = rng.range(-spray_config.spread, spray_config.spread);
+
+            commands.spawn_bundle(ParticleBundle::new(
+                material.clone(),
+                position.extend(Z_PARTICLE),
+                Mat2::from_angle(angle) * velocity,
+                spray_config.lifetime,
+            ));
+
+            emitter.accumulator -= 1.0;
+            particle_count += 1;
+        }
+
+        emitter.accumulator = emitter.accumulator.fract();
+    }
+}
+
+pub(crate) fn emit_smoke(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    time: Res<SimTime>,
+    mut smoke: ResMut<TireSmoke>,
+    smoke_materials: Res<SmokeMaterials>,
+    mut rng: ResMut<RngResource>,
+    zones: Query<(&SurfaceZone, &GlobalTransform)>,
+    puffs: Query<Entity, With<SmokePuff>>,
+    mut tires: Query<(&Tire, &mut SmokeEmitter, &GlobalTransform)>,
+) {
+    if keyboard_input.just_pressed(smoke.toggle_key) {
+        smoke.enabled = !smoke.enabled;
+    }
+
+    let dt_seconds = time.delta_seconds();
+
+    if !smoke.enabled || dt_seconds <= f32::EPSILON {
+        return;
+    }
+
+    let mut puff_count = puffs.iter().count();
+
+    for (tire, mut emitter, global_transform) in tires.iter_mut() {
+        let position = global_transform.translation.truncate();
+        let slip_speed = tire.slip_velocity.length();
+
+        // Grass and dirt throw up spray instead
+        let on_tarmac = zones
+            .iter()
+            .find(|(zone, zone_transform)| zone.contains(zone_transform, position))
+            .map_or(true, |(zone, _)| zone.surface == Surface::Tarmac);
+
+        if !tire.is_skidding || !on_tarmac || slip_speed < smoke.min_slip_speed {
+            emitter.accumulator = 0.0;
+            continue;
+        }
+
+        emitter.accumulator += slip_speed * dt_seconds * smoke.puffs_per_metre;
+
+        while emitter.accumulator >= 1.0 && puff_count < smoke.max_puffs {
+            let angle = rng.range(-std::f32::consts::PI, std::f32::consts::PI);
+            let direction = Vec2::new(angle.cos(), angle.sin());
+
+            commands
+                .spawn_bundle(ParticleBundle::new(
+                    smoke_materials.0[0].clone(),
+                    position.extend(Z_PARTICLE),
+                    smoke.drift_speed * direction,
+                    smoke.lifetime,
+                ))
+                .insert(SmokePuff);
+
+            emitter.accumulator -= 1.0;
+            puff_count += 1;
+        }
+
+        emitter.accumulator = emitter.accumulator.fract();
+    }
+}
+
+/// Spreads each puff out and fades it as it ages
+pub(crate) fn age_smoke(
+    smoke: Res<TireSmoke>,
+    smoke_materials: Res<SmokeMaterials>,
+    mut puffs: Query<(&DespawnTimer, &mut Sprite, &mut Handle<ColorMaterial>), With<SmokePuff>>,
+) {
+    for (timer, mut sprite, mut material) in puffs.iter_mut() {
+        let age = timer.0.percent();
+        let size = smoke.start_size + (smoke.end_size - smoke.start_size) * age;
+
+        sprite.size = size * Vec2::ONE;
+
+        let shade = ((age * SMOKE_SHADES as f32) as usize).min(SMOKE_SHADES - 1);
+        let target = &smoke_materials.0[shade];
+
+        if *material != *target {
+            *material = target.clone();
+        }
+    }
+}
+
+/// Particles move and age with the simulation, so they freeze when it's paused and slow down with
+/// it
+pub(crate) fn move_particles(
+    time: Res<SimTime>,
+    mut particles: Query<(&Particle, &mut Transform)>,
+) {
+    for (particle, mut transform) in particles.iter_mut() {
+        transform.translation += (particle.velocity * time.delta_seconds()).extend(0.0);
+    }
+}
+
+pub(crate) fn despawn_timers(
+    mut commands: Commands,
+    time: Res<SimTime>,
+    mut query: Query<(Entity, &mut DespawnTimer)>,
+) {
+    let delta = std::time::Duration::from_secs_f32(time.delta_seconds());
+
+    for (entity, mut timer) in query.iter_mut() {
+        if timer.0.tick(delta).finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
@@ -0,0 +1,1150 @@
+//! The readouts, gauges and plots drawn over the world, and the config editor
+
+use crate::{
+    camera::MainCamera,
+    car::{
+        CarNumber, ConfigSource, CruiseControl, DriftScore, Odometer, PerfTest, PlayerControlled,
+        StuckText, PERF_TEST_SPEED_MPS,
+    },
+    physics::{
+        AssistLevel, CarConfig, CarState, DisplayedStats, LatestStats, LaunchControlPhase,
+        PausedText, SimTime,
+    },
+    precision::Real,
+    track::{AiText, GateText, Gates},
+    Z_STEER_PLOT,
+};
+use bevy::{
+    asset::LoadState,
+    prelude::*,
+    render::{mesh::VertexAttributeValues, pipeline::PrimitiveTopology},
+};
+
+/// A plot, in the corner of the screen, of how far the wheels turn for each steering input at the
+/// car's current speed, with keys to change the steering exponent and watch the curve respond
+pub struct SteerCurvePlot {
+    pub visible: bool,
+    pub toggle_key: KeyCode,
+    pub softer_key: KeyCode,
+    pub sharper_key: KeyCode,
+    /// The plot's size, in pixels
+    pub size: Vec2,
+}
+
+impl Default for SteerCurvePlot {
+    fn default() -> Self {
+        Self {
+            visible: false,
+            toggle_key: KeyCode::F7,
+            softer_key: KeyCode::Period,
+            sharper_key: KeyCode::Comma,
+            size: Vec2::new(160.0, 120.0),
+        }
+    }
+}
+
+pub(crate) struct SteerCurveLines;
+
+const STEER_CURVE_SEGMENTS: usize = 32;
+const STEER_EXPONENT_STEP: f32 = 0.1;
+
+/// An empty mesh of lines, drawn afresh each frame while it's shown
+pub(crate) fn line_mesh() -> Mesh {
+    let mut mesh = Mesh::new(PrimitiveTopology::LineList);
+    set_line_positions(&mut mesh, Vec::new());
+
+    mesh
+}
+
+fn set_line_positions(mesh: &mut Mesh, positions: Vec<[f32; 3]>) {
+    let vertex_count = positions.len();
+
+    mesh.set_attribute(
+        Mesh::ATTRIBUTE_POSITION,
+        VertexAttributeValues::Float3(positions),
+    );
+    mesh.set_attribute(
+        Mesh::ATTRIBUTE_NORMAL,
+        VertexAttributeValues::Float3(vec![[0.0, 0.0, 1.0]; vertex_count]),
+    );
+    mesh.set_attribute(
+        Mesh::ATTRIBUTE_UV_0,
+        VertexAttributeValues::Float2(vec![[0.0, 0.0]; vertex_count]),
+    );
+}
+
+/// Input runs across the plot and steer angle up it, from nothing to full lock, so the curve
+/// falls short of the top once the speed correction takes some of the lock away
+fn steer_curve_lines(config: &CarConfig, steer_authority: Real, size: Vec2) -> Vec<[f32; 3]> {
+    let point = |input: f32| {
+        let steer = config.steer_response(config.shape_input(input) as Real) * steer_authority;
+
+        [input * size.x, steer as f32 * size.y, 0.0]
+    };
+
+    let frame = [
+        [0.0, 0.0, 0.0],
+        [size.x, 0.0, 0.0],
+        [size.x, size.y, 0.0],
+        [0.0, size.y, 0.0],
+    ];
+
+    let frame_lines = (0..frame.len()).flat_map(|index| {
+        std::iter::once(frame[index]).chain(std::iter::once(frame[(index + 1) % frame.len()]))
+    });
+
+    let curve_lines = (0..STEER_CURVE_SEGMENTS).flat_map(|index| {
+        let from = index as f32 / STEER_CURVE_SEGMENTS as f32;
+        let to = (index + 1) as f32 / STEER_CURVE_SEGMENTS as f32;
+
+        std::iter::once(point(from)).chain(std::iter::once(point(to)))
+    });
+
+    frame_lines.chain(curve_lines).collect()
+}
+
+pub(crate) fn plot_steer_curve(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut plot: ResMut<SteerCurvePlot>,
+    mut configs: ResMut<Assets<CarConfig>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    cars: Query<(&Handle<CarConfig>, &CarState), With<PlayerControlled>>,
+    cameras: Query<&OrthographicProjection, With<MainCamera>>,
+    mut lines: Query<(&Handle<Mesh>, &mut Transform, &mut Visible), With<SteerCurveLines>>,
+) {
+    if keyboard_input.just_pressed(plot.toggle_key) {
+        plot.visible = !plot.visible;
+    }
+
+    for (_, _, mut visible) in lines.iter_mut() {
+        if visible.is_visible != plot.visible {
+            visible.is_visible = plot.visible;
+        }
+    }
+
+    if !plot.visible {
+        return;
+    }
+
+    let (handle, state) = match cars.iter().next() {
+        Some(car) => car,
+        None => return,
+    };
+
+    let nudge = if keyboard_input.just_pressed(plot.softer_key) {
+        STEER_EXPONENT_STEP
+    } else if keyboard_input.just_pressed(plot.sharper_key) {
+        -STEER_EXPONENT_STEP
+    } else {
+        0.0
+    };
+
+    // Only borrow the asset mutably when editing, so it isn't marked as modified every frame
+    if nudge != 0.0 {
+        if let Some(config) = configs.get_mut(handle.clone()) {
+            config.steer_exponent = (config.steer_exponent + nudge).max(STEER_EXPONENT_STEP);
+
+            info!("Steer exponent: {:.1}", config.steer_exponent);
+        }
+    }
+
+    let config = match configs.get(handle.clone()) {
+        Some(config) => config,
+        None => return,
+    };
+
+    let projection = match cameras.iter().next() {
+        Some(projection) => projection,
+        None => return,
+    };
+
+    let steer_authority = config.speed_steer_curve.authority(
+        state.velocity.length(),
+        config.speed_steer_correction as Real,
+    );
+
+    let positions = steer_curve_lines(config, steer_authority, plot.size);
+
+    // In the bottom right corner of the view, clear of the text there
+    let view_corner = Vec2::new(projection.right, projection.bottom);
+    let origin = view_corner + Vec2::new(-15.0 - plot.size.x, 40.0);
+
+    for (mesh, mut transform, _) in lines.iter_mut() {
+        // The lines are in pixels, and the camera's children in meters
+        transform.translation = (projection.scale * origin).extend(Z_STEER_PLOT);
+        transform.scale = Vec3::new(projection.scale, projection.scale, 1.0);
+
+        if let Some(mesh) = meshes.get_mut(mesh) {
+            set_line_positions(mesh, positions.clone());
+        }
+    }
+}
+
+/// A dial of the car's drift angle, between where it points and where it's going, with a band
+/// around the sweet spot, in which the needle turns green
+pub struct DriftGauge {
+    pub visible: bool,
+    pub toggle_key: KeyCode,
+    /// The smallest and largest drift angles, in radians, counted as the sweet spot
+    pub target_band: Option<(f32, f32)>,
+    /// The slowest, in m/s, that the car has a drift angle to speak of
+    pub min_speed: f32,
+    /// The dial's radius, in pixels
+    pub radius: f32,
+}
+
+impl Default for DriftGauge {
+    fn default() -> Self {
+        Self {
+            visible: false,
+            toggle_key: KeyCode::D,
+            target_band: Some((0.35, 0.7)),
+            min_speed: 2.0,
+            radius: 60.0,
+        }
+    }
+}
+
+impl DriftGauge {
+    pub(crate) fn in_sweet_spot(&self, drift_angle: f32) -> bool {
+        self.target_band
+            .map_or(false, |(min, max)| (min..=max).contains(&drift_angle.abs()))
+    }
+}
+
+pub(crate) struct DriftDial;
+
+pub(crate) struct DriftNeedle;
+
+pub(crate) struct DriftAngleText;
+
+pub(crate) struct DriftGaugeMaterials {
+    pub(crate) normal: Handle<ColorMaterial>,
+    pub(crate) sweet_spot: Handle<ColorMaterial>,
+}
+
+const DRIFT_DIAL_SEGMENTS: usize = 32;
+
+/// The angle, in radians, from the way the car points round to the way it's moving, positive to
+/// the left, or `None` if it's barely moving
+pub(crate) fn car_drift_angle(state: &CarState, min_speed: f32) -> Option<f32> {
+    if state.velocity.length() < min_speed as Real {
+        return None;
+    }
+
+    let angle = state.velocity.y.atan2(state.velocity.x) - state.heading;
+    let pi = std::f64::consts::PI as Real;
+
+    Some(((angle + pi).rem_euclid(2.0 * pi) - pi) as f32)
+}
+
+/// A point on the dial, which runs from a quarter turn right to a quarter turn left with no drift
+/// straight up
+fn drift_dial_point(angle: f32, radius: f32) -> [f32; 3] {
+    [-radius * angle.sin(), radius * angle.cos(), 0.0]
+}
+
+/// The dial's arc, and the target band on either side of the middle as a thicker inner arc
+/// between ticks
+fn drift_dial_lines(target_band: Option<(f32, f32)>, radius: f32) -> Vec<[f32; 3]> {
+    let quarter_turn = std::f32::consts::FRAC_PI_2;
+
+    let arc = |from: f32, to: f32, radius: f32| {
+        (0..DRIFT_DIAL_SEGMENTS).flat_map(move |index| {
+            let angle =
+                |index: usize| from + (to - from) * index as f32 / DRIFT_DIAL_SEGMENTS as f32;
+
+            std::iter::once(drift_dial_point(angle(index), radius))
+                .chain(std::iter::once(drift_dial_point(angle(index + 1), radius)))
+        })
+    };
+
+    let mut lines = arc(-quarter_turn, quarter_turn, radius).collect::<Vec<_>>();
+
+    if let Some((min, max)) = target_band {
+        for &side in [-1.0, 1.0].iter() {
+            let (min, max) = (side * min.min(quarter_turn), side * max.min(quarter_turn));
+
+            lines.extend(arc(min, max, 0.9 * radius));
+            lines.extend(arc(min, max, 0.85 * radius));
+
+            for &edge in [min, max].iter() {
+                lines.push(drift_dial_point(edge, 0.8 * radius));
+                lines.push(drift_dial_point(edge, radius));
+            }
+        }
+    }
+
+    lines
+}
+
+pub(crate) fn show_drift_gauge(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut gauge: ResMut<DriftGauge>,
+    gauge_materials: Res<DriftGaugeMaterials>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    cars: Query<&CarState, With<PlayerControlled>>,
+    cameras: Query<&OrthographicProjection, With<MainCamera>>,
+    mut dial: Query<
+        (&Handle<Mesh>, &mut Transform, &mut Visible),
+        (With<DriftDial>, Without<DriftNeedle>),
+    >,
+    mut needle: Query<
+        (
+            &Handle<Mesh>,
+            &mut Handle<ColorMaterial>,
+            &mut Transform,
+            &mut Visible,
+        ),
+        With<DriftNeedle>,
+    >,
+    mut text: Query<&mut Text, With<DriftAngleText>>,
+) {
+    if keyboard_input.just_pressed(gauge.toggle_key) {
+        gauge.visible = !gauge.visible;
+    }
+
+    let drift_angle = cars
+        .iter()
+        .next()
+        .and_then(|state| car_drift_angle(state, gauge.min_speed));
+
+    for mut text in text.iter_mut() {
+        let (value, color) = match (gauge.visible, drift_angle) {
+            (true, Some(drift_angle)) => (
+                format!("Drift angle: {:.0}°", drift_angle.to_degrees()),
+                if gauge.in_sweet_spot(drift_angle) {
+                    Color::GREEN
+                } else {
+                    Color::WHITE
+                },
+            ),
+            (true, None) => (String::from("Drift angle: -"), Color::WHITE),
+            (false, _) => (String::new(), Color::WHITE),
+        };
+
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+
+        if text.sections[0].style.color != color {
+            text.sections[0].style.color = color;
+        }
+    }
+
+    let projection = cameras.iter().next();
+
+    // In the bottom left corner of the view, above the text there
+    let place = |transform: &mut Transform| {
+        if let Some(projection) = projection {
+            let origin = Vec2::new(projection.left, projection.bottom)
+                + Vec2::new(15.0 + gauge.radius, 80.0);
+
+            // The lines are in pixels, and the camera's children in meters
+            transform.translation = (projection.scale * origin).extend(Z_STEER_PLOT);
+            transform.scale = Vec3::new(projection.scale, projection.scale, 1.0);
+        }
+    };
+
+    for (mesh, mut transform, mut visible) in dial.iter_mut() {
+        if visible.is_visible != gauge.visible {
+            visible.is_visible = gauge.visible;
+        }
+
+        if gauge.visible {
+            place(&mut transform);
+
+            if let Some(mesh) = meshes.get_mut(mesh) {
+                set_line_positions(mesh, drift_dial_lines(gauge.target_band, gauge.radius));
+            }
+        }
+    }
+
+    let needle_visible = gauge.visible && drift_angle.is_some();
+
+    for (mesh, mut material, mut transform, mut visible) in needle.iter_mut() {
+        if visible.is_visible != needle_visible {
+            visible.is_visible = needle_visible;
+        }
+
+        let drift_angle = match (needle_visible, drift_angle) {
+            (true, Some(drift_angle)) => drift_angle,
+            _ => continue,
+        };
+
+        let quarter_turn = std::f32::consts::FRAC_PI_2;
+        let shown_angle = drift_angle.max(-quarter_turn).min(quarter_turn);
+
+        let wanted_material = if gauge.in_sweet_spot(drift_angle) {
+            &gauge_materials.sweet_spot
+        } else {
+            &gauge_materials.normal
+        };
+
+        if *material != *wanted_material {
+            *material = wanted_material.clone();
+        }
+
+        place(&mut transform);
+
+        if let Some(mesh) = meshes.get_mut(mesh) {
+            set_line_positions(
+                mesh,
+                vec![[0.0, 0.0, 0.0], drift_dial_point(shown_angle, gauge.radius)],
+            );
+        }
+    }
+}
+
+const HUD_FONT_PATH: &str = "fonts/fira_sans/FiraSans-Regular.ttf";
+
+/// The font all of the HUD text uses, kept so that failing to load it can be reported
+pub(crate) struct HudFont {
+    pub(crate) handle: Handle<Font>,
+    reported: bool,
+}
+
+/// Text with a missing font just isn't drawn, so explain why the HUD is blank rather than
+/// leaving it to guesswork
+pub(crate) fn report_missing_hud_font(
+    asset_server: Res<AssetServer>,
+    hud_font: Option<ResMut<HudFont>>,
+) {
+    let mut hud_font = match hud_font {
+        Some(hud_font) => hud_font,
+        None => return,
+    };
+
+    if hud_font.reported || asset_server.get_load_state(&hud_font.handle) != LoadState::Failed {
+        return;
+    }
+
+    warn!(
+        "Couldn't load the HUD font from assets/{}, so the HUD text won't be shown",
+        HUD_FONT_PATH
+    );
+
+    hud_font.reported = true;
+}
+
+/// Where a HUD element sits: `x` from the left or right edge, and `y` from the top or bottom
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HudAnchor {
+    pub corner: HudCorner,
+    pub x: Val,
+    pub y: Val,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HudCorner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl HudAnchor {
+    pub(crate) fn new(corner: HudCorner, x: Val, y: Val) -> Self {
+        Self { corner, x, y }
+    }
+
+    pub(crate) fn position(self) -> Rect<Val> {
+        let Self { corner, x, y } = self;
+
+        match corner {
+            HudCorner::TopLeft => Rect {
+                top: y,
+                left: x,
+                ..Default::default()
+            },
+            HudCorner::TopRight => Rect {
+                top: y,
+                right: x,
+                ..Default::default()
+            },
+            HudCorner::BottomLeft => Rect {
+                bottom: y,
+                left: x,
+                ..Default::default()
+            },
+            HudCorner::BottomRight => Rect {
+                bottom: y,
+                right: x,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// Where each of the HUD's text elements is placed, so that they can be moved clear of anything
+/// else drawn over the view
+#[derive(Debug, Clone, PartialEq)]
+pub struct HudLayout {
+    pub debug_text: HudAnchor,
+    /// The second player's readout
+    pub second_debug_text: HudAnchor,
+    pub config_editor: HudAnchor,
+    pub gates: HudAnchor,
+    pub stuck: HudAnchor,
+    pub paused: HudAnchor,
+    pub ai: HudAnchor,
+    pub drift_angle: HudAnchor,
+}
+
+impl Default for HudLayout {
+    fn default() -> Self {
+        Self {
+            debug_text: HudAnchor::new(HudCorner::TopLeft, Val::Px(15.0), Val::Px(5.0)),
+            // Over the right half of the window, and below the pause text
+            second_debug_text: HudAnchor::new(
+                HudCorner::TopLeft,
+                Val::Percent(50.0),
+                Val::Px(35.0),
+            ),
+            config_editor: HudAnchor::new(HudCorner::TopRight, Val::Px(15.0), Val::Px(5.0)),
+            gates: HudAnchor::new(HudCorner::BottomLeft, Val::Px(15.0), Val::Px(5.0)),
+            stuck: HudAnchor::new(HudCorner::BottomRight, Val::Px(15.0), Val::Px(5.0)),
+            paused: HudAnchor::new(HudCorner::TopLeft, Val::Percent(45.0), Val::Px(5.0)),
+            ai: HudAnchor::new(HudCorner::BottomRight, Val::Px(15.0), Val::Px(35.0)),
+            drift_angle: HudAnchor::new(HudCorner::BottomLeft, Val::Px(15.0), Val::Px(35.0)),
+        }
+    }
+}
+
+/// Which of the layout's anchors a HUD element follows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum HudItem {
+    DebugText,
+    SecondDebugText,
+    ConfigEditor,
+    Gates,
+    Stuck,
+    Paused,
+    Ai,
+    DriftAngle,
+}
+
+impl HudItem {
+    fn anchor(self, layout: &HudLayout) -> HudAnchor {
+        match self {
+            Self::DebugText => layout.debug_text,
+            Self::SecondDebugText => layout.second_debug_text,
+            Self::ConfigEditor => layout.config_editor,
+            Self::Gates => layout.gates,
+            Self::Stuck => layout.stuck,
+            Self::Paused => layout.paused,
+            Self::Ai => layout.ai,
+            Self::DriftAngle => layout.drift_angle,
+        }
+    }
+}
+
+/// Gives each player's car its own debug readout, as the cars are spawned, with the first player's
+/// in the top left corner and the second's over the right half of the window
+pub(crate) fn spawn_readouts(
+    mut commands: Commands,
+    hud_font: Option<Res<HudFont>>,
+    layout: Res<HudLayout>,
+    settings: Res<DebugTextSettings>,
+    readouts: Query<&DebugText>,
+    cars: Query<&CarNumber, Added<PlayerControlled>>,
+) {
+    let font = match hud_font {
+        Some(hud_font) => hud_font.handle.clone(),
+        None => return,
+    };
+
+    let mut numbers = cars
+        .iter()
+        .filter(|&&number| readouts.iter().all(|readout| readout.0 != number))
+        .copied()
+        .collect::<Vec<_>>();
+
+    numbers.sort();
+
+    let mut count = readouts.iter().count();
+
+    for number in numbers {
+        let item = if count == 0 {
+            HudItem::DebugText
+        } else {
+            HudItem::SecondDebugText
+        };
+
+        commands
+            .spawn_bundle(TextBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: item.anchor(&layout).position(),
+                    ..Default::default()
+                },
+                text: Text::with_section(
+                    "Debug Info",
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: 16.0,
+                        color: Color::WHITE,
+                    },
+                    TextAlignment {
+                        horizontal: HorizontalAlign::Left,
+                        ..Default::default()
+                    },
+                ),
+                visible: Visible {
+                    is_visible: settings.visible,
+                    is_transparent: true,
+                },
+                ..Default::default()
+            })
+            .insert(DebugText(number))
+            .insert(item);
+
+        count += 1;
+    }
+}
+
+/// Moves the HUD's elements when the layout is changed while running
+pub(crate) fn apply_hud_layout(layout: Res<HudLayout>, mut items: Query<(&HudItem, &mut Style)>) {
+    if !layout.is_changed() {
+        return;
+    }
+
+    for (item, mut style) in items.iter_mut() {
+        let position = item.anchor(&layout).position();
+
+        if style.position != position {
+            style.position = position;
+        }
+    }
+}
+
+pub(crate) fn spawn_hud(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    layout: Res<HudLayout>,
+) {
+    commands.spawn_bundle(UiCameraBundle::default());
+
+    let font = asset_server.load(HUD_FONT_PATH);
+
+    commands.insert_resource(HudFont {
+        handle: font.clone(),
+        reported: false,
+    });
+
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: layout.config_editor.position(),
+                ..Default::default()
+            },
+            text: Text {
+                sections: EDITABLE_FIELDS
+                    .iter()
+                    .map(|_| TextSection {
+                        value: String::new(),
+                        style: TextStyle {
+                            font: font.clone(),
+                            font_size: 16.0,
+                            color: Color::WHITE,
+                        },
+                    })
+                    .collect(),
+                alignment: TextAlignment {
+                    horizontal: HorizontalAlign::Left,
+                    ..Default::default()
+                },
+            },
+            visible: Visible {
+                is_visible: false,
+                is_transparent: true,
+            },
+            ..Default::default()
+        })
+        .insert(ConfigEditorText)
+        .insert(HudItem::ConfigEditor);
+
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: layout.gates.position(),
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: font.clone(),
+                    font_size: 24.0,
+                    color: Color::WHITE,
+                },
+                TextAlignment::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(GateText)
+        .insert(HudItem::Gates);
+
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: layout.stuck.position(),
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: font.clone(),
+                    font_size: 24.0,
+                    color: Color::WHITE,
+                },
+                TextAlignment::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(StuckText)
+        .insert(HudItem::Stuck);
+
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: layout.paused.position(),
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: font.clone(),
+                    font_size: 24.0,
+                    color: Color::WHITE,
+                },
+                TextAlignment::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(PausedText)
+        .insert(HudItem::Paused);
+
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: layout.ai.position(),
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: font.clone(),
+                    font_size: 24.0,
+                    color: Color::WHITE,
+                },
+                TextAlignment::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(AiText)
+        .insert(HudItem::Ai);
+
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: layout.drift_angle.position(),
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font,
+                    font_size: 24.0,
+                    color: Color::WHITE,
+                },
+                TextAlignment::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(DriftAngleText)
+        .insert(HudItem::DriftAngle);
+}
+
+/// The units used for display; configuration is always in SI units
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnitSystem {
+    Metric,
+    Imperial,
+}
+
+impl Default for UnitSystem {
+    fn default() -> Self {
+        Self::Metric
+    }
+}
+
+impl UnitSystem {
+    pub(crate) fn speed(self, mps: Real) -> String {
+        match self {
+            Self::Metric => format!("{:.0} kph", mps * 3.6),
+            Self::Imperial => format!("{:.0} mph", mps * 2.236_936),
+        }
+    }
+
+    pub(crate) fn distance(self, meters: f32) -> String {
+        match self {
+            Self::Metric => format!("{:.1} m", meters),
+            Self::Imperial => format!("{:.1} ft", meters * 3.280_84),
+        }
+    }
+}
+
+pub(crate) fn toggle_units(keyboard_input: Res<Input<KeyCode>>, mut units: ResMut<UnitSystem>) {
+    if keyboard_input.just_pressed(KeyCode::U) {
+        *units = match *units {
+            UnitSystem::Metric => UnitSystem::Imperial,
+            UnitSystem::Imperial => UnitSystem::Metric,
+        };
+    }
+}
+
+/// Whether the debug readout is shown, and the key which toggles it
+pub struct DebugTextSettings {
+    pub toggle_key: KeyCode,
+    pub visible: bool,
+    /// Switches between smoothed and raw stats
+    pub smoothing_toggle_key: KeyCode,
+    /// The time constant, in seconds, of the smoothing applied to the displayed stats, which
+    /// otherwise flicker from frame to frame
+    pub smoothing_time: f32,
+    pub smoothed: bool,
+    /// Shows an estimate of each step's integration error, at the cost of stepping the physics
+    /// three more times a frame
+    pub integration_error_toggle_key: KeyCode,
+    pub integration_error: bool,
+}
+
+impl Default for DebugTextSettings {
+    fn default() -> Self {
+        Self {
+            toggle_key: KeyCode::H,
+            visible: true,
+            smoothing_toggle_key: KeyCode::M,
+            smoothing_time: 0.1,
+            smoothed: true,
+            integration_error_toggle_key: KeyCode::F12,
+            integration_error: false,
+        }
+    }
+}
+
+/// The debug readout of the car with this number
+pub(crate) struct DebugText(pub(crate) CarNumber);
+
+/// Writes each player's car's latest stats into its readout
+pub(crate) fn show_debug_text(
+    time: Res<SimTime>,
+    configs: Res<Assets<CarConfig>>,
+    settings: Res<DebugTextSettings>,
+    units: Res<UnitSystem>,
+    assist_level: Res<AssistLevel>,
+    mut cars: Query<(
+        &CarNumber,
+        &Handle<CarConfig>,
+        &CarState,
+        &LatestStats,
+        &mut DisplayedStats,
+        &CruiseControl,
+        &Odometer,
+        &DriftScore,
+        &PerfTest,
+        Option<&ConfigSource>,
+    )>,
+    mut text: Query<(&mut Text, &DebugText)>,
+) {
+    if !settings.visible {
+        return;
+    }
+
+    for (
+        number,
+        config,
+        state,
+        latest_stats,
+        mut displayed_stats,
+        cruise_control,
+        odometer,
+        drift_score,
+        perf_test,
+        config_source,
+    ) in cars.iter_mut()
+    {
+        let (config, mut stats) = match (configs.get(config), latest_stats.0.clone()) {
+            (Some(config), Some(stats)) => (config, stats),
+            _ => continue,
+        };
+
+        // The readout lags the physics slightly so that it can be read, but the physics itself
+        // is never smoothed
+        if settings.smoothed {
+            let smoothing_time = settings.smoothing_time;
+
+            if let Some(previous) = displayed_stats
+                .0
+                .as_ref()
+                .filter(|_| smoothing_time > f32::EPSILON)
+            {
+                let dt_seconds = time.delta_seconds();
+                let blend = dt_seconds / (smoothing_time + dt_seconds);
+
+                stats.ease_from(previous, blend as Real);
+            }
+
+            displayed_stats.0 = Some(stats.clone());
+        } else {
+            displayed_stats.0 = None;
+        }
+
+        // Only players' cars have readouts, each showing only its own car
+        let mut text = match text.iter_mut().find(|(_, readout)| readout.0 == *number) {
+            Some((text, _)) => text,
+            None => continue,
+        };
+
+        let speed = state.velocity.length();
+        let input_steer = state.steer_input;
+        let steer_authority = config
+            .speed_steer_curve
+            .authority(speed, config.speed_steer_correction as Real);
+        let max_steer = config.max_steer_at(speed);
+
+        // Asking for more steering than the speed allows is otherwise invisible
+        let is_steer_limited = input_steer.abs() > steer_authority + Real::EPSILON;
+        let flash_on = (time.seconds_since_startup() * 4.0) as i64 % 2 == 0;
+
+        let format_result = |result: Option<String>| result.unwrap_or_else(|| "-".to_string());
+
+        text.sections[0].value = format!(
+            "Speed: {}\nSteer: {:.2} rad (limit {:.2}){}\nCruise: {}{}\nOdometer: {} (trip {})\nAssists: {:?}{}\n{:#?}\nDrift: {:.0} (total {:.0})\n0-{test_speed}: {}\n{test_speed}-0: {}",
+            units.speed(stats.speed_mps),
+            max_steer * input_steer,
+            max_steer * steer_authority,
+            if is_steer_limited && flash_on {
+                "  LIMITED"
+            } else {
+                ""
+            },
+            format_result(cruise_control.target.map(|target| units.speed(target))),
+            if let LaunchControlPhase::Armed { .. } = state.launch_control {
+                "\nLAUNCH ARMED"
+            } else {
+                ""
+            },
+            units.distance(odometer.total),
+            units.distance(odometer.trip),
+            *assist_level,
+            if config_source.map_or(false, |source| source.pending) {
+                "\nConfig reload pending"
+            } else {
+                ""
+            },
+            stats,
+            drift_score.chain,
+            drift_score.total,
+            format_result(perf_test.acceleration_time.map(|time| format!("{:.2} s", time))),
+            format_result(perf_test.braking_distance.map(|distance| units.distance(distance))),
+            test_speed = units.speed(PERF_TEST_SPEED_MPS),
+        );
+    }
+}
+
+pub(crate) fn toggle_debug_text(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut settings: ResMut<DebugTextSettings>,
+    mut text: Query<&mut Visible, With<DebugText>>,
+) {
+    if keyboard_input.just_pressed(settings.toggle_key) {
+        settings.visible = !settings.visible;
+    }
+
+    if keyboard_input.just_pressed(settings.smoothing_toggle_key) {
+        settings.smoothed = !settings.smoothed;
+    }
+
+    if keyboard_input.just_pressed(settings.integration_error_toggle_key) {
+        settings.integration_error = !settings.integration_error;
+    }
+
+    for mut visible in text.iter_mut() {
+        if visible.is_visible != settings.visible {
+            visible.is_visible = settings.visible;
+        }
+    }
+}
+
+struct EditableField {
+    name: &'static str,
+    step: f32,
+    get: fn(&CarConfig) -> f32,
+    get_mut: fn(&mut CarConfig) -> &mut f32,
+}
+
+macro_rules! editable_fields {
+    ($($field:ident: $step:expr,)*) => {
+        &[$(EditableField {
+            name: stringify!($field),
+            step: $step,
+            get: |config| config.$field,
+            get_mut: |config| &mut config.$field,
+        },)*]
+    };
+}
+
+/// The fields shown by the config editor, with the amount each key press changes them by.
+/// The weight transfer coefficient is left out as it has its own keys.
+const EDITABLE_FIELDS: &[EditableField] = editable_fields! {
+    mass: 50.0,
+    inertia_scale: 0.05,
+    centre_of_gravity_height: 0.05,
+    engine_force: 250.0,
+    brake_force: 500.0,
+    brake_heat_rate: 0.1,
+    brake_cool_rate: 0.01,
+    e_brake_force: 250.0,
+    max_steer: 0.05,
+    low_speed_steer_boost: 0.1,
+    steer_exponent: 0.1,
+    input_deadzone: 0.01,
+    input_saturation: 0.01,
+    rear_steer_ratio: 0.05,
+    front_brake_lock_ratio: 0.05,
+    corner_stiffness_front: 0.1,
+    corner_stiffness_rear: 0.1,
+    front_roll_stiffness: 0.1,
+    rear_roll_stiffness: 0.1,
+    air_resistance: 0.1,
+    roll_resistance: 0.5,
+    total_tire_grip_front: 0.1,
+    total_tire_grip_rear: 0.1,
+    e_brake_grip_ratio_front: 0.05,
+    e_brake_grip_ratio_rear: 0.05,
+    steer_speed: 0.1,
+    speed_steer_correction: 1.0,
+    speed_turning_stability: 0.1,
+    yaw_damping: 0.1,
+};
+
+#[derive(Default)]
+pub(crate) struct ConfigEditor {
+    open: bool,
+    selected: usize,
+}
+
+pub(crate) struct ConfigEditorText;
+
+pub(crate) fn write_config(
+    mut writer: impl std::io::Write,
+    config: &CarConfig,
+) -> anyhow::Result<()> {
+    serde::Serialize::serialize(
+        config,
+        &mut serde_json::Serializer::with_formatter(
+            &mut writer,
+            serde_json::ser::PrettyFormatter::with_indent(b"    "),
+        ),
+    )?;
+
+    writer.write_all(b"\n")?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+pub(crate) fn edit_config(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut editor: ResMut<ConfigEditor>,
+    mut configs: ResMut<Assets<CarConfig>>,
+    cars: Query<&Handle<CarConfig>>,
+    mut text: Query<(&mut Text, &mut Visible), With<ConfigEditorText>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F1) {
+        editor.open = !editor.open;
+    }
+
+    for (_, mut visible) in text.iter_mut() {
+        if visible.is_visible != editor.open {
+            visible.is_visible = editor.open;
+        }
+    }
+
+    if !editor.open {
+        return;
+    }
+
+    let field_count = EDITABLE_FIELDS.len();
+
+    if keyboard_input.just_pressed(KeyCode::PageDown) {
+        editor.selected = (editor.selected + 1) % field_count;
+    }
+
+    if keyboard_input.just_pressed(KeyCode::PageUp) {
+        editor.selected = (editor.selected + field_count - 1) % field_count;
+    }
+
+    let handle = match cars.iter().next() {
+        Some(handle) => handle.clone(),
+        None => return,
+    };
+
+    let selected = &EDITABLE_FIELDS[editor.selected];
+
+    let nudge = if keyboard_input.just_pressed(KeyCode::Equals) {
+        selected.step
+    } else if keyboard_input.just_pressed(KeyCode::Minus) {
+        -selected.step
+    } else {
+        0.0
+    };
+
+    // Only borrow the asset mutably when editing, so it isn't marked as modified every frame
+    if nudge != 0.0 {
+        if let Some(config) = configs.get_mut(handle.clone()) {
+            let value = (selected.get_mut)(config);
+            *value = (*value + nudge).max(0.0);
+
+            config.resolve();
+        }
+    }
+
+    let config = match configs.get(handle) {
+        Some(config) => config,
+        None => return,
+    };
+
+    if keyboard_input.just_pressed(KeyCode::F5) {
+        match std::fs::File::create("assets/config.car")
+            .map_err(anyhow::Error::from)
+            .and_then(|file| write_config(std::io::BufWriter::new(file), config))
+        {
+            Ok(()) => info!("Saved car config"),
+            Err(err) => error!("Failed to save car config: {}", err),
+        }
+    }
+
+    for (mut text, _) in text.iter_mut() {
+        for (index, (section, field)) in text
+            .sections
+            .iter_mut()
+            .zip(EDITABLE_FIELDS.iter())
+            .enumerate()
+        {
+            section.value = format!("{}: {}\n", field.name, (field.get)(config));
+            section.style.color = if index == editor.selected {
+                Color::YELLOW
+            } else {
+                Color::WHITE
+            };
+        }
+    }
+}
@@ -0,0 +1,135 @@
+// Bevy systems take each resource and query they use as a parameter, and queries name every
+// component they read as a tuple, so systems that do a lot are long-winded by nature
+#![allow(clippy::too_many_arguments, clippy::type_complexity)]
+
+mod camera;
+mod car;
+mod effects;
+mod hud;
+mod physics;
+mod plugin;
+mod scenery;
+mod skids;
+mod telemetry;
+#[cfg(test)]
+mod tests;
+mod track;
+
+pub use camera::{
+    AspectPolicy, CameraBounds, CameraDeadzone, CameraFraming, CameraLookAhead, CameraMode,
+    CameraShake, CameraTransition, WeightCamera,
+};
+pub use car::{
+    CarNumber, ConfigReloadPolicy, LaunchControl, Livery, Palette, PlayerControlled, PlayerKeys,
+    ReversingBeep, StuckReset,
+};
+pub use effects::{RngResource, TireSmoke};
+pub use hud::{
+    DebugTextSettings, DriftGauge, HudAnchor, HudCorner, HudLayout, SteerCurvePlot, UnitSystem,
+};
+pub use physics::{
+    AssistLevel, AxleConfig, CarConfig, CarConfigLoader, CheckpointPassed, Hill, InertiaMode,
+    PhysicsLogging, SimControl, SimTime, SkidEnded, SkidStarted, SpunOut, SteerCorrectionCurve,
+    Terrain, WallHit, WorldBounds,
+};
+pub use plugin::CarPlugin;
+pub use scenery::{GridSettings, GroundTexture};
+pub use skids::{SkidBaking, SkidBlend, SkidCulling, SkidTexture, SkidWidth};
+pub use telemetry::{dump_telemetry, InputRecordings, ReplaySettings, SpinReplay, WorldSnapshots};
+pub use track::{
+    AiControls, AiDifficulty, AiDriver, AiRoute, BestLaps, CornerAdvisory, GatePlacement, Gates,
+    LapTimer, TrackConfigLoader,
+};
+
+use bevy::prelude::*;
+
+#[cfg(not(feature = "f64-physics"))]
+mod precision {
+    pub type Real = f32;
+    pub type RealVec2 = bevy::math::Vec2;
+    pub type RealMat2 = bevy::math::Mat2;
+}
+
+#[cfg(feature = "f64-physics")]
+mod precision {
+    pub type Real = f64;
+    pub type RealVec2 = bevy::math::DVec2;
+    pub type RealMat2 = bevy::math::DMat2;
+}
+
+use precision::{Real, RealVec2};
+
+fn to_real(v: Vec2) -> RealVec2 {
+    RealVec2::new(v.x as Real, v.y as Real)
+}
+
+fn from_real(v: RealVec2) -> Vec2 {
+    Vec2::new(v.x as f32, v.y as f32)
+}
+
+/// Like `signum`, but zero for zero rather than treating `+0.0` as positive, so a stationary car
+/// has no direction for brakes or steering to act against
+fn direction_sign(t: Real) -> Real {
+    if t > Real::EPSILON {
+        1.0
+    } else if t < -Real::EPSILON {
+        -1.0
+    } else {
+        0.0
+    }
+}
+
+fn clamp(t: Real, min: Real, max: Real) -> (bool, Real) {
+    assert!(min <= max);
+    if t < min {
+        (true, min)
+    } else if t > max {
+        (true, max)
+    } else {
+        (false, t)
+    }
+}
+
+// The draw order, from back to front, in three groups which each place things in their own
+// frame: the world, a car, and the camera.
+
+// World heights
+const Z_BACKDROP: f32 = 0.0;
+const Z_GROUND: f32 = 0.1;
+const Z_GRID: f32 = 0.25;
+const Z_TERRAIN_SHADING: f32 = 0.5;
+const Z_SKID: f32 = 0.6;
+const Z_OBSTACLE: f32 = 0.75;
+const Z_CAR: f32 = 1.0;
+const Z_GATE: f32 = 1.5;
+const Z_PARTICLE: f32 = 2.0;
+const Z_GHOST: f32 = 3.0;
+
+// Offsets from the car, or from the ghost, which stay below the next world height up
+/// Tires, bumpers and the weight marker, above the car
+const Z_CAR_PARTS: f32 = 0.1;
+/// Brake and reverse lights, over the bumpers
+const Z_CAR_LIGHTS: f32 = 0.2;
+/// Slip arrows, over everything else on the car
+const Z_CAR_DEBUG: f32 = 0.3;
+
+// Offsets from the camera, which sits just inside the far end of its view, so these are all in
+// front of the world
+const Z_STEER_PLOT: f32 = -1.5;
+const Z_LETTERBOX: f32 = -1.0;
+/// The second window's readout, over the letterbox bars
+const Z_SECOND_READOUT: f32 = -0.5;
+
+trait IntoArray: Sized {
+    type A;
+
+    fn into_array(self) -> Self::A;
+}
+
+impl IntoArray for Vec3 {
+    type A = [f32; 3];
+
+    fn into_array(self) -> Self::A {
+        [self.x, self.y, self.z]
+    }
+}
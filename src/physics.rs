@@ -0,0 +1,1930 @@
+//! The world's physics, the car's config and state, and the step which moves it, with the
+//! simulation's clock, events and assists
+
+use crate::{
+    car::{
+        CarComponents, CruiseControl, DriftScore, Odometer, PerfTest, PlayerControlled, SpawnPoint,
+        SpinDetector, Teleported, Tire, WeightMarker,
+    },
+    clamp, direction_sign, from_real,
+    hud::DebugTextSettings,
+    precision::{Real, RealMat2, RealVec2},
+    telemetry::Replay,
+    to_real, Z_CAR,
+};
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    ecs::{schedule::ShouldRun, system::SystemParam},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+    window::{WindowFocused, WindowId},
+};
+
+pub(crate) struct Gravity(pub(crate) f32);
+
+/// A smooth round hill (or hollow, for a negative height) whose height falls off as a Gaussian
+/// with distance from its centre
+pub struct Hill {
+    pub centre: Vec2,
+    pub radius: f32,
+    pub height: f32,
+}
+
+impl Hill {
+    pub(crate) fn height(&self, position: RealVec2) -> Real {
+        let offset = position - to_real(self.centre);
+        let radius = self.radius as Real;
+
+        self.height as Real * (-offset.length_squared() / (radius * radius)).exp()
+    }
+
+    fn gradient(&self, position: RealVec2) -> RealVec2 {
+        let offset = position - to_real(self.centre);
+        let radius = self.radius as Real;
+
+        -2.0 * self.height(position) / (radius * radius) * offset
+    }
+}
+
+/// The ground height is the sum of its hills, so with none the world is flat
+#[derive(Default)]
+pub struct Terrain {
+    pub hills: Vec<Hill>,
+}
+
+impl Terrain {
+    pub(crate) fn height(&self, position: RealVec2) -> Real {
+        self.hills.iter().map(|hill| hill.height(position)).sum()
+    }
+
+    fn gradient(&self, position: RealVec2) -> RealVec2 {
+        self.hills.iter().fold(RealVec2::ZERO, |gradient, hill| {
+            gradient + hill.gradient(position)
+        })
+    }
+}
+
+/// What happens when the car reaches the edge of a world of the given half size, centred on the
+/// origin
+pub enum WorldBounds {
+    Unbounded,
+    Clamp {
+        half_size: Vec2,
+    },
+    /// Along an axis with no size, the car isn't wrapped at all
+    Wrap {
+        half_size: Vec2,
+    },
+}
+
+impl Default for WorldBounds {
+    fn default() -> Self {
+        Self::Unbounded
+    }
+}
+
+/// What the edge of the world did to the car
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum BoundsContact {
+    None,
+    /// The car stopped dead against the edge, losing this much speed
+    Wall {
+        speed: Real,
+    },
+    /// The car was teleported to the opposite edge
+    Wrapped,
+}
+
+impl WorldBounds {
+    pub(crate) fn half_size(&self) -> Option<Vec2> {
+        match *self {
+            Self::Unbounded => None,
+            Self::Clamp { half_size } | Self::Wrap { half_size } => Some(half_size),
+        }
+    }
+
+    pub(crate) fn apply(&self, position: &mut RealVec2, velocity: &mut RealVec2) -> BoundsContact {
+        match *self {
+            Self::Unbounded => BoundsContact::None,
+            Self::Clamp { half_size } => {
+                let half_size = to_real(half_size);
+                let clamped = position.max(-half_size).min(half_size);
+                let mut lost_velocity = RealVec2::ZERO;
+
+                // Stop dead against the edge rather than pushing into it every frame
+                if clamped.x != position.x {
+                    lost_velocity.x = velocity.x;
+                    velocity.x = 0.0;
+                }
+
+                if clamped.y != position.y {
+                    lost_velocity.y = velocity.y;
+                    velocity.y = 0.0;
+                }
+
+                *position = clamped;
+
+                // Sitting against the edge keeps the car clamped, but it only hits once
+                if lost_velocity.length() > Real::EPSILON {
+                    BoundsContact::Wall {
+                        speed: lost_velocity.length(),
+                    }
+                } else {
+                    BoundsContact::None
+                }
+            }
+            Self::Wrap { half_size } => {
+                let half_size = to_real(half_size);
+                let wrap = |t: Real, half_size: Real| {
+                    if half_size > Real::EPSILON {
+                        (t + half_size).rem_euclid(2.0 * half_size) - half_size
+                    } else {
+                        t
+                    }
+                };
+                let wrapped =
+                    RealVec2::new(wrap(position.x, half_size.x), wrap(position.y, half_size.y));
+                let has_wrapped = wrapped != *position;
+
+                *position = wrapped;
+
+                if has_wrapped {
+                    BoundsContact::Wrapped
+                } else {
+                    BoundsContact::None
+                }
+            }
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy)]
+pub(crate) struct CarInputs {
+    pub(crate) throttle: f32,
+    pub(crate) brake: f32,
+    pub(crate) e_brake: f32,
+    pub(crate) steer: f32,
+}
+
+/// One of a car's axles, listed in `CarConfig::axles` in place of the front and rear fields
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct AxleConfig {
+    pub position: f32,
+    pub half_width: f32,
+    pub steered: bool,
+    /// When none of a config's axles is driven, as in configs from before axles could be, the
+    /// rear-most one is
+    pub driven: bool,
+    pub drive_share: f32,
+    pub corner_stiffness: f32,
+    pub roll_stiffness: f32,
+    pub e_brake_grip_ratio: f32,
+    pub total_tire_grip: f32,
+}
+
+impl Default for AxleConfig {
+    fn default() -> Self {
+        Self {
+            position: 0.0,
+            half_width: 0.64,
+            steered: false,
+            driven: false,
+            drive_share: 1.0,
+            corner_stiffness: 5.2,
+            roll_stiffness: 1.0,
+            e_brake_grip_ratio: 0.4,
+            total_tire_grip: 2.5,
+        }
+    }
+}
+
+impl AxleConfig {
+    /// A pair of tires, or a single one on the centre line for an axle with no width
+    pub(crate) fn tire_count(&self) -> usize {
+        if self.half_width > f32::EPSILON {
+            2
+        } else {
+            1
+        }
+    }
+
+    pub(crate) fn lateral_offsets(&self) -> impl ExactSizeIterator<Item = f32> {
+        let half_width = self.half_width;
+        let tire_count = self.tire_count();
+
+        (0..tire_count).map(move |tire| match (tire_count, tire) {
+            (1, _) => 0.0,
+            (_, 0) => half_width,
+            _ => -half_width,
+        })
+    }
+}
+
+/// How much steering authority is left at a given speed, relative to `speed_steer_correction`
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SteerCorrectionCurve {
+    /// Falls off linearly, reaching zero at the correction speed
+    Linear,
+    /// Halves at the correction speed and tails off without ever reaching zero, more sharply
+    /// for a larger shape
+    Smooth { shape: f32 },
+}
+
+impl SteerCorrectionCurve {
+    pub(crate) fn authority(self, speed: Real, correction: Real) -> Real {
+        match self {
+            Self::Linear => 1.0 - (speed / correction).min(1.0),
+            Self::Smooth { shape } => 1.0 / (1.0 + (speed / correction).powf(shape as Real)),
+        }
+    }
+}
+
+/// How the car's yaw moment of inertia is worked out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InertiaMode {
+    /// The mass multiplied by `inertia_scale`
+    Scaled,
+    /// A uniform box filling the car's outline, turning about the centre of gravity
+    Box,
+}
+
+impl InertiaMode {
+    fn inertia(self, config: &CarConfig) -> Real {
+        let mass = config.mass as Real;
+
+        match self {
+            Self::Scaled => mass * config.inertia_scale as Real,
+            Self::Box => {
+                let front = config.centre_of_gravity_to_front as Real;
+                let rear = config.centre_of_gravity_to_rear as Real;
+                let length = front + rear;
+                let width = 2.0 * config.half_width as Real;
+
+                // About the box's own centre, then moved out to the centre of gravity
+                let centre_offset = 0.5 * (front - rear);
+
+                mass * ((length * length + width * width) / 12.0 + centre_offset * centre_offset)
+            }
+        }
+    }
+}
+
+/// A car's handling, loaded from a `.car` file such as `assets/config.car`
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize, TypeUuid)]
+#[uuid = "e8dbac6d-624d-466b-b38f-84737004b095"]
+#[serde(default)]
+pub struct CarConfig {
+    pub gravity: f32,
+    pub mass: f32,
+    pub inertia_mode: InertiaMode,
+    pub inertia_scale: f32,
+    pub half_width: f32,
+    pub centre_of_gravity_to_front: f32,
+    pub centre_of_gravity_to_rear: f32,
+    pub centre_of_gravity_to_front_axle: f32,
+    pub centre_of_gravity_to_rear_axle: f32,
+    pub centre_of_gravity_height: f32,
+    pub wheel_radius: f32,
+    pub wheel_width: f32,
+    pub engine_force: f32,
+    pub drive_split: f32,
+    pub launch_ramp_time: f32,
+    pub speed_limiter: Option<f32>,
+    pub brake_force: f32,
+    pub brake_heat_rate: f32,
+    pub brake_cool_rate: f32,
+    pub brake_fade_onset: f32,
+    pub brake_fade_per_degree: f32,
+    pub e_brake_force: f32,
+    pub e_brake_ramp_time: f32,
+    pub weight_transfer: f32,
+    pub max_steer: f32,
+    /// How many times `max_steer` the wheels turn at a standstill, for parking, blending back to
+    /// `max_steer` by `low_speed_steer_boost_speed`, in m/s
+    pub low_speed_steer_boost: f32,
+    pub low_speed_steer_boost_speed: f32,
+    pub steer_exponent: f32,
+    /// Analog steering within this much of the centre is ignored, so a stick that doesn't quite
+    /// centre doesn't pull the car to one side. Only the players' own inputs are shaped, never the
+    /// computer's, cruise control's or the perf test's.
+    pub input_deadzone: f32,
+    /// How far the stick moves for full lock, so that it reaches full lock short of the stick's
+    /// physical limit
+    pub input_saturation: f32,
+    /// Whether the throttle and brake triggers are shaped by the deadzone and saturation too
+    pub shape_pedal_inputs: bool,
+    pub rear_steer_ratio: f32,
+    pub min_turn_radius: f32,
+    /// How much of the grip on a steered axle its foot brake must exceed to lock it, or 0 for
+    /// wheels that never lock
+    pub front_brake_lock_ratio: f32,
+    /// Whether locked steered wheels lose their steering and slide straight on
+    pub locked_wheels_slide_straight: bool,
+    /// Each tire's share of the brakes relative to an even split, front to back and left to right
+    /// on each axle, with any left out at 1
+    pub tire_brake_ratios: Vec<f32>,
+    pub corner_stiffness_front: f32,
+    pub corner_stiffness_rear: f32,
+    pub stiffness_load_sensitivity: f32,
+    pub load_sensitivity: f32,
+    pub front_roll_stiffness: f32,
+    pub rear_roll_stiffness: f32,
+    pub air_resistance: f32,
+    pub air_resistance_x: Option<f32>,
+    pub air_resistance_y: Option<f32>,
+    pub roll_resistance: f32,
+    pub e_brake_grip_ratio_front: f32,
+    pub total_tire_grip_front: f32,
+    pub e_brake_grip_ratio_rear: f32,
+    pub total_tire_grip_rear: f32,
+    pub steer_speed: f32,
+    pub steer_settle_time: f32,
+    pub slip_speed_floor: f32,
+    pub steer_input_time_constant: f32,
+    pub weight_marker_time_constant: f32,
+    pub speed_steer_correction: f32,
+    pub speed_steer_curve: SteerCorrectionCurve,
+    pub speed_turning_stability: f32,
+    pub yaw_damping: f32,
+    pub axle_distance_correction: f32,
+    pub axles: Vec<AxleConfig>,
+    /// The axles which either `axles` or the front and rear fields describe, worked out once when
+    /// the config is loaded or changed, rather than every step
+    #[serde(skip)]
+    resolved_axles: Vec<AxleConfig>,
+    pub initial_position: [f32; 2],
+    pub initial_heading: f32,
+    pub initial_speed: f32,
+}
+
+impl Default for CarConfig {
+    fn default() -> Self {
+        Self {
+            gravity: 9.81,
+            mass: 1500.0,
+            inertia_mode: InertiaMode::Scaled,
+            inertia_scale: 1.0,
+            half_width: 0.64,
+            centre_of_gravity_to_front: 1.7,
+            centre_of_gravity_to_rear: 1.7,
+            centre_of_gravity_to_front_axle: 1.0,
+            centre_of_gravity_to_rear_axle: 1.0,
+            centre_of_gravity_height: 0.55,
+            wheel_radius: 0.5,
+            wheel_width: 0.2,
+            engine_force: 8000.0,
+            drive_split: 0.0,
+            launch_ramp_time: 0.0,
+            speed_limiter: None,
+            brake_force: 12000.0,
+            brake_heat_rate: 0.0,
+            brake_cool_rate: 0.0,
+            brake_fade_onset: 300.0,
+            brake_fade_per_degree: 0.002,
+            e_brake_force: 4800.0,
+            e_brake_ramp_time: 0.0,
+            weight_transfer: 0.2,
+            max_steer: 0.6,
+            low_speed_steer_boost: 1.0,
+            low_speed_steer_boost_speed: 5.0,
+            steer_exponent: 1.0,
+            input_deadzone: 0.05,
+            input_saturation: 1.0,
+            shape_pedal_inputs: false,
+            rear_steer_ratio: 0.0,
+            min_turn_radius: 0.0,
+            front_brake_lock_ratio: 0.0,
+            locked_wheels_slide_straight: false,
+            tire_brake_ratios: vec![1.0; 4],
+            corner_stiffness_front: 5.0,
+            corner_stiffness_rear: 5.2,
+            stiffness_load_sensitivity: 0.0,
+            load_sensitivity: 1.0,
+            front_roll_stiffness: 1.0,
+            rear_roll_stiffness: 1.0,
+            air_resistance: 2.5,
+            air_resistance_x: None,
+            air_resistance_y: None,
+            roll_resistance: 8.0,
+            e_brake_grip_ratio_front: 0.9,
+            total_tire_grip_front: 2.5,
+            e_brake_grip_ratio_rear: 0.4,
+            total_tire_grip_rear: 2.5,
+            steer_speed: 2.5,
+            steer_settle_time: 0.0,
+            slip_speed_floor: 0.0,
+            steer_input_time_constant: 0.0,
+            weight_marker_time_constant: 0.0,
+            speed_steer_correction: 60.0,
+            speed_steer_curve: SteerCorrectionCurve::Linear,
+            speed_turning_stability: 11.8,
+            yaw_damping: 0.0,
+            axle_distance_correction: 1.7,
+            axles: Vec::new(),
+            resolved_axles: Vec::new(),
+            initial_position: [0.0, 0.0],
+            initial_heading: 0.0,
+            initial_speed: 0.0,
+        }
+    }
+}
+
+impl CarConfig {
+    /// How much of the steering lock an input asks for; an exponent above one softens small
+    /// inputs for finer control about the centre, while still reaching full lock
+    pub(crate) fn steer_response(&self, input: Real) -> Real {
+        if input.abs() < Real::EPSILON {
+            0.0
+        } else {
+            input.signum() * input.abs().powf(self.steer_exponent as Real)
+        }
+    }
+
+    /// Ignores an analog input within the deadzone and reaches the full input at the saturation
+    /// point, scaling linearly in between
+    pub(crate) fn shape_input(&self, input: f32) -> f32 {
+        let magnitude = input.abs();
+        let range = self.input_saturation - self.input_deadzone;
+
+        if magnitude <= self.input_deadzone {
+            0.0
+        } else if range <= f32::EPSILON {
+            input.signum()
+        } else {
+            input.signum() * ((magnitude - self.input_deadzone) / range).min(1.0)
+        }
+    }
+
+    pub(crate) fn shape_inputs(&self, inputs: CarInputs) -> CarInputs {
+        let shape_pedal = |input| {
+            if self.shape_pedal_inputs {
+                self.shape_input(input)
+            } else {
+                input
+            }
+        };
+
+        CarInputs {
+            throttle: shape_pedal(inputs.throttle),
+            brake: shape_pedal(inputs.brake),
+            e_brake: inputs.e_brake,
+            steer: self.shape_input(inputs.steer),
+        }
+    }
+
+    /// The steering lock at the given speed, boosted below the low-speed boost's speed
+    pub(crate) fn max_steer_at(&self, speed: Real) -> Real {
+        let slowness = if self.low_speed_steer_boost_speed > f32::EPSILON {
+            (1.0 - speed / self.low_speed_steer_boost_speed as Real).max(0.0)
+        } else {
+            0.0
+        };
+
+        self.max_steer as Real * (1.0 + (self.low_speed_steer_boost as Real - 1.0) * slowness)
+    }
+
+    /// How far an axle's wheels turn for the given steer angle: all of it for steered axles, and
+    /// the rear steer ratio of it for the rest, so a negative ratio turns them against the front
+    pub(crate) fn axle_steer_angle(&self, axle: &AxleConfig, steer_angle: Real) -> Real {
+        if axle.steered {
+            steer_angle
+        } else {
+            self.rear_steer_ratio as Real * steer_angle
+        }
+    }
+
+    /// The resolved axles, or, for a config which hasn't been through the asset system, the
+    /// axles worked out afresh
+    pub(crate) fn axles(&self) -> std::borrow::Cow<'_, [AxleConfig]> {
+        if self.resolved_axles.is_empty() {
+            std::borrow::Cow::Owned(self.resolve_axles())
+        } else {
+            std::borrow::Cow::Borrowed(&self.resolved_axles)
+        }
+    }
+
+    /// Whether the resolved axles are out of date with the rest of the config
+    pub(crate) fn needs_resolving(&self) -> bool {
+        self.resolved_axles != self.resolve_axles()
+    }
+
+    /// Brings the resolved axles up to date with the rest of the config
+    pub(crate) fn resolve(&mut self) {
+        self.resolved_axles = self.resolve_axles();
+    }
+
+    pub(crate) fn resolve_axles(&self) -> Vec<AxleConfig> {
+        if !self.axles.is_empty() {
+            let mut axles = self.axles.clone();
+
+            if !axles.iter().any(|axle| axle.driven) {
+                if let Some(rear) = axles.iter_mut().min_by(|a, b| {
+                    a.position
+                        .partial_cmp(&b.position)
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                }) {
+                    rear.driven = true;
+                }
+            }
+
+            return axles;
+        }
+
+        vec![
+            AxleConfig {
+                position: self.centre_of_gravity_to_front_axle,
+                half_width: self.half_width,
+                steered: true,
+                driven: self.drive_split > 0.0,
+                drive_share: self.drive_split,
+                corner_stiffness: self.corner_stiffness_front,
+                roll_stiffness: self.front_roll_stiffness,
+                e_brake_grip_ratio: self.e_brake_grip_ratio_front,
+                total_tire_grip: self.total_tire_grip_front,
+            },
+            AxleConfig {
+                position: -self.centre_of_gravity_to_rear_axle,
+                half_width: self.half_width,
+                steered: false,
+                driven: self.drive_split < 1.0,
+                drive_share: 1.0 - self.drive_split,
+                corner_stiffness: self.corner_stiffness_rear,
+                roll_stiffness: self.rear_roll_stiffness,
+                e_brake_grip_ratio: self.e_brake_grip_ratio_rear,
+                total_tire_grip: self.total_tire_grip_rear,
+            },
+        ]
+    }
+
+    fn tire_brake_ratio(&self, tire: usize) -> Real {
+        self.tire_brake_ratios
+            .get(tire)
+            .map_or(1.0, |&ratio| ratio as Real)
+    }
+
+    pub(crate) fn tire_count(&self) -> usize {
+        self.axles().iter().map(AxleConfig::tire_count).sum()
+    }
+}
+
+#[derive(Default)]
+pub struct CarConfigLoader;
+
+impl AssetLoader for CarConfigLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let mut config = serde_json::from_str::<CarConfig>(std::str::from_utf8(bytes)?)?;
+            config.resolve();
+            load_context.set_default_asset(LoadedAsset::new(config));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["car"]
+    }
+}
+
+#[derive(Default, Clone)]
+pub(crate) struct CarState {
+    pub(crate) heading: Real,
+    pub(crate) position: RealVec2,
+    pub(crate) velocity: RealVec2,
+    pub(crate) acceleration: RealVec2,
+    pub(crate) local_acceleration: RealVec2,
+    pub(crate) yaw_rate: Real,
+    pub(crate) steer_input: Real,
+    pub(crate) steer: Real,
+    pub(crate) steer_angle: Real,
+    pub(crate) launch_progress: Real,
+    pub(crate) e_brake: Real,
+    pub(crate) brake_temperature: Real,
+    pub(crate) launch_control: LaunchControlPhase,
+    /// Set from the `AssistLevel` before each step, on top of the car's own config
+    pub(crate) assists: Assists,
+}
+
+/// Where a car is in a launch-control start
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum LaunchControlPhase {
+    Off,
+    /// Held at a standstill with the clutch already this far in, ready to go
+    Armed {
+        preload: f32,
+    },
+    /// Pulling away, with the drive kept within this fraction of the driven tires' grip until the
+    /// car reaches the given speed
+    Launching {
+        grip_ratio: f32,
+        until_speed: f32,
+    },
+}
+
+impl Default for LaunchControlPhase {
+    fn default() -> Self {
+        Self::Off
+    }
+}
+
+impl CarState {
+    /// Points the car along `heading`, without any rotation, for a clean start to a test
+    pub(crate) fn snap_heading(&mut self, heading: Real) {
+        self.heading = heading;
+        self.yaw_rate = 0.0;
+    }
+
+    pub(crate) fn initial(config: &CarConfig) -> Self {
+        let heading = config.initial_heading as Real;
+
+        Self {
+            heading,
+            position: to_real(Vec2::from(config.initial_position)),
+            velocity: RealMat2::from_angle(heading)
+                * RealVec2::new(config.initial_speed as Real, 0.0),
+            ..Self::default()
+        }
+    }
+
+    /// The initial state, at the car's own spawn point
+    pub(crate) fn spawned(config: &CarConfig, spawn_point: &SpawnPoint) -> Self {
+        let mut state = Self::initial(config);
+        state.position += RealMat2::from_angle(state.heading) * to_real(spawn_point.offset);
+        state
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct CarStats {
+    fps: i32,
+    pub(crate) speed_mps: Real,
+    pub(crate) steering: Real,
+    pub(crate) steer_angle: Real,
+    rear_steer_angle: Real,
+    turn_radius: Option<Real>,
+    inertia: Real,
+    pub(crate) yaw_damping_torque: Real,
+    axle_drive_forces: Vec<Real>,
+    speed_limiter_active: bool,
+    pub(crate) launch_progress: Real,
+    pub(crate) brake_temperature: Real,
+    brake_fade: Real,
+    weight_transfer: f32,
+    pub(crate) tires: Vec<TireStats>,
+    weight_position: RealVec2,
+    pub(crate) integration_error: Option<IntegrationError>,
+}
+
+/// How far one step strays from the same time taken in two half steps, as an estimate of the
+/// error the step size adds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct IntegrationError {
+    pub(crate) position: Real,
+    pub(crate) velocity: Real,
+    pub(crate) heading: Real,
+}
+
+impl IntegrationError {
+    pub(crate) fn estimate(
+        dt_seconds: f32,
+        inputs: &CarInputs,
+        config: &CarConfig,
+        gravity: f32,
+        slope: RealVec2,
+        state: &CarState,
+    ) -> Self {
+        let mut full_step = state.clone();
+        physics_step(dt_seconds, inputs, config, gravity, slope, &mut full_step);
+
+        let mut half_steps = state.clone();
+        for _ in 0..2 {
+            physics_step(
+                0.5 * dt_seconds,
+                inputs,
+                config,
+                gravity,
+                slope,
+                &mut half_steps,
+            );
+        }
+
+        Self {
+            position: (full_step.position - half_steps.position).length(),
+            velocity: (full_step.velocity - half_steps.velocity).length(),
+            heading: (full_step.heading - half_steps.heading).abs(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct TireStats {
+    pub(crate) axle: usize,
+    pub(crate) steered: bool,
+    pub(crate) driven: bool,
+    pub(crate) slip_angle: Real,
+    pub(crate) slip_velocity: RealVec2,
+    pub(crate) active_weight: Real,
+    pub(crate) friction: Real,
+    pub(crate) brake_torque: Real,
+    pub(crate) is_skidding: bool,
+    pub(crate) is_locked: bool,
+}
+
+impl CarStats {
+    /// Moves these stats only `blend` of the way on from `previous`, for a steadier readout
+    pub(crate) fn ease_from(&mut self, previous: &CarStats, blend: Real) {
+        let ease = |from: Real, to: &mut Real| *to = from + (*to - from) * blend;
+
+        let mut fps = self.fps as Real;
+        ease(previous.fps as Real, &mut fps);
+        self.fps = fps.round() as i32;
+
+        ease(previous.speed_mps, &mut self.speed_mps);
+        ease(previous.steering, &mut self.steering);
+        ease(previous.steer_angle, &mut self.steer_angle);
+        ease(previous.rear_steer_angle, &mut self.rear_steer_angle);
+        ease(previous.yaw_damping_torque, &mut self.yaw_damping_torque);
+        ease(previous.launch_progress, &mut self.launch_progress);
+        ease(previous.brake_temperature, &mut self.brake_temperature);
+        ease(previous.brake_fade, &mut self.brake_fade);
+        ease(previous.weight_position.x, &mut self.weight_position.x);
+        ease(previous.weight_position.y, &mut self.weight_position.y);
+
+        if let (Some(previous), Some(turn_radius)) = (previous.turn_radius, &mut self.turn_radius) {
+            ease(previous, turn_radius);
+        }
+
+        for (previous, force) in previous
+            .axle_drive_forces
+            .iter()
+            .zip(self.axle_drive_forces.iter_mut())
+        {
+            ease(*previous, force);
+        }
+
+        for (previous, tire) in previous.tires.iter().zip(self.tires.iter_mut()) {
+            ease(previous.slip_angle, &mut tire.slip_angle);
+            ease(previous.slip_velocity.x, &mut tire.slip_velocity.x);
+            ease(previous.slip_velocity.y, &mut tire.slip_velocity.y);
+            ease(previous.active_weight, &mut tire.active_weight);
+            ease(previous.friction, &mut tire.friction);
+            ease(previous.brake_torque, &mut tire.brake_torque);
+        }
+    }
+}
+
+/// The stats from the car's last physics step, for the readout to show
+#[derive(Default)]
+pub(crate) struct LatestStats(pub(crate) Option<CarStats>);
+
+/// The stats last shown in the readout, when it is smoothed
+#[derive(Default)]
+pub(crate) struct DisplayedStats(pub(crate) Option<CarStats>);
+
+/// Rolling resistance and air resistance, with the air resistance along and across the car each
+/// falling back to `air_resistance`
+pub(crate) fn drag_force(config: &CarConfig, local_velocity: RealVec2) -> RealVec2 {
+    let air_resistance = RealVec2::new(
+        config.air_resistance_x.unwrap_or(config.air_resistance) as Real,
+        config.air_resistance_y.unwrap_or(config.air_resistance) as Real,
+    );
+
+    -(config.roll_resistance as Real) * local_velocity
+        - air_resistance * local_velocity * local_velocity.abs()
+}
+
+/// The weight that a tire's grip is proportional to: its load, unless `load_sensitivity` is
+/// below one, when grip grows more slowly than load beyond the tire's load at rest, so a pair of
+/// tires grips less in total the more weight shifts from one to the other
+pub(crate) fn gripping_weight(
+    active_weight: Real,
+    static_tire_weight: Real,
+    load_sensitivity: Real,
+) -> Real {
+    if (load_sensitivity - 1.0).abs() <= Real::EPSILON || static_tire_weight <= Real::EPSILON {
+        return active_weight;
+    }
+
+    static_tire_weight
+        * (active_weight / static_tire_weight)
+            .max(0.0)
+            .powf(load_sensitivity)
+}
+
+/// A tire's cornering stiffness, following its load relative to when the car is at rest, so with
+/// a non-zero `stiffness_load_sensitivity`, `weight_transfer` also moves cornering stiffness
+/// between tires, on top of the grip limit which already scales with load
+pub(crate) fn corner_stiffness(
+    stiffness: Real,
+    active_weight: Real,
+    static_tire_weight: Real,
+    stiffness_load_sensitivity: Real,
+) -> Real {
+    let load_ratio = if static_tire_weight > Real::EPSILON {
+        active_weight / static_tire_weight
+    } else {
+        1.0
+    };
+
+    stiffness * (1.0 + stiffness_load_sensitivity * (load_ratio - 1.0)).max(0.0)
+}
+
+/// Axles closer together than this, in meters, once corrected, would pass an unbounded load between
+/// them, so they share the load as if they were one
+const MIN_WHEEL_BASE: Real = 0.05;
+
+/// Axles narrower than this, in meters, transfer load across them as if they were this wide
+const MIN_TRACK_WIDTH: Real = 0.05;
+
+static DEGENERATE_WHEEL_BASE_WARNING: std::sync::Once = std::sync::Once::new();
+static DEGENERATE_TRACK_WIDTH_WARNING: std::sync::Once = std::sync::Once::new();
+
+/// Each axle's share of the car's static weight, and its share of the longitudinal transfer, in
+/// the same units as `transfer_x`.
+///
+/// They're the smallest set of shares that balance both the total weight and the pitch moment
+/// about the centre of gravity, which is the usual lever rule for two axles; two axles are worked
+/// out exactly as the front and rear fields always were, so that such cars behave just as they
+/// did before axles could be configured.
+pub(crate) fn axle_load_shares(
+    axle_positions: &[Real],
+    transfer_x: Real,
+    wheel_base: Real,
+    is_wheel_base_degenerate: bool,
+) -> Vec<(Real, Real)> {
+    let axle_count = axle_positions.len() as Real;
+    let even = vec![(1.0 / axle_count, 0.0); axle_positions.len()];
+
+    if is_wheel_base_degenerate {
+        return even;
+    }
+
+    if let [first, second] = *axle_positions {
+        let transfer = transfer_x / wheel_base;
+
+        // The front axle takes the rear axle's distance from the centre of gravity, over the
+        // wheel base, and the rear the front's
+        let front_and_rear = |front: Real, rear: Real| {
+            (
+                (-rear / wheel_base, -transfer),
+                (front / wheel_base, transfer),
+            )
+        };
+
+        return if first >= second {
+            let (front, rear) = front_and_rear(first, second);
+            vec![front, rear]
+        } else {
+            let (front, rear) = front_and_rear(second, first);
+            vec![rear, front]
+        };
+    }
+
+    let sum_x = axle_positions.iter().sum::<Real>();
+    let sum_x_squared = axle_positions.iter().map(|x| x * x).sum::<Real>();
+    let determinant = axle_count * sum_x_squared - sum_x * sum_x;
+
+    if determinant <= Real::EPSILON {
+        return even;
+    }
+
+    axle_positions
+        .iter()
+        .map(|&axle_position| {
+            (
+                (sum_x_squared - sum_x * axle_position) / determinant,
+                transfer_x * (sum_x - axle_count * axle_position) / determinant,
+            )
+        })
+        .collect()
+}
+
+pub(crate) fn physics_step(
+    dt_seconds: f32,
+    inputs: &CarInputs,
+    config: &CarConfig,
+    gravity: f32,
+    slope: RealVec2,
+    state: &mut CarState,
+) -> CarStats {
+    let dt_seconds = dt_seconds as Real;
+
+    // The handbrake takes a moment to bite fully, but lets go at once
+    let e_brake_ramp_time = config
+        .e_brake_ramp_time
+        .max(state.assists.e_brake_ramp_time);
+
+    state.e_brake = if e_brake_ramp_time > f32::EPSILON {
+        Real::min(
+            inputs.e_brake as Real,
+            state.e_brake + dt_seconds / e_brake_ramp_time as Real,
+        )
+    } else {
+        inputs.e_brake as Real
+    };
+
+    let e_brake = state.e_brake as f32;
+
+    let mass = config.mass as Real;
+    let gravity = gravity as Real;
+    let centre_of_gravity_height = config.centre_of_gravity_height as Real;
+    let weight_transfer = config.weight_transfer as Real;
+
+    let inertia = config.inertia_mode.inertia(config);
+
+    let axles = config.axles();
+    let axle_positions = axles
+        .iter()
+        .map(|axle| (axle.position * config.axle_distance_correction) as Real)
+        .collect::<Vec<_>>();
+
+    let axle_count = axle_positions.len() as Real;
+
+    let wheel_base = axle_positions.iter().copied().fold(Real::MIN, Real::max)
+        - axle_positions.iter().copied().fold(Real::MAX, Real::min);
+    let is_wheel_base_degenerate = axle_positions.len() > 1 && wheel_base < MIN_WHEEL_BASE;
+
+    if is_wheel_base_degenerate {
+        DEGENERATE_WHEEL_BASE_WARNING.call_once(|| {
+            warn!(
+                "The axles are only {} m apart, so they share the car's weight evenly",
+                wheel_base
+            );
+        });
+    }
+
+    // Each axle takes a share of the lateral transfer in proportion to its roll stiffness, so
+    // equal stiffnesses split it evenly
+    let mean_roll_stiffness = axles
+        .iter()
+        .map(|axle| axle.roll_stiffness as Real)
+        .sum::<Real>()
+        / axle_count;
+
+    let local_velocity = RealMat2::from_angle(-state.heading) * state.velocity;
+
+    // Crawling along, the yaw term in the slip angle dwarfs the tiny forward speed and fights any
+    // turn, so measure slip against at least the floor speed
+    let slip_speed_floor = config.slip_speed_floor as Real;
+
+    let transfer_x = weight_transfer * centre_of_gravity_height * state.local_acceleration.x;
+    let load_shares = axle_load_shares(
+        &axle_positions,
+        transfer_x,
+        wheel_base,
+        is_wheel_base_degenerate,
+    );
+
+    // Braking heats the brakes in proportion to the work they do, and they shed heat in
+    // proportion to how hot they are; in degrees above the air around them
+    state.brake_temperature +=
+        (config.brake_heat_rate as Real * inputs.brake as Real * local_velocity.x.abs()
+            - config.brake_cool_rate as Real * state.brake_temperature)
+            * dt_seconds;
+    state.brake_temperature = state.brake_temperature.max(0.0);
+
+    let brake_fade = (1.0
+        - config.brake_fade_per_degree as Real
+            * (state.brake_temperature - config.brake_fade_onset as Real).max(0.0))
+    .max(0.0);
+
+    let foot_brake_force = brake_fade * (inputs.brake * config.brake_force) as Real;
+
+    let brake = Real::min(
+        foot_brake_force + (e_brake * config.e_brake_force) as Real,
+        config.brake_force as Real,
+    );
+    let speed_limiter_active = config
+        .speed_limiter
+        .map_or(false, |limit| state.velocity.length() > limit as Real);
+
+    let throttle = if speed_limiter_active {
+        0.0
+    } else {
+        (inputs.throttle * config.engine_force) as Real
+    };
+
+    // Pulling away from a stop, the clutch slips and only passes on the engine's force gradually
+    state.launch_progress = if config.launch_ramp_time > f32::EPSILON {
+        if throttle < Real::EPSILON && state.velocity.length() < 0.5 {
+            0.0
+        } else {
+            (state.launch_progress + dt_seconds / config.launch_ramp_time as Real).min(1.0)
+        }
+    } else {
+        1.0
+    };
+
+    let drive_force = state.launch_progress * throttle / config.wheel_radius as Real;
+
+    // Launch control holds the car still while armed, then lets the clutch in from the preload
+    // rather than from nothing, limiting the drive to what the driven tires can grip with their
+    // share of the car's weight
+    let drive_force = match state.launch_control {
+        LaunchControlPhase::Off => drive_force,
+        LaunchControlPhase::Armed { preload } => {
+            state.launch_progress = preload as Real;
+
+            0.0
+        }
+        LaunchControlPhase::Launching {
+            grip_ratio,
+            until_speed,
+        } => {
+            if throttle < Real::EPSILON || state.velocity.length() > until_speed as Real {
+                state.launch_control = LaunchControlPhase::Off;
+            }
+
+            let driven_grip = axles
+                .iter()
+                .filter(|axle| axle.driven)
+                .map(|axle| axle.total_tire_grip as Real)
+                .sum::<Real>();
+            let max_drive_force = grip_ratio as Real * driven_grip * mass * gravity / axle_count;
+
+            drive_force.min(max_drive_force)
+        }
+    };
+
+    let total_drive_share = axles
+        .iter()
+        .filter(|axle| axle.driven)
+        .map(|axle| axle.drive_share as Real)
+        .sum::<Real>();
+
+    // Pulling away from rest, steering acts in the direction the car is about to move
+    let steer_direction = if local_velocity.x.abs() > Real::EPSILON {
+        direction_sign(local_velocity.x)
+    } else {
+        direction_sign(drive_force)
+    };
+
+    let car_tire_count = axles.iter().map(AxleConfig::tire_count).sum::<usize>() as Real;
+    let steered_tire_count = axles
+        .iter()
+        .filter(|axle| axle.steered)
+        .map(AxleConfig::tire_count)
+        .sum::<usize>()
+        .max(1) as Real;
+    let front_brake_per_tire = foot_brake_force / steered_tire_count;
+    let brake_per_tire = brake / car_tire_count;
+
+    let mut tires = Vec::new();
+    let mut lateral_force = 0.0;
+    let mut angular_torque = 0.0;
+    let mut brake_force = 0.0;
+    let mut brake_yaw_torque = 0.0;
+    let mut drive_force_x = 0.0;
+    let mut drive_force_y = 0.0;
+    let mut axle_drive_forces = Vec::new();
+    let mut weighted_position = RealVec2::ZERO;
+    let mut total_weight = 0.0;
+
+    for (index, ((axle, &axle_position), &(axle_weight_ratio, axle_transfer))) in axles
+        .iter()
+        .zip(axle_positions.iter())
+        .zip(load_shares.iter())
+        .enumerate()
+    {
+        let axle_weight = mass * (axle_weight_ratio * gravity + axle_transfer);
+
+        let roll_share = if mean_roll_stiffness > Real::EPSILON {
+            axle.roll_stiffness as Real / mean_roll_stiffness
+        } else {
+            1.0
+        };
+
+        let track_width = axle.half_width as Real * 2.0;
+
+        if track_width > Real::EPSILON && track_width < MIN_TRACK_WIDTH {
+            DEGENERATE_TRACK_WIDTH_WARNING.call_once(|| {
+                warn!(
+                    "An axle is only {} m wide, so it transfers load as if it were {} m wide",
+                    track_width, MIN_TRACK_WIDTH
+                );
+            });
+        }
+
+        let transfer_y = if axle.half_width > f32::EPSILON {
+            weight_transfer * state.local_acceleration.y * centre_of_gravity_height
+                / track_width.max(MIN_TRACK_WIDTH)
+                * 20.0
+                * roll_share
+        } else {
+            0.0
+        };
+
+        let grip =
+            (axle.total_tire_grip * (1.0 - e_brake * (1.0 - axle.e_brake_grip_ratio))) as Real;
+
+        let lateral_offsets = axle.lateral_offsets();
+        let first_tire = tires.len();
+        let max_brake_ratio = (first_tire..first_tire + lateral_offsets.len())
+            .map(|tire| config.tire_brake_ratio(tire))
+            .fold(0.0, Real::max);
+
+        // The hardest braked wheel on the axle locks first, and only slides straight on
+        // regardless of where it points when the config asks for it
+        let is_locked = axle.steered
+            && !state.assists.abs
+            && config.front_brake_lock_ratio > 0.0
+            && local_velocity.x.abs() > 1.0
+            && max_brake_ratio * front_brake_per_tire
+                > config.front_brake_lock_ratio as Real * grip * axle_weight;
+
+        let steer_angle = if is_locked && config.locked_wheels_slide_straight {
+            0.0
+        } else {
+            config.axle_steer_angle(axle, state.steer_angle)
+        };
+
+        let slip_angle = Real::atan2(
+            local_velocity.y + axle_position * state.yaw_rate,
+            local_velocity.x.abs().max(slip_speed_floor),
+        ) - steer_direction * steer_angle;
+
+        let tire_count = lateral_offsets.len() as Real;
+        let static_tire_weight = mass * axle_weight_ratio * gravity;
+        let mut axle_friction = 0.0;
+        let mut axle_brake_yaw_torque = 0.0;
+
+        let tire_drive_force = if axle.driven && total_drive_share > Real::EPSILON {
+            drive_force * axle.drive_share as Real / total_drive_share / tire_count
+        } else {
+            0.0
+        };
+        let mut axle_drive_force = 0.0;
+
+        for lateral_offset in lateral_offsets {
+            let lateral_offset = lateral_offset as Real;
+            let active_weight = axle_weight - transfer_y * lateral_offset.signum();
+
+            // The most force the tire can pass to the ground before it slides
+            let grip_limit = (grip
+                * gripping_weight(
+                    active_weight,
+                    static_tire_weight,
+                    config.load_sensitivity as Real,
+                )
+                / tire_count)
+                .max(0.0);
+
+            // Uneven brakes across the axle turn the car towards the side braking harder. ABS
+            // keeps each tire's brake within its grip, but lets go while the handbrake is
+            // pulled, so that handbrake turns still lock the wheels
+            let tire_brake_force = config.tire_brake_ratio(tires.len()) * brake_per_tire;
+            let tire_brake_force = if state.assists.abs && e_brake < f32::EPSILON {
+                tire_brake_force.min(grip_limit)
+            } else {
+                tire_brake_force
+            };
+            brake_force += tire_brake_force;
+            axle_brake_yaw_torque +=
+                lateral_offset * tire_brake_force * direction_sign(local_velocity.x);
+
+            // Traction control likewise keeps each driven tire's drive within its grip
+            axle_drive_force += if state.assists.traction_control {
+                clamp(tire_drive_force, -grip_limit, grip_limit).1
+            } else {
+                tire_drive_force
+            };
+
+            let corner_stiffness = corner_stiffness(
+                axle.corner_stiffness as Real,
+                active_weight,
+                static_tire_weight,
+                config.stiffness_load_sensitivity as Real,
+            );
+
+            let (is_skidding, friction) = clamp(-corner_stiffness * slip_angle, -grip, grip);
+            let friction = friction
+                * gripping_weight(
+                    active_weight,
+                    static_tire_weight,
+                    config.load_sensitivity as Real,
+                );
+
+            axle_friction += friction / tire_count;
+
+            weighted_position +=
+                active_weight / tire_count * RealVec2::new(axle_position, lateral_offset);
+            total_weight += active_weight / tire_count;
+
+            // The contact patch's velocity across the direction that the tire rolls
+            let patch_velocity =
+                local_velocity + state.yaw_rate * RealVec2::new(-lateral_offset, axle_position);
+            let rolling_direction = RealVec2::new(steer_angle.cos(), steer_angle.sin());
+            let slip_velocity =
+                patch_velocity - patch_velocity.dot(rolling_direction) * rolling_direction;
+
+            tires.push(TireStats {
+                axle: index,
+                steered: axle.steered,
+                driven: axle.driven,
+                slip_angle,
+                slip_velocity,
+                active_weight,
+                friction,
+                brake_torque: tire_brake_force * config.wheel_radius as Real,
+                is_skidding: is_skidding || is_locked,
+                is_locked,
+            });
+        }
+
+        lateral_force += steer_angle.cos() * axle_friction;
+        angular_torque += axle_friction * axle_position;
+        brake_yaw_torque += axle_brake_yaw_torque;
+
+        // Driven wheels push along the way they point, so a steered driven axle also pulls the
+        // nose round
+        drive_force_x += steer_angle.cos() * axle_drive_force;
+        drive_force_y += steer_angle.sin() * axle_drive_force;
+        angular_torque += steer_angle.sin() * axle_drive_force * axle_position;
+
+        axle_drive_forces.push(axle_drive_force);
+    }
+
+    let weight_position = if total_weight > Real::EPSILON {
+        weighted_position / total_weight
+    } else {
+        RealVec2::ZERO
+    };
+
+    let traction_force_x = drive_force_x - brake_force * direction_sign(local_velocity.x);
+    let traction_force_y = drive_force_y;
+
+    let drag_force = drag_force(config, local_velocity);
+
+    // The part of the car's weight along the ground, pulling it downhill
+    let slope_force = RealMat2::from_angle(-state.heading)
+        * (-mass * gravity * slope / (1.0 + slope.length_squared()).sqrt());
+
+    let total_force_x = traction_force_x + drag_force.x + slope_force.x;
+    let mut total_force_y = traction_force_y + drag_force.y + slope_force.y + lateral_force;
+
+    if state.velocity.length() > 10.0 {
+        total_force_y *=
+            (state.velocity.length() + 1.0) / (21.0 - config.speed_turning_stability as Real);
+    }
+
+    let total_force_y = total_force_y;
+
+    state.local_acceleration.x = total_force_x / mass;
+    state.local_acceleration.y = total_force_y / mass;
+
+    state.acceleration = RealMat2::from_angle(state.heading) * state.local_acceleration;
+
+    state.velocity += state.acceleration * dt_seconds;
+
+    let mut absolute_velocity = state.velocity.length();
+
+    // A car at rest stays put unless the slope can overcome its brakes
+    if absolute_velocity < 0.5 && throttle < Real::EPSILON && slope_force.length() <= brake_force {
+        state.local_acceleration = RealVec2::ZERO;
+        absolute_velocity = 0.0;
+        state.velocity = RealVec2::ZERO;
+        angular_torque = 0.0;
+        brake_yaw_torque = 0.0;
+        state.yaw_rate = 0.0;
+        state.acceleration = RealVec2::ZERO;
+    }
+
+    let absolute_velocity = absolute_velocity;
+    let angular_torque = angular_torque + brake_yaw_torque;
+
+    let speed_kph = absolute_velocity * 3.6;
+
+    // Opposes the yaw rate in proportion to it, but never by so much in one step that it would
+    // turn the rotation around
+    let yaw_damping = config.yaw_damping.max(state.assists.yaw_damping) as Real;
+    let yaw_damping_torque = -yaw_damping.min(1.0 / dt_seconds) * inertia * state.yaw_rate;
+
+    let angular_acceleration = (angular_torque + yaw_damping_torque) / inertia;
+
+    state.yaw_rate += angular_acceleration * dt_seconds;
+
+    if ((absolute_velocity < 1.0 || state.local_acceleration.y.abs() < 2.5)
+        && state.steer_angle.abs() < Real::EPSILON
+        && brake_yaw_torque.abs() < Real::EPSILON)
+        || speed_kph < 0.2
+    {
+        state.yaw_rate = 0.0;
+    }
+
+    // Turning at a given yaw rate traces a circle of radius speed / yaw rate, so a minimum
+    // radius caps the yaw rate in proportion to speed
+    if config.min_turn_radius > f32::EPSILON {
+        let max_yaw_rate = absolute_velocity / config.min_turn_radius as Real;
+
+        state.yaw_rate = clamp(state.yaw_rate, -max_yaw_rate, max_yaw_rate).1;
+    }
+
+    let turn_radius = if state.yaw_rate.abs() > Real::EPSILON {
+        Some(absolute_velocity / state.yaw_rate.abs())
+    } else {
+        None
+    };
+
+    state.heading += state.yaw_rate * dt_seconds;
+    state.position += state.velocity * dt_seconds;
+
+    CarStats {
+        fps: (1.0 / dt_seconds) as i32,
+        speed_mps: absolute_velocity,
+        steering: state.steer,
+        steer_angle: state.steer_angle,
+        rear_steer_angle: config.rear_steer_ratio as Real * state.steer_angle,
+        turn_radius,
+        inertia,
+        yaw_damping_torque,
+        axle_drive_forces,
+        speed_limiter_active,
+        launch_progress: state.launch_progress,
+        brake_temperature: state.brake_temperature,
+        brake_fade,
+        weight_transfer: config.weight_transfer,
+        tires,
+        weight_position,
+        integration_error: None,
+    }
+}
+
+/// Periodic `debug` level records of the physics, for capturing to a terminal or file with
+/// `RUST_LOG=driving_test::physics=debug`
+pub struct PhysicsLogging {
+    pub enabled: bool,
+    pub interval_seconds: f64,
+    last_logged_seconds: f64,
+}
+
+impl Default for PhysicsLogging {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_seconds: 0.25,
+            last_logged_seconds: f64::NEG_INFINITY,
+        }
+    }
+}
+
+impl PhysicsLogging {
+    pub(crate) fn is_due(&mut self, now_seconds: f64) -> bool {
+        if !self.enabled || now_seconds - self.last_logged_seconds < self.interval_seconds {
+            return false;
+        }
+
+        self.last_logged_seconds = now_seconds;
+
+        true
+    }
+}
+
+fn log_physics(state: &CarState, stats: &CarStats) {
+    debug!(
+        target: "driving_test::physics",
+        speed = stats.speed_mps as f64,
+        heading = state.heading as f64,
+        yaw_rate = state.yaw_rate as f64,
+        steer_angle = stats.steer_angle as f64,
+        local_acceleration_x = state.local_acceleration.x as f64,
+        local_acceleration_y = state.local_acceleration.y as f64,
+        "car"
+    );
+
+    for (index, tire) in stats.tires.iter().enumerate() {
+        debug!(
+            target: "driving_test::physics",
+            tire = index,
+            axle = tire.axle,
+            slip_angle = tire.slip_angle as f64,
+            active_weight = tire.active_weight as f64,
+            friction = tire.friction as f64,
+            brake_torque = tire.brake_torque as f64,
+            is_skidding = tire.is_skidding,
+            is_locked = tire.is_locked,
+            "tire"
+        );
+    }
+}
+
+pub(crate) fn toggle_physics_logging(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut logging: ResMut<PhysicsLogging>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F3) {
+        logging.enabled = !logging.enabled;
+        info!(
+            "Physics logging {}",
+            if logging.enabled { "on" } else { "off" }
+        );
+    }
+}
+
+/// Pauses the physics, either when set directly or, if opted in to, while the window is out of
+/// focus, so that nothing happens while nobody is watching
+#[derive(Default)]
+pub struct SimControl {
+    pub paused: bool,
+    pub pause_on_focus_loss: bool,
+    focus_lost: bool,
+}
+
+impl SimControl {
+    pub(crate) fn is_paused(&self) -> bool {
+        self.paused || (self.pause_on_focus_loss && self.focus_lost)
+    }
+}
+
+pub(crate) struct PausedText;
+
+pub(crate) fn track_window_focus(
+    mut events: EventReader<WindowFocused>,
+    mut sim_control: ResMut<SimControl>,
+    mut text: Query<&mut Text, With<PausedText>>,
+) {
+    for event in events.iter() {
+        if event.id == WindowId::primary() {
+            sim_control.focus_lost = !event.focused;
+        }
+    }
+
+    let value = if !sim_control.is_paused() {
+        ""
+    } else if sim_control.paused {
+        "Paused"
+    } else {
+        "Paused (focus lost)"
+    };
+
+    for mut text in text.iter_mut() {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.to_string();
+        }
+    }
+}
+
+pub(crate) fn physics_running(sim_control: Res<SimControl>) -> ShouldRun {
+    if sim_control.is_paused() {
+        ShouldRun::No
+    } else {
+        ShouldRun::Yes
+    }
+}
+
+pub(crate) fn physics_paused(sim_control: Res<SimControl>) -> ShouldRun {
+    if sim_control.is_paused() {
+        ShouldRun::Yes
+    } else {
+        ShouldRun::No
+    }
+}
+
+/// A tire started skidding
+pub struct SkidStarted {
+    pub car: Entity,
+    pub tire: Entity,
+}
+
+/// A tire stopped skidding
+pub struct SkidEnded {
+    pub car: Entity,
+    pub tire: Entity,
+}
+
+/// A car spun, turning far away from the way it was going
+pub struct SpunOut {
+    pub car: Entity,
+}
+
+/// A car stopped dead against the edge of a clamped world
+pub struct WallHit {
+    pub car: Entity,
+    /// The speed lost into the wall, in m/s
+    pub speed: f32,
+}
+
+/// A car drove through the current gate
+pub struct CheckpointPassed {
+    pub car: Entity,
+    /// The gate's index in the gates' placements
+    pub gate: usize,
+}
+
+/// The events sent by the physics, grouped to keep the step's parameters within Bevy's limit
+#[derive(SystemParam)]
+pub(crate) struct PhysicsEvents<'a> {
+    skid_started: EventWriter<'a, SkidStarted>,
+    skid_ended: EventWriter<'a, SkidEnded>,
+    spun_out: EventWriter<'a, SpunOut>,
+    wall_hits: EventWriter<'a, WallHit>,
+}
+
+/// The time that the physics moves on by, which mirrors the real time by default, but can be
+/// slowed down, sped up or given a fixed step, so that runs can be repeated exactly
+pub struct SimTime {
+    /// Steps the physics by exactly this many seconds each frame, however long the frame took
+    pub fixed_delta: Option<f32>,
+    /// How many seconds of simulation pass in each real second
+    pub time_scale: f32,
+    pub(crate) delta_seconds: f32,
+    pub(crate) seconds_since_startup: f64,
+}
+
+impl Default for SimTime {
+    fn default() -> Self {
+        Self {
+            fixed_delta: None,
+            time_scale: 1.0,
+            delta_seconds: 0.0,
+            seconds_since_startup: 0.0,
+        }
+    }
+}
+
+impl SimTime {
+    /// Moves the simulation on after a frame that took `real_delta` seconds
+    pub fn advance(&mut self, real_delta: f32) {
+        self.delta_seconds = self.fixed_delta.unwrap_or(real_delta) * self.time_scale;
+        self.seconds_since_startup += self.delta_seconds as f64;
+    }
+
+    pub fn delta_seconds(&self) -> f32 {
+        self.delta_seconds
+    }
+
+    /// Steps by `delta_seconds` this frame instead, as a recording of the steps demands
+    pub(crate) fn replace_delta(&mut self, delta_seconds: f32) {
+        self.seconds_since_startup += (delta_seconds - self.delta_seconds) as f64;
+        self.delta_seconds = delta_seconds;
+    }
+
+    pub fn seconds_since_startup(&self) -> f64 {
+        self.seconds_since_startup
+    }
+
+    /// Stands still for a frame in which the physics is paused
+    pub(crate) fn hold(&mut self) {
+        self.delta_seconds = 0.0;
+    }
+}
+
+fn advance_sim_time(time: Res<Time>, mut sim_time: ResMut<SimTime>) {
+    sim_time.advance(time.delta_seconds());
+}
+
+fn hold_sim_time(mut sim_time: ResMut<SimTime>) {
+    sim_time.hold();
+}
+
+pub(crate) fn step(
+    mut commands: Commands,
+    time: Res<SimTime>,
+    configs: ResMut<Assets<CarConfig>>,
+    gravity: Option<Res<Gravity>>,
+    terrain: Res<Terrain>,
+    world_bounds: Res<WorldBounds>,
+    mut events: PhysicsEvents,
+    mut cars: Query<(
+        Entity,
+        &Handle<CarConfig>,
+        &mut CarState,
+        &mut CarInputs,
+        &mut DriftScore,
+        &mut SpinDetector,
+        &mut PerfTest,
+        &CruiseControl,
+        &mut Odometer,
+        &mut LatestStats,
+        &mut Transform,
+        &CarComponents,
+        Option<&PlayerControlled>,
+    )>,
+    mut weight_marker: Query<&mut WeightMarker>,
+    mut tires: Query<&mut Tire>,
+    debug_text_settings: Res<DebugTextSettings>,
+    assist_level: Res<AssistLevel>,
+    replay: Res<Replay>,
+    mut physics_logging: ResMut<PhysicsLogging>,
+) {
+    // The live car is frozen while a replay is playing
+    if replay.active {
+        return;
+    }
+
+    let log_this_frame = physics_logging.is_due(time.seconds_since_startup());
+
+    for (
+        car,
+        config,
+        mut state,
+        mut car_inputs,
+        mut drift_score,
+        mut spin_detector,
+        mut perf_test,
+        cruise_control,
+        mut odometer,
+        mut latest_stats,
+        mut transform,
+        car_components,
+        player,
+    ) in cars.iter_mut()
+    {
+        let config = match configs.get(config.clone()) {
+            Some(config) => config,
+            None => continue,
+        };
+
+        state.assists = assist_level.assists();
+
+        let drive = |driver_inputs| {
+            perf_test
+                .inputs()
+                .unwrap_or_else(|| cruise_control.inputs(driver_inputs, &state))
+        };
+
+        // Only the players' own devices need a deadzone, so the computer's, cruise control's and
+        // the perf test's inputs reach the car as they are. The car's inputs keep what the driver
+        // asked for, so that recordings shape them afresh.
+        let inputs = drive(*car_inputs);
+        let shaped_inputs = match player {
+            Some(_) => drive(config.shape_inputs(*car_inputs)),
+            None => inputs,
+        };
+
+        let raw_input_steer = shaped_inputs.steer as Real;
+
+        let steer_input_time_constant = config
+            .steer_input_time_constant
+            .max(state.assists.steer_input_time_constant);
+
+        let input_steer = if steer_input_time_constant > f32::EPSILON {
+            let dt_seconds = time.delta_seconds();
+            let blend = (dt_seconds / (steer_input_time_constant + dt_seconds)) as Real;
+
+            state.steer_input + (raw_input_steer - state.steer_input) * blend
+        } else {
+            raw_input_steer
+        };
+
+        state.steer_input = input_steer;
+
+        let steer_authority = config.speed_steer_curve.authority(
+            state.velocity.length(),
+            config.speed_steer_correction as Real,
+        );
+
+        let target_steer = config.steer_response(input_steer) * steer_authority;
+
+        let max_steer_offset = (config.steer_speed * time.delta_seconds()) as Real;
+
+        // Close in on the target by a fraction of the remaining distance, so the steering eases
+        // in rather than chattering about a target that moves with speed, but never faster than
+        // the steering rate allows
+        let remaining_steer = target_steer - state.steer;
+
+        let steer_settle_time = config
+            .steer_settle_time
+            .max(state.assists.steer_settle_time);
+
+        let steer_offset = if steer_settle_time > f32::EPSILON {
+            let dt_seconds = time.delta_seconds();
+            let blend = (dt_seconds / (steer_settle_time + dt_seconds)) as Real;
+
+            remaining_steer * blend
+        } else {
+            remaining_steer
+        };
+
+        let (_, steer_offset) = clamp(steer_offset, -max_steer_offset, max_steer_offset);
+
+        state.steer += steer_offset;
+
+        let max_steer = config.max_steer_at(state.velocity.length());
+
+        state.steer_angle = max_steer * state.steer;
+
+        let gravity = gravity.as_ref().map_or(config.gravity, |gravity| gravity.0);
+
+        *car_inputs = inputs;
+
+        let slope = terrain.gradient(state.position);
+
+        let integration_error = if debug_text_settings.integration_error {
+            Some(IntegrationError::estimate(
+                time.delta_seconds(),
+                &shaped_inputs,
+                config,
+                gravity,
+                slope,
+                &state,
+            ))
+        } else {
+            None
+        };
+
+        let stats = CarStats {
+            integration_error,
+            ..physics_step(
+                time.delta_seconds(),
+                &shaped_inputs,
+                config,
+                gravity,
+                slope,
+                &mut state,
+            )
+        };
+
+        if spin_detector.update(&state) {
+            events.spun_out.send(SpunOut { car });
+        }
+
+        drift_score.update(time.delta_seconds(), &stats);
+        perf_test.update(time.delta_seconds(), &state);
+        odometer.update(time.delta_seconds(), &state);
+
+        if log_this_frame {
+            log_physics(&state, &stats);
+        }
+
+        let CarState {
+            position, velocity, ..
+        } = &mut *state;
+
+        match world_bounds.apply(position, velocity) {
+            BoundsContact::None => (),
+            BoundsContact::Wall { speed } => events.wall_hits.send(WallHit {
+                car,
+                speed: speed as f32,
+            }),
+            BoundsContact::Wrapped => {
+                for &tire in car_components.tires.iter() {
+                    commands.entity(tire).insert(Teleported);
+                }
+            }
+        }
+
+        transform.translation = from_real(state.position).extend(Z_CAR);
+        transform.rotation = Quat::from_rotation_z(state.heading as f32);
+
+        let mut marker = weight_marker.get_mut(car_components.weight_marker).unwrap();
+        let weight_position = from_real(stats.weight_position);
+
+        marker.position = if config.weight_marker_time_constant > f32::EPSILON {
+            let dt_seconds = time.delta_seconds();
+            let blend = dt_seconds / (config.weight_marker_time_constant + dt_seconds);
+
+            marker.position + (weight_position - marker.position) * blend
+        } else {
+            weight_position
+        };
+
+        // A tire's active weight is expressed relative to its whole axle's load
+        let axle_count = stats.tires.last().map_or(1, |tire| tire.axle + 1);
+        let static_tire_weight = (config.mass * gravity) as Real / axle_count as Real;
+
+        for (&entity, tire_stats) in car_components.tires.iter().zip(stats.tires.iter()) {
+            if let Ok(mut tire) = tires.get_mut(entity) {
+                match (tire.is_skidding, tire_stats.is_skidding) {
+                    (false, true) => events.skid_started.send(SkidStarted { car, tire: entity }),
+                    (true, false) => events.skid_ended.send(SkidEnded { car, tire: entity }),
+                    _ => (),
+                }
+
+                tire.is_skidding = tire_stats.is_skidding;
+                tire.is_locked = tire_stats.is_locked;
+                tire.driven = tire_stats.driven;
+                tire.slip = tire_stats.slip_angle.abs() as f32;
+                tire.slip_velocity = from_real(tire_stats.slip_velocity);
+                tire.load_ratio = (tire_stats.active_weight / static_tire_weight) as f32;
+                tire.speed = stats.speed_mps as f32;
+            }
+        }
+
+        latest_stats.0 = Some(stats);
+    }
+}
+
+/// A bundle of driving assists, so they can be turned up together rather than tuned one by one
+///
+/// Each level only raises the car's own settings, so a config that is already smoother than the
+/// level asks for is left alone
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssistLevel {
+    None,
+    Low,
+    Full,
+}
+
+impl Default for AssistLevel {
+    fn default() -> Self {
+        Self::None
+    }
+}
+
+impl AssistLevel {
+    pub(crate) fn next(self) -> Self {
+        match self {
+            Self::None => Self::Low,
+            Self::Low => Self::Full,
+            Self::Full => Self::None,
+        }
+    }
+
+    pub(crate) fn assists(self) -> Assists {
+        match self {
+            Self::None => Assists::default(),
+            Self::Low => Assists {
+                steer_input_time_constant: 0.05,
+                steer_settle_time: 0.05,
+                yaw_damping: 0.5,
+                e_brake_ramp_time: 0.1,
+                abs: true,
+                traction_control: false,
+            },
+            Self::Full => Assists {
+                steer_input_time_constant: 0.1,
+                steer_settle_time: 0.15,
+                yaw_damping: 1.5,
+                e_brake_ramp_time: 0.3,
+                abs: true,
+                traction_control: true,
+            },
+        }
+    }
+}
+
+/// What an `AssistLevel` asks of a car: the smallest steering input smoothing, steering settle
+/// time, yaw damping and e-brake ramp time to use, whatever its config says, and whether to keep
+/// its brakes and drive within its tires' grip
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub(crate) struct Assists {
+    steer_input_time_constant: f32,
+    pub(crate) steer_settle_time: f32,
+    pub(crate) yaw_damping: f32,
+    pub(crate) e_brake_ramp_time: f32,
+    pub(crate) abs: bool,
+    pub(crate) traction_control: bool,
+}
+
+pub(crate) fn cycle_assist_level(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut assist_level: ResMut<AssistLevel>,
+) {
+    if keyboard_input.just_pressed(KeyCode::A) {
+        *assist_level = assist_level.next();
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct WeightTransferOverride(pub(crate) Option<f32>);
+
+pub(crate) fn tune_weight_transfer(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut weight_transfer: ResMut<WeightTransferOverride>,
+    mut configs: ResMut<Assets<CarConfig>>,
+    cars: Query<&Handle<CarConfig>>,
+) {
+    let nudge = if keyboard_input.just_pressed(KeyCode::RBracket) {
+        0.05
+    } else if keyboard_input.just_pressed(KeyCode::LBracket) {
+        -0.05
+    } else {
+        0.0
+    };
+
+    if nudge != 0.0 {
+        let current = weight_transfer.0.or_else(|| {
+            cars.iter()
+                .find_map(|config| configs.get(config.clone()))
+                .map(|config| config.weight_transfer)
+        });
+
+        if let Some(current) = current {
+            weight_transfer.0 = Some((current + nudge).clamp(0.0, 1.0));
+        }
+    }
+
+    // Reapply after every hot reload, but only write when the value differs so the
+    // asset isn't marked as modified every frame
+    if let Some(value) = weight_transfer.0 {
+        for config in cars.iter() {
+            let needs_update = configs
+                .get(config.clone())
+                .map_or(false, |config| config.weight_transfer != value);
+
+            if needs_update {
+                if let Some(config) = configs.get_mut(config.clone()) {
+                    config.weight_transfer = value;
+                }
+            }
+        }
+    }
+}
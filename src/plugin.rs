@@ -0,0 +1,455 @@
+//! Wiring every system into the app
+
+use crate::{
+    camera::{
+        apply_pixels_per_meter, follow_car, open_second_window, set_up_second_window, AspectPolicy,
+        CameraBounds, CameraDeadzone, CameraFraming, CameraLookAhead, CameraMode, CameraShake,
+        CameraTransition, LetterboxBar, MainCamera, PixelsPerMeter, SecondWindow, WeightCamera,
+    },
+    car::{
+        apply_config_reloads, apply_initial_state, apply_palette, beep_while_reversing,
+        launch_control, place_brake_lights, place_bumpers, place_slip_arrows, place_tires,
+        place_weight_marker, resolve_car_configs, score_spins, spawn_ai_car, spawn_car,
+        spawn_second_car, spawn_tires, track_stuck_cars, ConfigReloadPolicy, LaunchControl,
+        Palette, PaletteMaterials, ReversingBeep, SlipArrows, StuckReset,
+    },
+    effects::{
+        age_smoke, despawn_timers, emit_smoke, log_rng_seed, move_particles, restart_rng,
+        RngResource, SmokeMaterials, SprayConfig, SprayMaterials, TireSmoke,
+    },
+    hud::{
+        apply_hud_layout, edit_config, line_mesh, plot_steer_curve, report_missing_hud_font,
+        show_debug_text, show_drift_gauge, spawn_hud, spawn_readouts, toggle_debug_text,
+        toggle_units, ConfigEditor, DebugTextSettings, DriftDial, DriftGauge, DriftGaugeMaterials,
+        DriftNeedle, HudLayout, SteerCurveLines, SteerCurvePlot, UnitSystem,
+    },
+    physics::{
+        cycle_assist_level, physics_paused, physics_running, step, toggle_physics_logging,
+        track_window_focus, tune_weight_transfer, AssistLevel, CarConfig, CarConfigLoader,
+        CheckpointPassed, PhysicsLogging, SimControl, SimTime, SkidEnded, SkidStarted, SpunOut,
+        Terrain, WallHit, WeightTransferOverride, WorldBounds,
+    },
+    scenery::{
+        place_backdrop, spawn_backdrop, spawn_grid, spawn_terrain_shading, toggle_grid,
+        GridSettings, GroundTexture,
+    },
+    skids::{
+        bake_skids, cleanup_skids, cull_skids, repeat_textures, setup_skid_material, skid,
+        SkidBaking, SkidBlend, SkidCulling, SkidTexture, SkidWidth,
+    },
+    telemetry::{
+        record_telemetry, replay, save_and_load_world, GhostMaterials, InputRecordings, Replay,
+        ReplaySettings, SpinReplay, TelemetryRecorder, WorldSnapshots,
+    },
+    track::{
+        drive_through_gates, drop_obstacles, spawn_gate_posts, spawn_track, time_laps, AiControls,
+        AiRoute, BestLaps, CornerAdvisory, Gates, LapTimer, TrackConfig, TrackConfigLoader,
+        TrackHandle,
+    },
+};
+use bevy::{prelude::*, transform::TransformSystem};
+
+fn setup(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    asset_server: Res<AssetServer>,
+    pixels_per_meter: Res<PixelsPerMeter>,
+    steer_curve_plot: Res<SteerCurvePlot>,
+    drift_gauge: Res<DriftGauge>,
+) {
+    asset_server.watch_for_changes().unwrap();
+
+    let letterbox_material = materials.add(ColorMaterial::color(Color::BLACK));
+
+    let steer_curve_mesh = meshes.add(line_mesh());
+    let drift_dial_mesh = meshes.add(line_mesh());
+    let drift_needle_mesh = meshes.add(line_mesh());
+
+    let drift_gauge_materials = DriftGaugeMaterials {
+        normal: materials.add(ColorMaterial::color(Color::WHITE)),
+        sweet_spot: materials.add(ColorMaterial::color(Color::GREEN)),
+    };
+
+    commands
+        .spawn_bundle({
+            let mut camera = OrthographicCameraBundle::new_2d();
+
+            camera.orthographic_projection.scale = 1.0 / pixels_per_meter.0;
+
+            camera
+        })
+        .insert(MainCamera)
+        .with_children(|camera| {
+            for &side in [-1.0, 1.0].iter() {
+                camera
+                    .spawn_bundle(SpriteBundle {
+                        material: letterbox_material.clone(),
+                        visible: Visible {
+                            is_visible: false,
+                            is_transparent: false,
+                        },
+                        ..Default::default()
+                    })
+                    .insert(LetterboxBar { side });
+            }
+
+            camera
+                .spawn_bundle(SpriteBundle {
+                    sprite: Sprite {
+                        size: Vec2::ONE,
+                        ..Default::default()
+                    },
+                    mesh: steer_curve_mesh,
+                    material: materials.add(ColorMaterial::color(Color::WHITE)),
+                    visible: Visible {
+                        is_visible: steer_curve_plot.visible,
+                        is_transparent: false,
+                    },
+                    ..Default::default()
+                })
+                .insert(SteerCurveLines);
+
+            let drift_gauge_bundle = |mesh| SpriteBundle {
+                sprite: Sprite {
+                    size: Vec2::ONE,
+                    ..Default::default()
+                },
+                mesh,
+                material: drift_gauge_materials.normal.clone(),
+                visible: Visible {
+                    is_visible: drift_gauge.visible,
+                    is_transparent: false,
+                },
+                ..Default::default()
+            };
+
+            camera
+                .spawn_bundle(drift_gauge_bundle(drift_dial_mesh))
+                .insert(DriftDial);
+            camera
+                .spawn_bundle(drift_gauge_bundle(drift_needle_mesh))
+                .insert(DriftNeedle);
+        });
+
+    commands.insert_resource(drift_gauge_materials);
+
+    commands.insert_resource(TrackHandle(asset_server.load("default.track")));
+
+    commands.insert_resource(SprayMaterials {
+        grass: materials.add(ColorMaterial::color(Color::rgb(0.35, 0.6, 0.25))),
+        dirt: materials.add(ColorMaterial::color(Color::rgb(0.6, 0.45, 0.3))),
+    });
+}
+
+/// Within `CoreStage::Update`, everything that feeds the physics runs before `Physics`, and
+/// everything that presents its results (the car's children, the HUD) runs after it, so they all
+/// see the same frame's state. Camera shake is added on top once `FollowCar` has placed the camera.
+///
+/// Anything that needs world positions of the car's children (skids, spray) runs in
+/// `CoreStage::PostUpdate` after transform propagation, comparing against the previous frame's
+/// `GlobalTransform`, which is seeded when a tire is spawned and refreshed last.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, SystemLabel)]
+pub(crate) enum MyStages {
+    Inputs,
+    Physics,
+    FollowCar,
+    InitPreviousGlobalTransform,
+    UpdatePreviousGlobalTransform,
+}
+
+/// Registers the car asset and all of the driving systems.
+///
+/// By default this also spawns the track, a car driven by `assets/config.car`, and the debug HUD;
+/// apps that manage their own entities can turn those off.
+pub struct CarPlugin {
+    spawn_default_car: bool,
+    spawn_second_car: bool,
+    spawn_ai_car: bool,
+    spawn_hud: bool,
+    open_second_window: bool,
+}
+
+impl Default for CarPlugin {
+    fn default() -> Self {
+        Self {
+            spawn_default_car: true,
+            spawn_second_car: false,
+            spawn_ai_car: false,
+            spawn_hud: true,
+            open_second_window: false,
+        }
+    }
+}
+
+impl CarPlugin {
+    pub fn with_default_car(mut self, spawn_default_car: bool) -> Self {
+        self.spawn_default_car = spawn_default_car;
+        self
+    }
+
+    /// Adds a second player's car, driven by "I", "J", "K", "L" and right shift
+    pub fn with_second_car(mut self, spawn_second_car: bool) -> Self {
+        self.spawn_second_car = spawn_second_car;
+        self
+    }
+
+    /// Opens a second window for the second player's car, which follows it with its own readout,
+    /// while the main window follows the first player's alone
+    pub fn with_second_window(mut self, open_second_window: bool) -> Self {
+        self.open_second_window = open_second_window;
+        self
+    }
+
+    /// Adds a car driven round the gates by the computer
+    pub fn with_ai_car(mut self, spawn_ai_car: bool) -> Self {
+        self.spawn_ai_car = spawn_ai_car;
+        self
+    }
+
+    pub fn with_hud(mut self, spawn_hud: bool) -> Self {
+        self.spawn_hud = spawn_hud;
+        self
+    }
+}
+
+impl Plugin for CarPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_asset::<CarConfig>()
+            .init_asset_loader::<CarConfigLoader>()
+            .add_asset::<TrackConfig>()
+            .init_asset_loader::<TrackConfigLoader>()
+            .init_resource::<PixelsPerMeter>()
+            .init_resource::<SprayConfig>()
+            .init_resource::<TireSmoke>()
+            .init_resource::<SmokeMaterials>()
+            .init_resource::<GhostMaterials>()
+            .init_resource::<TelemetryRecorder>()
+            .init_resource::<WeightTransferOverride>()
+            .init_resource::<DebugTextSettings>()
+            .init_resource::<WorldBounds>()
+            .init_resource::<Terrain>()
+            .init_resource::<CameraMode>()
+            .init_resource::<SlipArrows>()
+            .init_resource::<Palette>()
+            .init_resource::<PaletteMaterials>()
+            .init_resource::<UnitSystem>()
+            .init_resource::<AssistLevel>()
+            .init_resource::<ConfigReloadPolicy>()
+            .init_resource::<CameraShake>()
+            .init_resource::<CameraDeadzone>()
+            .init_resource::<StuckReset>()
+            .init_resource::<ReversingBeep>()
+            .init_resource::<SimControl>()
+            .init_resource::<SimTime>()
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                advance_sim_time
+                    .system()
+                    .with_run_criteria(physics_running.system()),
+            )
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                hold_sim_time
+                    .system()
+                    .with_run_criteria(physics_paused.system()),
+            )
+            .init_resource::<LaunchControl>()
+            .init_resource::<WeightCamera>()
+            .init_resource::<CameraBounds>()
+            .init_resource::<CameraLookAhead>()
+            .init_resource::<CameraFraming>()
+            .init_resource::<CameraTransition>()
+            .init_resource::<BestLaps>()
+            .init_resource::<LapTimer>()
+            .init_resource::<Gates>()
+            .init_resource::<CornerAdvisory>()
+            .init_resource::<AiControls>()
+            .init_resource::<AiRoute>()
+            .init_resource::<WorldSnapshots>()
+            .init_resource::<InputRecordings>()
+            .init_resource::<SkidTexture>()
+            .init_resource::<SkidBlend>()
+            .init_resource::<RngResource>()
+            .init_resource::<AspectPolicy>()
+            .init_resource::<Replay>()
+            .init_resource::<SpinReplay>()
+            .init_resource::<ReplaySettings>()
+            .init_resource::<SkidCulling>()
+            .init_resource::<SkidBaking>()
+            .init_resource::<SkidWidth>()
+            .init_resource::<GridSettings>()
+            .init_resource::<SteerCurvePlot>()
+            .init_resource::<DriftGauge>()
+            .add_event::<SkidStarted>()
+            .add_event::<SkidEnded>()
+            .add_event::<SpunOut>()
+            .add_event::<WallHit>()
+            .add_event::<CheckpointPassed>()
+            .init_resource::<GroundTexture>()
+            .init_resource::<PhysicsLogging>()
+            .init_resource::<ConfigEditor>()
+            .add_startup_system(setup.system())
+            .add_startup_system(spawn_terrain_shading.system())
+            .add_startup_system(spawn_grid.system())
+            .add_startup_system(spawn_backdrop.system())
+            .add_startup_system(spawn_gate_posts.system())
+            .add_startup_system(setup_skid_material.system())
+            .add_startup_system(log_rng_seed.system())
+            .add_system(restart_rng.system().before(MyStages::Physics))
+            .add_system(repeat_textures.system())
+            .add_system(apply_initial_state.system().before(MyStages::Physics))
+            .add_system(apply_config_reloads.system().before(MyStages::Physics))
+            .add_system(resolve_car_configs.system().before(MyStages::Physics))
+            .add_system(spawn_tires.system().before(MyStages::Physics))
+            .add_system(tune_weight_transfer.system().before(MyStages::Physics))
+            .add_system(toggle_debug_text.system().before(MyStages::Physics))
+            .add_system(show_debug_text.system().after(MyStages::Physics))
+            .add_system(toggle_units.system().before(MyStages::Physics))
+            .add_system(cycle_assist_level.system().before(MyStages::Physics))
+            .add_system(toggle_grid.system().before(MyStages::Physics))
+            .add_system(toggle_physics_logging.system().before(MyStages::Physics))
+            .add_system(apply_palette.system().after(MyStages::Physics))
+            .add_system(replay.system().before(MyStages::Physics))
+            .add_system(edit_config.system().before(MyStages::Physics))
+            .add_system(drop_obstacles.system().before(MyStages::Physics))
+            .add_system(save_and_load_world.system().before(MyStages::Physics))
+            .add_system(drive_through_gates.system().after(MyStages::Physics))
+            .add_system(time_laps.system().after(MyStages::Physics))
+            .add_system(score_spins.system().after(MyStages::Physics))
+            .add_system(
+                drive_ai_cars
+                    .system()
+                    .label(MyStages::Inputs)
+                    .before(MyStages::Physics),
+            )
+            .add_system(
+                play_inputs
+                    .system()
+                    .after(MyStages::Inputs)
+                    .before(MyStages::Physics)
+                    .with_run_criteria(physics_running.system()),
+            )
+            .add_system(
+                record_inputs
+                    .system()
+                    .after(MyStages::Physics)
+                    .with_run_criteria(physics_running.system()),
+            )
+            .add_system(
+                replay_spin_outs
+                    .system()
+                    .after(MyStages::Physics)
+                    .with_run_criteria(physics_running.system()),
+            )
+            .add_system(track_stuck_cars.system().after(MyStages::Physics))
+            .add_system(track_window_focus.system().before(MyStages::Physics))
+            .add_system(spawn_track.system().before(MyStages::Physics))
+            .add_system(
+                read_player_inputs
+                    .system()
+                    .label(MyStages::Inputs)
+                    .before(MyStages::Physics),
+            )
+            .add_system(launch_control.system().before(MyStages::Physics))
+            .add_system(
+                step.system()
+                    .label(MyStages::Physics)
+                    .with_run_criteria(physics_running.system()),
+            )
+            .add_system(
+                shake_camera
+                    .system()
+                    .after(MyStages::Physics)
+                    .after(MyStages::FollowCar),
+            )
+            .add_system_set(
+                SystemSet::new()
+                    .with_system(place_weight_marker.system())
+                    .with_system(place_bumpers.system())
+                    .with_system(place_brake_lights.system())
+                    .with_system(beep_while_reversing.system())
+                    .with_system(place_slip_arrows.system())
+                    .with_system(place_tires.system())
+                    .with_system(cleanup_skids.system())
+                    .with_system(cull_skids.system())
+                    .with_system(bake_skids.system())
+                    .with_system(apply_pixels_per_meter.system())
+                    .with_system(follow_car.system().label(MyStages::FollowCar))
+                    .with_system(place_backdrop.system().after(MyStages::FollowCar))
+                    .with_system(plot_steer_curve.system())
+                    .with_system(show_drift_gauge.system())
+                    .with_system(record_telemetry.system())
+                    .with_system(move_particles.system())
+                    .with_system(age_smoke.system())
+                    .with_system(despawn_timers.system())
+                    .after(MyStages::Physics),
+            )
+            .add_system_set_to_stage(
+                CoreStage::PostUpdate,
+                SystemSet::new()
+                    .with_system(
+                        init_previous_global_transform
+                            .system()
+                            .label(MyStages::InitPreviousGlobalTransform)
+                            .after(TransformSystem::TransformPropagate),
+                    )
+                    .with_system(
+                        reset_teleported_tires
+                            .system()
+                            .label(MyStages::InitPreviousGlobalTransform)
+                            .after(TransformSystem::TransformPropagate),
+                    )
+                    .with_system(
+                        skid.system()
+                            .before(MyStages::UpdatePreviousGlobalTransform)
+                            .after(MyStages::InitPreviousGlobalTransform),
+                    )
+                    .with_system(
+                        spray
+                            .system()
+                            .before(MyStages::UpdatePreviousGlobalTransform)
+                            .after(MyStages::InitPreviousGlobalTransform),
+                    )
+                    .with_system(emit_smoke.system())
+                    .with_system(
+                        update_previous_global_transform
+                            .system()
+                            .label(MyStages::UpdatePreviousGlobalTransform)
+                            .after(TransformSystem::TransformPropagate),
+                    ),
+            );
+
+        if self.spawn_default_car {
+            app.add_startup_system(spawn_car.system());
+        }
+
+        if self.spawn_second_car {
+            app.add_startup_system(spawn_second_car.system());
+        }
+
+        if self.spawn_ai_car {
+            app.add_startup_system(spawn_ai_car.system());
+        }
+
+        if self.open_second_window {
+            app.init_resource::<SecondWindow>()
+                .add_startup_system(open_second_window.system())
+                .add_system(set_up_second_window.system().after(MyStages::Physics))
+                .add_system(
+                    follow_second_car
+                        .system()
+                        .after(MyStages::Physics)
+                        .before(MyStages::FollowCar),
+                );
+        }
+
+        if self.spawn_hud {
+            app.init_resource::<HudLayout>()
+                .add_startup_system(spawn_hud.system())
+                .add_system(spawn_readouts.system().before(MyStages::Physics))
+                .add_system(report_missing_hud_font.system())
+                .add_system(apply_hud_layout.system());
+        }
+    }
+}
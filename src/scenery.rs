@@ -0,0 +1,359 @@
+//! The backdrop, the ground, the grid and the terrain shading
+
+use crate::{camera::MainCamera, physics::Terrain, to_real, Z_BACKDROP, Z_GRID, Z_TERRAIN_SHADING};
+use bevy::{
+    prelude::*,
+    render::{mesh::VertexAttributeValues, pipeline::PrimitiveTopology},
+    sprite::SpriteResizeMode,
+};
+
+/// A reference grid over the ground, with every few lines emphasised
+pub struct GridSettings {
+    pub visible: bool,
+    /// The meters between lines, held to at least `MIN_GRID_SPACING`
+    pub spacing: f32,
+    pub major_every: u32,
+    pub half_extent: f32,
+    pub color: Color,
+    pub major_color: Color,
+}
+
+impl Default for GridSettings {
+    fn default() -> Self {
+        Self {
+            visible: false,
+            spacing: 1.0,
+            major_every: 10,
+            half_extent: 200.0,
+            color: Color::rgba(1.0, 1.0, 1.0, 0.1),
+            major_color: Color::rgba(1.0, 1.0, 1.0, 0.3),
+        }
+    }
+}
+
+pub(crate) struct GridLines;
+
+/// The closest that the grid's lines may be, in meters, so that a spacing of zero or less doesn't
+/// ask for endless lines
+const MIN_GRID_SPACING: f32 = 0.1;
+
+pub(crate) fn spawn_grid(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    settings: Res<GridSettings>,
+) {
+    let spacing = settings.spacing.max(MIN_GRID_SPACING);
+
+    if settings.spacing < MIN_GRID_SPACING || settings.spacing.is_nan() {
+        warn!(
+            "Grid spacing of {} m is too small, using {} m",
+            settings.spacing, spacing
+        );
+    }
+
+    let line_count = (settings.half_extent / spacing).floor() as i32;
+    let half_extent = line_count as f32 * spacing;
+
+    let mut minor = Vec::new();
+    let mut major = Vec::new();
+
+    for index in -line_count..=line_count {
+        let offset = index as f32 * spacing;
+
+        let lines = if index % settings.major_every.max(1) as i32 == 0 {
+            &mut major
+        } else {
+            &mut minor
+        };
+
+        lines.push([offset, -half_extent, 0.0]);
+        lines.push([offset, half_extent, 0.0]);
+        lines.push([-half_extent, offset, 0.0]);
+        lines.push([half_extent, offset, 0.0]);
+    }
+
+    for (positions, color) in vec![(minor, settings.color), (major, settings.major_color)] {
+        let vertex_count = positions.len();
+
+        let mut mesh = Mesh::new(PrimitiveTopology::LineList);
+        mesh.set_attribute(
+            Mesh::ATTRIBUTE_POSITION,
+            VertexAttributeValues::Float3(positions),
+        );
+        mesh.set_attribute(
+            Mesh::ATTRIBUTE_NORMAL,
+            VertexAttributeValues::Float3(vec![[0.0, 0.0, 1.0]; vertex_count]),
+        );
+        mesh.set_attribute(
+            Mesh::ATTRIBUTE_UV_0,
+            VertexAttributeValues::Float2(vec![[0.0, 0.0]; vertex_count]),
+        );
+
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    size: Vec2::ONE,
+                    ..Default::default()
+                },
+                mesh: meshes.add(mesh),
+                material: materials.add(ColorMaterial::color(color)),
+                transform: Transform::from_translation(Vec3::new(0.0, 0.0, Z_GRID)),
+                visible: Visible {
+                    is_visible: settings.visible,
+                    is_transparent: true,
+                },
+                ..Default::default()
+            })
+            .insert(GridLines);
+    }
+}
+
+pub(crate) fn toggle_grid(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut settings: ResMut<GridSettings>,
+    mut grids: Query<&mut Visible, With<GridLines>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::G) {
+        settings.visible = !settings.visible;
+    }
+
+    for mut visible in grids.iter_mut() {
+        if visible.is_visible != settings.visible {
+            visible.is_visible = settings.visible;
+        }
+    }
+}
+
+/// An optional texture tiled over the ground, beneath everything else, so that the car's movement
+/// shows even where there's nothing else to see go by; without one, the clear colour shows through
+pub struct GroundTexture {
+    /// The texture's path within the assets folder
+    pub path: Option<String>,
+    /// The size, in meters, of each repeat of the texture
+    pub tile_size: f32,
+    pub visible: bool,
+    pub toggle_key: KeyCode,
+}
+
+impl Default for GroundTexture {
+    fn default() -> Self {
+        Self {
+            path: None,
+            tile_size: 8.0,
+            visible: true,
+            toggle_key: KeyCode::B,
+        }
+    }
+}
+
+/// The ground texture, which covers the camera's view and moves with it, while its texture
+/// coordinates stay fixed to the world
+pub(crate) struct Backdrop {
+    pub(crate) texture: Handle<Texture>,
+    pub(crate) mesh: Handle<Mesh>,
+}
+
+pub(crate) fn spawn_backdrop(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    ground_texture: Res<GroundTexture>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let path = match &ground_texture.path {
+        Some(path) => path,
+        None => return,
+    };
+
+    let texture = asset_server.load(path.as_str());
+
+    // The corners are placed each frame, to cover whatever the camera can see
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleStrip);
+    mesh.set_attribute(
+        Mesh::ATTRIBUTE_POSITION,
+        VertexAttributeValues::Float3(vec![[0.0, 0.0, 0.0]; 4]),
+    );
+    mesh.set_attribute(
+        Mesh::ATTRIBUTE_NORMAL,
+        VertexAttributeValues::Float3(vec![[0.0, 0.0, 1.0]; 4]),
+    );
+    mesh.set_attribute(
+        Mesh::ATTRIBUTE_UV_0,
+        VertexAttributeValues::Float2(vec![[0.0, 0.0]; 4]),
+    );
+
+    let mesh = meshes.add(mesh);
+
+    commands
+        .spawn_bundle(SpriteBundle {
+            // Sprites with a texture otherwise take its size in pixels
+            sprite: Sprite {
+                size: Vec2::ONE,
+                resize_mode: SpriteResizeMode::Manual,
+                ..Default::default()
+            },
+            mesh: mesh.clone(),
+            material: materials.add(ColorMaterial::texture(texture.clone())),
+            visible: Visible {
+                is_visible: ground_texture.visible,
+                is_transparent: false,
+            },
+            ..Default::default()
+        })
+        .insert(BackdropSprite);
+
+    commands.insert_resource(Backdrop { texture, mesh });
+}
+
+pub(crate) struct BackdropSprite;
+
+pub(crate) fn place_backdrop(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut ground_texture: ResMut<GroundTexture>,
+    backdrop: Option<Res<Backdrop>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    cameras: Query<(&Transform, &OrthographicProjection), With<MainCamera>>,
+    mut sprites: Query<(&mut Transform, &mut Visible), (With<BackdropSprite>, Without<MainCamera>)>,
+) {
+    if keyboard_input.just_pressed(ground_texture.toggle_key) {
+        ground_texture.visible = !ground_texture.visible;
+    }
+
+    for (_, mut visible) in sprites.iter_mut() {
+        if visible.is_visible != ground_texture.visible {
+            visible.is_visible = ground_texture.visible;
+        }
+    }
+
+    let backdrop = match backdrop {
+        Some(backdrop) if ground_texture.visible => backdrop,
+        _ => return,
+    };
+
+    let (camera, projection) = match cameras.iter().next() {
+        Some(camera) => camera,
+        None => return,
+    };
+
+    let centre = camera.translation.truncate();
+
+    // Cover the view's whole diagonal, so that it stays covered however the camera turns, with a
+    // tile to spare for the camera shaking
+    let view_size = projection.scale
+        * Vec2::new(
+            projection.right - projection.left,
+            projection.top - projection.bottom,
+        );
+    let half_size = 0.5 * view_size.length() + ground_texture.tile_size;
+
+    let corners = [
+        Vec2::new(-half_size, -half_size),
+        Vec2::new(half_size, -half_size),
+        Vec2::new(-half_size, half_size),
+        Vec2::new(half_size, half_size),
+    ];
+
+    let tile_size = ground_texture.tile_size.max(f32::EPSILON);
+
+    if let Some(mesh) = meshes.get_mut(&backdrop.mesh) {
+        mesh.set_attribute(
+            Mesh::ATTRIBUTE_POSITION,
+            VertexAttributeValues::Float3(
+                corners
+                    .iter()
+                    .map(|corner| [corner.x, corner.y, 0.0])
+                    .collect(),
+            ),
+        );
+
+        // Texture coordinates run down the image, so flip them to keep it the right way up
+        mesh.set_attribute(
+            Mesh::ATTRIBUTE_UV_0,
+            VertexAttributeValues::Float2(
+                corners
+                    .iter()
+                    .map(|&corner| {
+                        let world = (centre + corner) / tile_size;
+
+                        [world.x, -world.y]
+                    })
+                    .collect(),
+            ),
+        );
+    }
+
+    for (mut transform, _) in sprites.iter_mut() {
+        transform.translation = centre.extend(Z_BACKDROP);
+    }
+}
+
+/// Shades the ground lighter on hills and darker in hollows
+pub(crate) fn spawn_terrain_shading(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    terrain: Res<Terrain>,
+) {
+    const TILE_SIZE: f32 = 2.0;
+    const SHADE_LEVELS: i32 = 8;
+
+    let max_height = terrain
+        .hills
+        .iter()
+        .map(|hill| hill.height.abs())
+        .fold(0.0, f32::max);
+
+    if max_height < f32::EPSILON {
+        return;
+    }
+
+    let shades = (-SHADE_LEVELS..=SHADE_LEVELS)
+        .map(|level| {
+            let alpha = 0.4 * level.abs() as f32 / SHADE_LEVELS as f32;
+            let color = if level > 0 {
+                Color::rgba(1.0, 1.0, 1.0, alpha)
+            } else {
+                Color::rgba(0.0, 0.0, 0.0, alpha)
+            };
+
+            materials.add(ColorMaterial::color(color))
+        })
+        .collect::<Vec<_>>();
+
+    // Hills barely rise beyond twice their radius, so only tile the area which they cover
+    let (min, max) = terrain.hills.iter().fold(
+        (Vec2::splat(f32::INFINITY), Vec2::splat(f32::NEG_INFINITY)),
+        |(min, max), hill| {
+            (
+                min.min(hill.centre - Vec2::splat(2.0 * hill.radius)),
+                max.max(hill.centre + Vec2::splat(2.0 * hill.radius)),
+            )
+        },
+    );
+
+    let tiles = ((max - min) / TILE_SIZE).ceil();
+
+    for x in 0..=tiles.x as i32 {
+        for y in 0..=tiles.y as i32 {
+            let centre = min + TILE_SIZE * Vec2::new(x as f32, y as f32);
+            let height = terrain.height(to_real(centre)) as f32;
+            let level = (height / max_height * SHADE_LEVELS as f32).round() as i32;
+
+            if level == 0 {
+                continue;
+            }
+
+            commands.spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    size: TILE_SIZE * Vec2::ONE,
+                    ..Default::default()
+                },
+                material: shades
+                    [(level.clamp(-SHADE_LEVELS, SHADE_LEVELS) + SHADE_LEVELS) as usize]
+                    .clone(),
+                transform: Transform::from_translation(centre.extend(Z_TERRAIN_SHADING)),
+                ..Default::default()
+            });
+        }
+    }
+}
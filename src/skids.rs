@@ -0,0 +1,775 @@
+//! Skid marks: laying them down, culling them and baking them into strips
+
+use crate::{
+    car::{CurrentSkid, Palette, PreviousGlobalTransform, Tire},
+    from_real,
+    physics::{CarState, SimTime},
+    scenery::Backdrop,
+    IntoArray, Z_SKID,
+};
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::VertexAttributeValues,
+        pipeline::{
+            BlendFactor, BlendOperation, BlendState, PipelineDescriptor, PrimitiveTopology,
+            RenderPipeline, RenderPipelines,
+        },
+        texture::AddressMode,
+    },
+    sprite::{SpriteResizeMode, SPRITE_PIPELINE_HANDLE},
+};
+
+pub(crate) struct Skid {
+    pub(crate) started_seconds: f64,
+}
+
+/// An optional tread texture for the skid marks, tiled along their length, instead of the tires'
+/// flat colour
+pub struct SkidTexture {
+    /// The texture's path within the assets folder
+    pub path: Option<String>,
+    /// The length of skid, in meters, covered by each repeat of the texture
+    pub tread_length: f32,
+}
+
+impl Default for SkidTexture {
+    fn default() -> Self {
+        Self {
+            path: None,
+            tread_length: 0.5,
+        }
+    }
+}
+
+/// How skid marks combine where they overlap
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SkidBlend {
+    /// Each skid is drawn over those beneath it, so translucent skids darken further with every
+    /// pass over the same ground
+    Cumulative { opacity: f32 },
+    /// Skids darken the ground only as far as a single skid would, however many times it's
+    /// driven over, as each pixel keeps the darker of the ground and the skid
+    Capped { opacity: f32 },
+}
+
+impl Default for SkidBlend {
+    fn default() -> Self {
+        Self::Cumulative { opacity: 1.0 }
+    }
+}
+
+impl SkidBlend {
+    pub(crate) fn color(self, color: Color) -> Color {
+        match self {
+            Self::Cumulative { opacity } => {
+                let mut color = color;
+                color.set_a(opacity.clamp(0.0, 1.0));
+                color
+            }
+            // Blending by keeping the darker colour ignores alpha, so fade the skid to white
+            // instead, which never darkens the ground
+            Self::Capped { opacity } => {
+                let opacity = opacity.clamp(0.0, 1.0);
+                let fade = |channel: f32| channel * opacity + (1.0 - opacity);
+
+                Color::rgb(fade(color.r()), fade(color.g()), fade(color.b()))
+            }
+        }
+    }
+}
+
+/// The material, and when blending is capped the pipeline, shared by every skid when they don't
+/// simply use the tires' own material
+pub(crate) struct SkidMaterial {
+    pub(crate) texture: Option<Handle<Texture>>,
+    pub(crate) material: Handle<ColorMaterial>,
+    pub(crate) pipeline: Option<Handle<PipelineDescriptor>>,
+}
+
+pub(crate) fn setup_skid_material(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    skid_texture: Res<SkidTexture>,
+    skid_blend: Res<SkidBlend>,
+    palette: Res<Palette>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut pipelines: ResMut<Assets<PipelineDescriptor>>,
+) {
+    if skid_texture.path.is_none() && *skid_blend == SkidBlend::default() {
+        return;
+    }
+
+    let texture = skid_texture
+        .path
+        .as_ref()
+        .map(|path| asset_server.load(path.as_str()));
+
+    let material = materials.add(ColorMaterial {
+        color: skid_blend.color(palette.colors().tire),
+        texture: texture.clone(),
+    });
+
+    let pipeline = match *skid_blend {
+        SkidBlend::Cumulative { .. } => None,
+        SkidBlend::Capped { .. } => {
+            let mut descriptor = pipelines
+                .get(&SPRITE_PIPELINE_HANDLE.typed::<PipelineDescriptor>())
+                .expect("sprite pipeline is added with the sprite plugin")
+                .clone();
+
+            for target in descriptor.color_target_states.iter_mut() {
+                target.color_blend = BlendState {
+                    src_factor: BlendFactor::One,
+                    dst_factor: BlendFactor::One,
+                    operation: BlendOperation::Min,
+                };
+            }
+
+            Some(pipelines.add(descriptor))
+        }
+    };
+
+    commands.insert_resource(SkidMaterial {
+        texture,
+        material,
+        pipeline,
+    });
+}
+
+/// Textures are clamped at their edges by default, so make the tread and the backdrop repeat once
+/// they have loaded
+pub(crate) fn repeat_textures(
+    mut events: EventReader<AssetEvent<Texture>>,
+    skid_material: Option<Res<SkidMaterial>>,
+    backdrop: Option<Res<Backdrop>>,
+    mut textures: ResMut<Assets<Texture>>,
+) {
+    let repeating = skid_material
+        .as_ref()
+        .and_then(|skid_material| skid_material.texture.as_ref())
+        .into_iter()
+        .chain(backdrop.as_ref().map(|backdrop| &backdrop.texture))
+        .collect::<Vec<_>>();
+
+    if repeating.is_empty() {
+        return;
+    }
+
+    for event in events.iter() {
+        let handle = match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => handle,
+            AssetEvent::Removed { .. } => continue,
+        };
+
+        if !repeating.contains(&handle) {
+            continue;
+        }
+
+        // Only borrow the texture mutably when it needs changing, so this doesn't modify it again
+        // every time it's modified
+        let needs_repeat = textures.get(handle).map_or(false, |texture| {
+            texture.sampler.address_mode_u != AddressMode::Repeat
+                || texture.sampler.address_mode_v != AddressMode::Repeat
+        });
+
+        if needs_repeat {
+            if let Some(texture) = textures.get_mut(handle) {
+                texture.sampler.address_mode_u = AddressMode::Repeat;
+                texture.sampler.address_mode_v = AddressMode::Repeat;
+            }
+        }
+    }
+}
+
+#[derive(Bundle)]
+pub(crate) struct SkidBundle {
+    #[bundle]
+    pub(crate) sprite: SpriteBundle,
+    pub(crate) skid: Skid,
+}
+
+impl SkidBundle {
+    pub(crate) fn new(
+        mesh: Handle<Mesh>,
+        material: Handle<ColorMaterial>,
+        pipeline: Option<Handle<PipelineDescriptor>>,
+        started_seconds: f64,
+    ) -> Self {
+        let render_pipelines = pipeline.map_or_else(RenderPipelines::default, |pipeline| {
+            RenderPipelines::from_pipelines(vec![RenderPipeline::new(pipeline)])
+        });
+
+        Self {
+            sprite: SpriteBundle {
+                // A tread texture would otherwise stretch the skid to its size in pixels
+                sprite: Sprite {
+                    size: Vec2::ONE,
+                    resize_mode: SpriteResizeMode::Manual,
+                    ..Default::default()
+                },
+                mesh,
+                material,
+                render_pipelines,
+                ..Default::default()
+            },
+            skid: Skid { started_seconds },
+        }
+    }
+}
+
+pub(crate) fn skid(
+    mut commands: Commands,
+    time: Res<SimTime>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    skid_texture: Res<SkidTexture>,
+    skid_material: Option<Res<SkidMaterial>>,
+    skid_width: Res<SkidWidth>,
+    mut tire: Query<(
+        &Tire,
+        &mut CurrentSkid,
+        &GlobalTransform,
+        &PreviousGlobalTransform,
+    )>,
+) {
+    for (tire, mut skid, &global_transform, &PreviousGlobalTransform(previous_global_transform)) in
+        tire.iter_mut()
+    {
+        // Skids lie on the ground, under everything but the surface itself
+        let previous_position = previous_global_transform
+            .translation
+            .truncate()
+            .extend(Z_SKID);
+        let current_position = global_transform.translation.truncate().extend(Z_SKID);
+
+        let offset = current_position - previous_position;
+
+        let width = global_transform.scale.y
+            * tire.load_ratio.clamp(0.5, 2.0)
+            * skid_width.scale(tire.speed);
+
+        let sideways = 0.5 * width * Vec3::new(-offset.y, offset.x, 0.0).normalize_or_zero();
+
+        let p1 = (current_position - sideways).into_array();
+        let p2 = (current_position + sideways).into_array();
+        let n1 = [0.0, 0.0, 1.0];
+        let n2 = n1;
+
+        // The texture runs across the skid, and repeats along its length
+        let length = if skid.mesh.is_some() {
+            skid.length + offset.truncate().length()
+        } else {
+            0.0
+        };
+
+        skid.length = length;
+
+        let v = length / skid_texture.tread_length.max(f32::EPSILON);
+        let uv1 = [0.0, v];
+        let uv2 = [1.0, v];
+
+        match (
+            tire.is_skidding,
+            skid.mesh.as_ref().and_then(|handle| meshes.get_mut(handle)),
+        ) {
+            (true, None) => {
+                let mut mesh = Mesh::new(PrimitiveTopology::TriangleStrip);
+                mesh.set_attribute(
+                    Mesh::ATTRIBUTE_POSITION,
+                    VertexAttributeValues::Float3(vec![p1, p2]),
+                );
+                mesh.set_attribute(
+                    Mesh::ATTRIBUTE_NORMAL,
+                    VertexAttributeValues::Float3(vec![n1, n2]),
+                );
+                mesh.set_attribute(
+                    Mesh::ATTRIBUTE_UV_0,
+                    VertexAttributeValues::Float2(vec![uv1, uv2]),
+                );
+
+                let handle = meshes.add(mesh);
+
+                skid.mesh = Some(handle.clone());
+
+                let (material, pipeline) = skid_material.as_ref().map_or_else(
+                    || (skid.material.clone(), None),
+                    |skid_material| {
+                        (
+                            skid_material.material.clone(),
+                            skid_material.pipeline.clone(),
+                        )
+                    },
+                );
+
+                commands.spawn_bundle(SkidBundle::new(
+                    handle,
+                    material,
+                    pipeline,
+                    time.seconds_since_startup(),
+                ));
+            }
+            (true, Some(mesh)) => {
+                match mesh.attribute_mut(Mesh::ATTRIBUTE_POSITION).unwrap() {
+                    VertexAttributeValues::Float3(positions) => {
+                        positions.push(p1);
+                        positions.push(p2);
+                    }
+                    _ => panic!(),
+                }
+
+                match mesh.attribute_mut(Mesh::ATTRIBUTE_NORMAL).unwrap() {
+                    VertexAttributeValues::Float3(positions) => {
+                        positions.push(n1);
+                        positions.push(n2);
+                    }
+                    _ => panic!(),
+                }
+
+                match mesh.attribute_mut(Mesh::ATTRIBUTE_UV_0).unwrap() {
+                    VertexAttributeValues::Float2(positions) => {
+                        positions.push(uv1);
+                        positions.push(uv2);
+                    }
+                    _ => panic!(),
+                }
+            }
+            (false, None) => (),
+            (false, Some(_mesh)) => {
+                skid.mesh = None;
+            }
+        }
+    }
+}
+
+/// Optionally widens skids laid at speed, and narrows them as the car slows, so that the marks
+/// taper off; otherwise skids are as wide as the tire, adjusted only for its load
+pub struct SkidWidth {
+    /// The speed, in m/s, at which skids are the tire's own width
+    pub reference_speed: Option<f32>,
+    pub min_scale: f32,
+    pub max_scale: f32,
+}
+
+impl Default for SkidWidth {
+    fn default() -> Self {
+        Self {
+            reference_speed: None,
+            min_scale: 0.5,
+            max_scale: 1.5,
+        }
+    }
+}
+
+impl SkidWidth {
+    pub(crate) fn scale(&self, speed: f32) -> f32 {
+        match self.reference_speed {
+            Some(reference_speed) if reference_speed > f32::EPSILON => {
+                (speed / reference_speed).clamp(self.min_scale, self.max_scale)
+            }
+            _ => 1.0,
+        }
+    }
+}
+
+/// Opt-in limits on how many skids are kept, so that long sessions don't accumulate them forever
+#[derive(Default)]
+pub struct SkidCulling {
+    /// Skids whose newest end is further than this from every car are removed
+    pub max_distance: Option<f32>,
+    /// Beyond this many skids, the oldest are removed
+    pub max_count: Option<usize>,
+    /// Once the skids' vertices take up more than this many bytes, the oldest are removed
+    pub max_bytes: Option<usize>,
+}
+
+/// Each skid vertex has a position, a normal and a texture coordinate
+const SKID_VERTEX_BYTES: usize = (3 + 3 + 2) * std::mem::size_of::<f32>();
+
+/// How many of the oldest skids, given their sizes in bytes from oldest to newest, must go to
+/// bring them within the limits
+pub(crate) fn skids_to_evict(
+    sizes: &[usize],
+    max_count: Option<usize>,
+    max_bytes: Option<usize>,
+) -> usize {
+    let mut evicted = max_count.map_or(0, |max_count| sizes.len().saturating_sub(max_count));
+
+    if let Some(max_bytes) = max_bytes {
+        let mut total = sizes[evicted..].iter().sum::<usize>();
+
+        while total > max_bytes {
+            total -= sizes[evicted];
+            evicted += 1;
+        }
+    }
+
+    evicted
+}
+
+/// A skid's positions and texture coordinates, when its mesh has both
+fn skid_vertices(mesh: &Mesh) -> Option<(&[[f32; 3]], &[[f32; 2]])> {
+    match (
+        mesh.attribute(Mesh::ATTRIBUTE_POSITION),
+        mesh.attribute(Mesh::ATTRIBUTE_UV_0),
+    ) {
+        (
+            Some(VertexAttributeValues::Float3(positions)),
+            Some(VertexAttributeValues::Float2(uvs)),
+        ) => Some((positions.as_slice(), uvs.as_slice())),
+        _ => None,
+    }
+}
+
+fn skid_mesh(positions: Vec<[f32; 3]>, uvs: Vec<[f32; 2]>) -> Mesh {
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleStrip);
+    mesh.set_attribute(
+        Mesh::ATTRIBUTE_NORMAL,
+        VertexAttributeValues::Float3(vec![[0.0, 0.0, 1.0]; positions.len()]),
+    );
+    mesh.set_attribute(
+        Mesh::ATTRIBUTE_POSITION,
+        VertexAttributeValues::Float3(positions),
+    );
+    mesh.set_attribute(Mesh::ATTRIBUTE_UV_0, VertexAttributeValues::Float2(uvs));
+    mesh
+}
+
+/// A skid which culling may remove, either whole or from within a baked skid
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CulledSkid {
+    Whole(Entity),
+    Baked(Entity, usize),
+}
+
+pub(crate) fn cull_skids(
+    mut commands: Commands,
+    culling: Res<SkidCulling>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    cars: Query<&CarState>,
+    skids: Query<(Entity, &Skid, &Handle<Mesh>), Without<BakedSkid>>,
+    mut baked_skids: Query<(Entity, &mut BakedSkid, &Handle<Mesh>)>,
+) {
+    if culling.max_distance.is_none() && culling.max_count.is_none() && culling.max_bytes.is_none()
+    {
+        return;
+    }
+
+    let car_positions = cars
+        .iter()
+        .map(|state| from_real(state.position))
+        .collect::<Vec<_>>();
+
+    // The most recently laid point is the end nearest a car which is still skidding
+    let is_too_far = |newest_point: Option<&[f32; 3]>| match (culling.max_distance, newest_point) {
+        (Some(max_distance), Some(&[x, y, _])) => car_positions
+            .iter()
+            .all(|&car| car.distance(Vec2::new(x, y)) > max_distance),
+        _ => false,
+    };
+
+    let mut kept = Vec::new();
+    let mut removed = Vec::new();
+
+    for (entity, skid, handle) in skids.iter() {
+        let positions = meshes
+            .get(handle)
+            .and_then(skid_vertices)
+            .map(|(positions, _)| positions);
+        let bytes = positions.map_or(0, |positions| positions.len() * SKID_VERTEX_BYTES);
+        let culled = CulledSkid::Whole(entity);
+
+        if is_too_far(positions.and_then(|positions| positions.last())) {
+            removed.push(culled);
+        } else {
+            kept.push((culled, skid.started_seconds, bytes));
+        }
+    }
+
+    // Each skid within a baked skid is judged as it would have been before it was baked
+    for (entity, baked, handle) in baked_skids.iter_mut() {
+        let positions = meshes
+            .get(handle)
+            .and_then(skid_vertices)
+            .map(|(positions, _)| positions);
+
+        for (index, (strip, range)) in baked
+            .strips
+            .iter()
+            .zip(strip_ranges(&baked.strips))
+            .enumerate()
+        {
+            let culled = CulledSkid::Baked(entity, index);
+
+            if is_too_far(positions.and_then(|positions| positions.get(range.end - 1))) {
+                removed.push(culled);
+            } else {
+                kept.push((culled, strip.started_seconds, strip.len * SKID_VERTEX_BYTES));
+            }
+        }
+    }
+
+    kept.sort_by(|(_, a, _), (_, b, _)| a.partial_cmp(b).unwrap());
+
+    let sizes = kept.iter().map(|&(_, _, bytes)| bytes).collect::<Vec<_>>();
+    let evicted = skids_to_evict(&sizes, culling.max_count, culling.max_bytes);
+
+    removed.extend(kept.drain(..evicted).map(|(culled, _, _)| culled));
+
+    for culled in &removed {
+        if let CulledSkid::Whole(entity) = *culled {
+            if let Ok((_, _, handle)) = skids.get(entity) {
+                commands.entity(entity).despawn();
+                meshes.remove(handle);
+            }
+        }
+    }
+
+    for (entity, mut baked, handle) in baked_skids.iter_mut() {
+        let is_trimmed = |index: usize| removed.contains(&CulledSkid::Baked(entity, index));
+
+        if !(0..baked.strips.len()).any(is_trimmed) {
+            continue;
+        }
+
+        if (0..baked.strips.len()).all(is_trimmed) {
+            commands.entity(entity).despawn();
+            meshes.remove(handle);
+            continue;
+        }
+
+        let trimmed = meshes
+            .get(handle)
+            .and_then(skid_vertices)
+            .map(|(positions, uvs)| {
+                join_strips(
+                    strip_ranges(&baked.strips)
+                        .enumerate()
+                        .filter(|&(index, _)| !is_trimmed(index))
+                        .map(|(_, range)| (&positions[range.clone()], &uvs[range])),
+                )
+            });
+
+        if let (Some((positions, uvs)), Some(mesh)) = (trimmed, meshes.get_mut(handle)) {
+            *mesh = skid_mesh(positions, uvs);
+        }
+
+        baked.strips = std::mem::take(&mut baked.strips)
+            .into_iter()
+            .enumerate()
+            .filter(|&(index, _)| !is_trimmed(index))
+            .map(|(_, strip)| strip)
+            .collect();
+    }
+}
+
+/// Opt-in periodic merging of finished skids into one mesh per material, so that long sessions
+/// draw many skids with a single draw call. Skids that are still being laid are left alone.
+#[derive(Default)]
+pub struct SkidBaking {
+    /// How often to merge finished skids, in seconds
+    pub interval: Option<f32>,
+}
+
+/// One of the finished skids merged into a baked skid
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct BakedStrip {
+    pub(crate) started_seconds: f64,
+    /// How many vertices are the skid's own, leaving out those joining it to the skid before
+    pub(crate) len: usize,
+}
+
+/// The skid that each material's finished skids are merged into, which keeps track of the skids
+/// within it so that culling can still remove them one by one
+pub(crate) struct BakedSkid {
+    strips: Vec<BakedStrip>,
+}
+
+/// Where each strip lies among the vertices that `join_strips` made of them
+pub(crate) fn strip_ranges(
+    strips: &[BakedStrip],
+) -> impl Iterator<Item = std::ops::Range<usize>> + '_ {
+    strips.iter().scan(0, |start, strip| {
+        let range = *start..*start + strip.len;
+
+        // Past the two vertices repeated at the join
+        *start = range.end + 2;
+
+        Some(range)
+    })
+}
+
+/// Joins triangle strips, as positions and texture coordinates, into one strip, repeating the
+/// vertices either side of each join so that the triangles across it have no area. Skids have an
+/// even number of vertices, so each strip's triangles keep facing the same way.
+pub(crate) fn join_strips<'a>(
+    strips: impl IntoIterator<Item = (&'a [[f32; 3]], &'a [[f32; 2]])>,
+) -> (Vec<[f32; 3]>, Vec<[f32; 2]>) {
+    let mut positions = Vec::<[f32; 3]>::new();
+    let mut uvs = Vec::<[f32; 2]>::new();
+
+    for (strip_positions, strip_uvs) in strips {
+        if strip_positions.is_empty() {
+            continue;
+        }
+
+        if let (Some(&last_position), Some(&last_uv)) = (positions.last(), uvs.last()) {
+            positions.push(last_position);
+            uvs.push(last_uv);
+            positions.push(strip_positions[0]);
+            uvs.push(strip_uvs[0]);
+        }
+
+        positions.extend_from_slice(strip_positions);
+        uvs.extend_from_slice(strip_uvs);
+    }
+
+    (positions, uvs)
+}
+
+pub(crate) fn bake_skids(
+    mut commands: Commands,
+    time: Res<SimTime>,
+    baking: Res<SkidBaking>,
+    skid_material: Option<Res<SkidMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut last_baked: Local<f64>,
+    tires: Query<&CurrentSkid>,
+    skids: Query<(Entity, &Skid, &Handle<Mesh>, &Handle<ColorMaterial>), Without<BakedSkid>>,
+    mut baked_skids: Query<(&mut BakedSkid, &Handle<Mesh>, &Handle<ColorMaterial>)>,
+) {
+    let interval = match baking.interval {
+        Some(interval) => interval,
+        None => return,
+    };
+
+    let now = time.seconds_since_startup();
+
+    if now - *last_baked < f64::from(interval) {
+        return;
+    }
+
+    *last_baked = now;
+
+    let growing = tires
+        .iter()
+        .filter_map(|skid| skid.mesh.clone())
+        .collect::<Vec<_>>();
+
+    // Skids are only merged with others drawn the same way
+    let mut groups = Vec::<(Handle<ColorMaterial>, Vec<_>)>::new();
+
+    for (entity, skid, mesh, material) in skids.iter() {
+        if growing.contains(mesh) {
+            continue;
+        }
+
+        let finished = (entity, skid.started_seconds, mesh.clone());
+
+        match groups.iter_mut().find(|(handle, _)| handle == material) {
+            Some((_, group)) => group.push(finished),
+            None => groups.push((material.clone(), vec![finished])),
+        }
+    }
+
+    let mut merged = 0;
+    let mut spawned = 0;
+
+    for (material, mut group) in groups {
+        let baked = baked_skids
+            .iter_mut()
+            .find(|(_, _, handle)| **handle == material);
+
+        // A lone skid only saves a draw call once there's a baked skid to add it to
+        if baked.is_none() && group.len() < 2 {
+            continue;
+        }
+
+        group.sort_by(|(_, a, _), (_, b, _)| a.partial_cmp(b).unwrap());
+
+        let finished = group
+            .iter()
+            .filter_map(|(_, started_seconds, handle)| {
+                let (positions, uvs) = meshes.get(handle).and_then(skid_vertices)?;
+
+                Some((*started_seconds, positions, uvs)).filter(|_| !positions.is_empty())
+            })
+            .collect::<Vec<_>>();
+
+        let strips = finished
+            .iter()
+            .map(|&(started_seconds, positions, _)| BakedStrip {
+                started_seconds,
+                len: positions.len(),
+            })
+            .collect::<Vec<_>>();
+
+        // The finished skids are added to the end of the baked skid, which is already joined
+        // into one strip
+        let (positions, uvs) = join_strips(
+            baked
+                .as_ref()
+                .and_then(|(_, handle, _)| meshes.get(*handle))
+                .and_then(skid_vertices)
+                .into_iter()
+                .chain(finished.iter().map(|&(_, positions, uvs)| (positions, uvs))),
+        );
+
+        let mesh = skid_mesh(positions, uvs);
+
+        match baked {
+            Some((mut baked, handle, _)) => {
+                if let Some(baked_mesh) = meshes.get_mut(handle) {
+                    *baked_mesh = mesh;
+                }
+
+                baked.strips.extend(strips);
+            }
+            None => {
+                let pipeline = skid_material
+                    .as_ref()
+                    .filter(|skid_material| skid_material.material == material)
+                    .and_then(|skid_material| skid_material.pipeline.clone());
+
+                commands
+                    .spawn_bundle(SkidBundle::new(
+                        meshes.add(mesh),
+                        material,
+                        pipeline,
+                        group[0].1,
+                    ))
+                    .insert(BakedSkid { strips });
+
+                spawned += 1;
+            }
+        }
+
+        for (entity, _, handle) in &group {
+            commands.entity(*entity).despawn();
+            meshes.remove(handle);
+        }
+
+        merged += group.len();
+    }
+
+    if merged > 0 {
+        info!(
+            "Baked {} skids, saving {} draw calls",
+            merged,
+            merged - spawned
+        );
+    }
+}
+
+pub(crate) fn cleanup_skids(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    skids: Query<(Entity, &Handle<Mesh>), With<Skid>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::C) {
+        for (entity, handle) in skids.iter() {
+            commands.entity(entity).despawn();
+            meshes.remove(handle);
+        }
+    }
+}
@@ -0,0 +1,1276 @@
+//! Telemetry, world snapshots, input recordings and replays
+
+use crate::{
+    camera::MainCamera,
+    car::{CarNumber, ConfigSource, CruiseControl, PaletteMaterials, PerfTest, PlayerControlled},
+    effects::RngResource,
+    from_real,
+    hud::DebugText,
+    physics::{
+        Assists, CarConfig, CarInputs, CarState, LaunchControlPhase, SimControl, SimTime, SpunOut,
+    },
+    precision::{Real, RealVec2},
+    skids::{Skid, SkidBundle, SkidMaterial},
+    track::{Obstacle, ObstacleBundle, TrackEntity},
+    Z_GHOST,
+};
+use bevy::{
+    prelude::*,
+    render::{mesh::VertexAttributeValues, pipeline::PrimitiveTopology},
+};
+
+const TELEMETRY_PATH: &str = "telemetry.bin";
+const PREVIOUS_TELEMETRY_PATH: &str = "telemetry.previous.bin";
+const TELEMETRY_MAGIC: &[u8; 4] = b"DTTL";
+const TELEMETRY_VERSION: u32 = 1;
+pub(crate) const TELEMETRY_FIELD_COUNT: usize = 11;
+const TELEMETRY_RECORD_SIZE: usize = TELEMETRY_FIELD_COUNT * std::mem::size_of::<f32>();
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct TelemetryFrame {
+    pub(crate) time: f32,
+    pub(crate) position: Vec2,
+    pub(crate) velocity: Vec2,
+    pub(crate) heading: f32,
+    pub(crate) yaw_rate: f32,
+    pub(crate) steer: f32,
+    pub(crate) throttle: f32,
+    pub(crate) brake: f32,
+    pub(crate) e_brake: f32,
+}
+
+impl TelemetryFrame {
+    pub(crate) fn new(time: f32, state: &CarState, inputs: &CarInputs) -> Self {
+        Self {
+            time,
+            position: from_real(state.position),
+            velocity: from_real(state.velocity),
+            heading: state.heading as f32,
+            yaw_rate: state.yaw_rate as f32,
+            steer: state.steer as f32,
+            throttle: inputs.throttle,
+            brake: inputs.brake,
+            e_brake: inputs.e_brake,
+        }
+    }
+
+    pub(crate) fn to_fields(self) -> [f32; TELEMETRY_FIELD_COUNT] {
+        [
+            self.time,
+            self.position.x,
+            self.position.y,
+            self.velocity.x,
+            self.velocity.y,
+            self.heading,
+            self.yaw_rate,
+            self.steer,
+            self.throttle,
+            self.brake,
+            self.e_brake,
+        ]
+    }
+
+    pub(crate) fn from_fields(fields: [f32; TELEMETRY_FIELD_COUNT]) -> Self {
+        Self {
+            time: fields[0],
+            position: Vec2::new(fields[1], fields[2]),
+            velocity: Vec2::new(fields[3], fields[4]),
+            heading: fields[5],
+            yaw_rate: fields[6],
+            steer: fields[7],
+            throttle: fields[8],
+            brake: fields[9],
+            e_brake: fields[10],
+        }
+    }
+}
+
+pub(crate) fn write_telemetry(
+    mut writer: impl std::io::Write,
+    frames: &[TelemetryFrame],
+) -> std::io::Result<()> {
+    writer.write_all(TELEMETRY_MAGIC)?;
+    writer.write_all(&TELEMETRY_VERSION.to_le_bytes())?;
+    writer.write_all(&(TELEMETRY_RECORD_SIZE as u32).to_le_bytes())?;
+
+    for frame in frames {
+        for field in frame.to_fields().iter() {
+            writer.write_all(&field.to_le_bytes())?;
+        }
+    }
+
+    writer.flush()
+}
+
+pub(crate) fn read_telemetry(
+    mut reader: impl std::io::Read,
+) -> anyhow::Result<Vec<TelemetryFrame>> {
+    let mut header = [0; 12];
+    reader.read_exact(&mut header)?;
+
+    let read_u32 = |bytes: &[u8]| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+
+    if header[0..4] != TELEMETRY_MAGIC[..] {
+        anyhow::bail!("Not a telemetry file");
+    }
+
+    let version = read_u32(&header[4..8]);
+    if version != TELEMETRY_VERSION {
+        anyhow::bail!("Unsupported telemetry version {}", version);
+    }
+
+    let record_size = read_u32(&header[8..12]) as usize;
+    if record_size != TELEMETRY_RECORD_SIZE {
+        anyhow::bail!("Unexpected telemetry record size {}", record_size);
+    }
+
+    let mut records = Vec::new();
+    reader.read_to_end(&mut records)?;
+
+    let chunks = records.chunks_exact(TELEMETRY_RECORD_SIZE);
+
+    if !chunks.remainder().is_empty() {
+        anyhow::bail!("Telemetry file ends with a partial record");
+    }
+
+    Ok(chunks
+        .map(|record| {
+            let mut fields = [0.0; TELEMETRY_FIELD_COUNT];
+
+            for (field, bytes) in fields.iter_mut().zip(record.chunks_exact(4)) {
+                *field = f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            }
+
+            TelemetryFrame::from_fields(fields)
+        })
+        .collect())
+}
+
+fn write_telemetry_csv(
+    mut writer: impl std::io::Write,
+    frames: &[TelemetryFrame],
+) -> std::io::Result<()> {
+    writeln!(
+        writer,
+        "time,position_x,position_y,velocity_x,velocity_y,heading,yaw_rate,steer,throttle,brake,e_brake"
+    )?;
+
+    for frame in frames {
+        writeln!(
+            writer,
+            "{},{},{},{},{},{},{},{},{},{},{}",
+            frame.time,
+            frame.position.x,
+            frame.position.y,
+            frame.velocity.x,
+            frame.velocity.y,
+            frame.heading,
+            frame.yaw_rate,
+            frame.steer,
+            frame.throttle,
+            frame.brake,
+            frame.e_brake,
+        )?;
+    }
+
+    writer.flush()
+}
+
+/// Prints a binary telemetry recording to stdout as CSV
+pub fn dump_telemetry(path: &str) -> anyhow::Result<()> {
+    let frames = read_telemetry(std::io::BufReader::new(std::fs::File::open(path)?))?;
+
+    write_telemetry_csv(std::io::stdout(), &frames)?;
+
+    Ok(())
+}
+
+/// Records the first player's car, the one with the lowest `CarNumber`, as the telemetry format
+/// holds a single car
+#[derive(Default)]
+pub(crate) struct TelemetryRecorder {
+    recording: bool,
+    start_seconds: f64,
+    pub(crate) frames: Vec<TelemetryFrame>,
+}
+
+/// Saves the whole scene to a file, and restores it from there, for sharing interesting setups
+/// and reproducing bugs with exact state
+pub struct WorldSnapshots {
+    pub path: String,
+    pub save_key: KeyCode,
+    pub load_key: KeyCode,
+    /// Whether the skid marks are saved along with the cars and obstacles
+    pub include_skids: bool,
+}
+
+impl Default for WorldSnapshots {
+    fn default() -> Self {
+        Self {
+            path: String::from("world.snapshot"),
+            save_key: KeyCode::F10,
+            load_key: KeyCode::F11,
+            include_skids: true,
+        }
+    }
+}
+
+/// Bumped whenever a snapshot's meaning changes; added fields default when older snapshots are
+/// loaded, while snapshots from newer versions are refused
+pub(crate) const WORLD_SNAPSHOT_VERSION: u32 = 2;
+
+#[derive(Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub(crate) struct WorldSnapshot {
+    pub(crate) version: u32,
+    pub(crate) cars: Vec<CarSnapshot>,
+    pub(crate) obstacles: Vec<ObstacleSnapshot>,
+    pub(crate) skids: Vec<SkidSnapshot>,
+}
+
+#[derive(Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub(crate) struct CarSnapshot {
+    /// Which car this was, by its `CarNumber`; snapshots from before version 2 have none, and
+    /// their cars are matched up in order instead
+    pub(crate) number: Option<u32>,
+    /// The car's config file, within the assets folder, if it was loaded from one
+    pub(crate) config: Option<String>,
+    pub(crate) heading: Real,
+    pub(crate) position: [Real; 2],
+    pub(crate) velocity: [Real; 2],
+    pub(crate) acceleration: [Real; 2],
+    pub(crate) local_acceleration: [Real; 2],
+    pub(crate) yaw_rate: Real,
+    pub(crate) steer_input: Real,
+    pub(crate) steer: Real,
+    pub(crate) steer_angle: Real,
+    pub(crate) launch_progress: Real,
+    pub(crate) e_brake: Real,
+    pub(crate) brake_temperature: Real,
+    pub(crate) launch_control: LaunchControlPhase,
+}
+
+impl CarSnapshot {
+    pub(crate) fn new(config: Option<String>, state: &CarState) -> Self {
+        Self {
+            number: None,
+            config,
+            heading: state.heading,
+            position: [state.position.x, state.position.y],
+            velocity: [state.velocity.x, state.velocity.y],
+            acceleration: [state.acceleration.x, state.acceleration.y],
+            local_acceleration: [state.local_acceleration.x, state.local_acceleration.y],
+            yaw_rate: state.yaw_rate,
+            steer_input: state.steer_input,
+            steer: state.steer,
+            steer_angle: state.steer_angle,
+            launch_progress: state.launch_progress,
+            e_brake: state.e_brake,
+            brake_temperature: state.brake_temperature,
+            launch_control: state.launch_control,
+        }
+    }
+
+    pub(crate) fn state(&self) -> CarState {
+        let vector = |[x, y]: [Real; 2]| RealVec2::new(x, y);
+
+        CarState {
+            heading: self.heading,
+            position: vector(self.position),
+            velocity: vector(self.velocity),
+            acceleration: vector(self.acceleration),
+            local_acceleration: vector(self.local_acceleration),
+            yaw_rate: self.yaw_rate,
+            steer_input: self.steer_input,
+            steer: self.steer,
+            steer_angle: self.steer_angle,
+            launch_progress: self.launch_progress,
+            e_brake: self.e_brake,
+            brake_temperature: self.brake_temperature,
+            launch_control: self.launch_control,
+            // Set afresh from the assist level before every step
+            assists: Assists::default(),
+        }
+    }
+}
+
+/// The snapshot of the car with the given number, or for snapshots without numbers, of the car at
+/// the same place in the query
+pub(crate) fn find_car_snapshot(
+    cars: &[CarSnapshot],
+    number: CarNumber,
+    index: usize,
+) -> Option<&CarSnapshot> {
+    if cars.iter().any(|car| car.number.is_some()) {
+        cars.iter().find(|car| car.number == Some(number.0))
+    } else {
+        cars.get(index)
+    }
+}
+
+#[derive(Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub(crate) struct ObstacleSnapshot {
+    pub(crate) centre: [f32; 2],
+    pub(crate) half_size: [f32; 2],
+}
+
+#[derive(Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub(crate) struct SkidSnapshot {
+    pub(crate) color: [f32; 4],
+    pub(crate) positions: Vec<[f32; 3]>,
+    pub(crate) uvs: Vec<[f32; 2]>,
+}
+
+pub(crate) fn write_world_snapshot(
+    mut writer: impl std::io::Write,
+    snapshot: &WorldSnapshot,
+) -> anyhow::Result<()> {
+    serde_json::to_writer(&mut writer, snapshot)?;
+
+    writer.write_all(b"\n")?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+pub(crate) fn read_world_snapshot(reader: impl std::io::Read) -> anyhow::Result<WorldSnapshot> {
+    let snapshot = serde_json::from_reader::<_, WorldSnapshot>(reader)?;
+
+    if snapshot.version > WORLD_SNAPSHOT_VERSION {
+        anyhow::bail!(
+            "Snapshot version {} is newer than the supported version {}",
+            snapshot.version,
+            WORLD_SNAPSHOT_VERSION
+        );
+    }
+
+    if snapshot.version < WORLD_SNAPSHOT_VERSION {
+        warn!(
+            "Loading a snapshot from version {}; anything added since then is left at its default",
+            snapshot.version
+        );
+    }
+
+    Ok(snapshot)
+}
+
+pub(crate) fn save_and_load_world(
+    mut commands: Commands,
+    time: Res<SimTime>,
+    keyboard_input: Res<Input<KeyCode>>,
+    settings: Res<WorldSnapshots>,
+    asset_server: Res<AssetServer>,
+    palette_materials: Res<PaletteMaterials>,
+    skid_material: Option<Res<SkidMaterial>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut cars: Query<(
+        &CarNumber,
+        &mut CarState,
+        &mut Handle<CarConfig>,
+        Option<&mut ConfigSource>,
+    )>,
+    obstacles: Query<(Entity, &Obstacle, &Transform), Without<TrackEntity>>,
+    skids: Query<(Entity, &Handle<Mesh>, &Handle<ColorMaterial>), With<Skid>>,
+) {
+    if keyboard_input.just_pressed(settings.save_key) {
+        let snapshot = WorldSnapshot {
+            version: WORLD_SNAPSHOT_VERSION,
+            cars: cars
+                .iter_mut()
+                .map(|(number, state, config, config_source)| {
+                    // A car whose reloads are held back runs on an unnamed copy of its file
+                    let handle =
+                        config_source.map_or(config.clone(), |source| source.handle.clone());
+                    let path = asset_server
+                        .get_handle_path(handle)
+                        .and_then(|path| path.path().to_str().map(String::from));
+
+                    CarSnapshot {
+                        number: Some(number.0),
+                        ..CarSnapshot::new(path, &state)
+                    }
+                })
+                .collect(),
+            obstacles: obstacles
+                .iter()
+                .map(|(_, obstacle, transform)| ObstacleSnapshot {
+                    centre: transform.translation.truncate().into(),
+                    half_size: obstacle.half_size.into(),
+                })
+                .collect(),
+            skids: if settings.include_skids {
+                skids
+                    .iter()
+                    .filter_map(|(_, mesh, material)| {
+                        let mesh = meshes.get(mesh)?;
+                        let color = materials
+                            .get(material)
+                            .map_or(Color::BLACK, |material| material.color);
+
+                        match (
+                            mesh.attribute(Mesh::ATTRIBUTE_POSITION),
+                            mesh.attribute(Mesh::ATTRIBUTE_UV_0),
+                        ) {
+                            (
+                                Some(VertexAttributeValues::Float3(positions)),
+                                Some(VertexAttributeValues::Float2(uvs)),
+                            ) => Some(SkidSnapshot {
+                                color: color.into(),
+                                positions: positions.clone(),
+                                uvs: uvs.clone(),
+                            }),
+                            _ => None,
+                        }
+                    })
+                    .collect()
+            } else {
+                Vec::new()
+            },
+        };
+
+        match std::fs::File::create(&settings.path)
+            .map_err(anyhow::Error::from)
+            .and_then(|file| write_world_snapshot(std::io::BufWriter::new(file), &snapshot))
+        {
+            Ok(()) => info!("Saved the world to {}", settings.path),
+            Err(err) => error!("Failed to save the world to {}: {}", settings.path, err),
+        }
+    }
+
+    if keyboard_input.just_pressed(settings.load_key) {
+        let snapshot = match std::fs::File::open(&settings.path)
+            .map_err(anyhow::Error::from)
+            .and_then(|file| read_world_snapshot(std::io::BufReader::new(file)))
+        {
+            Ok(snapshot) => snapshot,
+            Err(err) => {
+                error!("Failed to load the world from {}: {}", settings.path, err);
+                return;
+            }
+        };
+
+        for (index, (number, mut state, mut config, config_source)) in cars.iter_mut().enumerate() {
+            let car = match find_car_snapshot(&snapshot.cars, *number, index) {
+                Some(car) => car,
+                None => {
+                    warn!("Car {} isn't in the snapshot", number.0);
+                    continue;
+                }
+            };
+
+            *state = car.state();
+
+            if let Some(path) = &car.config {
+                let handle = asset_server.load(path.as_str());
+
+                if let Some(mut config_source) = config_source {
+                    config_source.handle = handle.clone();
+                }
+
+                *config = handle;
+            }
+        }
+
+        for (entity, _, _) in obstacles.iter() {
+            commands.entity(entity).despawn();
+        }
+
+        for obstacle in snapshot.obstacles.iter() {
+            commands.spawn_bundle(ObstacleBundle::new(
+                palette_materials.obstacle.clone(),
+                Vec2::from(obstacle.centre),
+                Vec2::from(obstacle.half_size),
+            ));
+        }
+
+        if settings.include_skids {
+            for (entity, mesh, _) in skids.iter() {
+                commands.entity(entity).despawn();
+                meshes.remove(mesh);
+            }
+
+            let mut skid_materials = Vec::<([f32; 4], Handle<ColorMaterial>)>::new();
+
+            for skid in snapshot.skids.iter() {
+                let mut mesh = Mesh::new(PrimitiveTopology::TriangleStrip);
+                mesh.set_attribute(
+                    Mesh::ATTRIBUTE_POSITION,
+                    VertexAttributeValues::Float3(skid.positions.clone()),
+                );
+                mesh.set_attribute(
+                    Mesh::ATTRIBUTE_NORMAL,
+                    VertexAttributeValues::Float3(vec![[0.0, 0.0, 1.0]; skid.positions.len()]),
+                );
+                mesh.set_attribute(
+                    Mesh::ATTRIBUTE_UV_0,
+                    VertexAttributeValues::Float2(skid.uvs.clone()),
+                );
+
+                let (material, pipeline) = match &skid_material {
+                    Some(skid_material) => (
+                        skid_material.material.clone(),
+                        skid_material.pipeline.clone(),
+                    ),
+                    None => {
+                        let material = match skid_materials
+                            .iter()
+                            .find(|(color, _)| *color == skid.color)
+                        {
+                            Some((_, material)) => material.clone(),
+                            None => {
+                                let material =
+                                    materials.add(ColorMaterial::color(Color::from(skid.color)));
+                                skid_materials.push((skid.color, material.clone()));
+                                material
+                            }
+                        };
+
+                        (material, None)
+                    }
+                };
+
+                commands.spawn_bundle(SkidBundle::new(
+                    meshes.add(mesh),
+                    material,
+                    pipeline,
+                    time.seconds_since_startup(),
+                ));
+            }
+        }
+
+        info!("Loaded the world from {}", settings.path);
+    }
+}
+
+/// Records every numbered car's inputs, step by step, and plays them back through the physics
+/// from the same starting states, so that a whole race can be re-simulated exactly
+pub struct InputRecordings {
+    pub path: String,
+    /// Starts recording, and stops and saves the recording
+    pub record_key: KeyCode,
+    /// Plays the saved recording back, or stops it playing
+    pub play_key: KeyCode,
+    pub(crate) mode: InputRecordingMode,
+}
+
+impl Default for InputRecordings {
+    fn default() -> Self {
+        Self {
+            path: String::from("inputs.recording"),
+            record_key: KeyCode::Insert,
+            play_key: KeyCode::End,
+            mode: InputRecordingMode::Idle,
+        }
+    }
+}
+
+impl InputRecordings {
+    pub fn is_playing(&self) -> bool {
+        matches!(self.mode, InputRecordingMode::Playing { .. })
+    }
+}
+
+pub(crate) enum InputRecordingMode {
+    Idle,
+    Recording(InputRecording),
+    Playing {
+        recording: InputRecording,
+        frame: usize,
+    },
+}
+
+/// Bumped whenever a recording's meaning changes; added fields default when older recordings are
+/// played, while recordings from newer versions are refused
+pub(crate) const INPUT_RECORDING_VERSION: u32 = 1;
+
+#[derive(Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub(crate) struct InputRecording {
+    pub(crate) version: u32,
+    pub(crate) seed: u64,
+    pub(crate) cars: Vec<RecordedCar>,
+    pub(crate) frames: Vec<RecordedFrame>,
+}
+
+#[derive(Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub(crate) struct RecordedCar {
+    pub(crate) number: u32,
+    pub(crate) start: CarSnapshot,
+}
+
+/// One physics step: how long it was, and each car's throttle, brake, handbrake and steering, in
+/// the order of the recording's cars
+#[derive(Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub(crate) struct RecordedFrame {
+    pub(crate) delta_seconds: f32,
+    pub(crate) inputs: Vec<[f32; 4]>,
+}
+
+impl RecordedFrame {
+    pub(crate) fn inputs(&self, car: usize) -> CarInputs {
+        let [throttle, brake, e_brake, steer] = self.inputs.get(car).copied().unwrap_or_default();
+
+        CarInputs {
+            throttle,
+            brake,
+            e_brake,
+            steer,
+        }
+    }
+}
+
+pub(crate) fn write_input_recording(
+    mut writer: impl std::io::Write,
+    recording: &InputRecording,
+) -> anyhow::Result<()> {
+    serde_json::to_writer(&mut writer, recording)?;
+
+    writer.write_all(b"\n")?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+pub(crate) fn read_input_recording(reader: impl std::io::Read) -> anyhow::Result<InputRecording> {
+    let recording = serde_json::from_reader::<_, InputRecording>(reader)?;
+
+    if recording.version > INPUT_RECORDING_VERSION {
+        anyhow::bail!(
+            "Input recording version {} is newer than the supported version {}",
+            recording.version,
+            INPUT_RECORDING_VERSION
+        );
+    }
+
+    Ok(recording)
+}
+
+/// Starts and stops recordings, and feeds a playing recording's inputs to the cars, in place of
+/// the players' and the computer's
+fn play_inputs(
+    keyboard_input: Res<Input<KeyCode>>,
+    replay: Res<Replay>,
+    mut recordings: ResMut<InputRecordings>,
+    mut sim_time: ResMut<SimTime>,
+    mut rng: ResMut<RngResource>,
+    mut cars: Query<(
+        &CarNumber,
+        &mut CarState,
+        &mut CarInputs,
+        &mut CruiseControl,
+        &mut PerfTest,
+    )>,
+) {
+    if keyboard_input.just_pressed(recordings.record_key) {
+        let mode = match std::mem::replace(&mut recordings.mode, InputRecordingMode::Idle) {
+            InputRecordingMode::Recording(recording) => {
+                match std::fs::File::create(&recordings.path)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|file| {
+                        write_input_recording(std::io::BufWriter::new(file), &recording)
+                    }) {
+                    Ok(()) => info!(
+                        "Saved {} frames of inputs to {}",
+                        recording.frames.len(),
+                        recordings.path
+                    ),
+                    Err(err) => error!("Failed to save inputs to {}: {}", recordings.path, err),
+                }
+
+                InputRecordingMode::Idle
+            }
+            InputRecordingMode::Idle | InputRecordingMode::Playing { .. } => {
+                // The effects start their sequence again too, so that they match on playback
+                let seed = rng.seed();
+                rng.reseed(seed);
+
+                let mut cars = cars
+                    .iter_mut()
+                    .map(|(number, state, _, _, _)| RecordedCar {
+                        number: number.0,
+                        start: CarSnapshot::new(None, &state),
+                    })
+                    .collect::<Vec<_>>();
+                cars.sort_by_key(|car| car.number);
+
+                InputRecordingMode::Recording(InputRecording {
+                    version: INPUT_RECORDING_VERSION,
+                    seed,
+                    cars,
+                    frames: Vec::new(),
+                })
+            }
+        };
+
+        recordings.mode = mode;
+    }
+
+    if keyboard_input.just_pressed(recordings.play_key) {
+        if recordings.is_playing() {
+            recordings.mode = InputRecordingMode::Idle;
+        } else {
+            match std::fs::File::open(&recordings.path)
+                .map_err(anyhow::Error::from)
+                .and_then(|file| read_input_recording(std::io::BufReader::new(file)))
+            {
+                Ok(recording) => {
+                    rng.reseed(recording.seed);
+
+                    for (number, mut state, _, _, _) in cars.iter_mut() {
+                        match recording.cars.iter().find(|car| car.number == number.0) {
+                            Some(car) => *state = car.start.state(),
+                            None => warn!("Car {} isn't in the input recording", number.0),
+                        }
+                    }
+
+                    recordings.mode = InputRecordingMode::Playing {
+                        recording,
+                        frame: 0,
+                    };
+                }
+                Err(err) => error!("Failed to load inputs from {}: {}", recordings.path, err),
+            }
+        }
+    }
+
+    // The physics don't step while a replay is playing
+    if replay.active {
+        return;
+    }
+
+    let finished = match &mut recordings.mode {
+        InputRecordingMode::Playing { recording, frame } => match recording.frames.get(*frame) {
+            Some(recorded) => {
+                sim_time.replace_delta(recorded.delta_seconds);
+
+                for (number, _, mut inputs, mut cruise_control, mut perf_test) in cars.iter_mut() {
+                    if let Some(car) = recording.cars.iter().position(|car| car.number == number.0)
+                    {
+                        *inputs = recorded.inputs(car);
+                        cruise_control.target = None;
+                        *perf_test = PerfTest::default();
+                    }
+                }
+
+                *frame += 1;
+
+                false
+            }
+            None => true,
+        },
+        InputRecordingMode::Idle | InputRecordingMode::Recording(_) => false,
+    };
+
+    if finished {
+        info!("Finished playing the input recording");
+        recordings.mode = InputRecordingMode::Idle;
+    }
+}
+
+/// Adds the inputs that each car was just stepped with to the recording
+fn record_inputs(
+    sim_time: Res<SimTime>,
+    replay: Res<Replay>,
+    mut recordings: ResMut<InputRecordings>,
+    cars: Query<(&CarNumber, &CarInputs)>,
+) {
+    if replay.active {
+        return;
+    }
+
+    if let InputRecordingMode::Recording(recording) = &mut recordings.mode {
+        let inputs = recording
+            .cars
+            .iter()
+            .map(|car| {
+                cars.iter()
+                    .find(|(number, _)| number.0 == car.number)
+                    .map_or([0.0; 4], |(_, inputs)| {
+                        [inputs.throttle, inputs.brake, inputs.e_brake, inputs.steer]
+                    })
+            })
+            .collect();
+
+        recording.frames.push(RecordedFrame {
+            delta_seconds: sim_time.delta_seconds(),
+            inputs,
+        });
+    }
+}
+
+pub(crate) fn record_telemetry(
+    time: Res<SimTime>,
+    sim_control: Res<SimControl>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut recorder: ResMut<TelemetryRecorder>,
+    cars: Query<(&CarNumber, &CarState, &CarInputs)>,
+) {
+    if keyboard_input.just_pressed(KeyCode::T) {
+        if recorder.recording {
+            recorder.recording = false;
+
+            let frames = std::mem::take(&mut recorder.frames);
+
+            // Keep the last recording, so that the two can be compared in replay mode
+            if let Err(err) = std::fs::rename(TELEMETRY_PATH, PREVIOUS_TELEMETRY_PATH) {
+                if err.kind() != std::io::ErrorKind::NotFound {
+                    warn!("Failed to keep the previous telemetry: {}", err);
+                }
+            }
+
+            match std::fs::File::create(TELEMETRY_PATH)
+                .and_then(|file| write_telemetry(std::io::BufWriter::new(file), &frames))
+            {
+                Ok(()) => info!("Saved {} telemetry frames", frames.len()),
+                Err(err) => error!("Failed to save telemetry: {}", err),
+            }
+        } else {
+            recorder.recording = true;
+            recorder.start_seconds = time.seconds_since_startup();
+            recorder.frames.clear();
+        }
+    }
+
+    // A paused frame would only repeat the one before it
+    if !recorder.recording || sim_control.is_paused() {
+        return;
+    }
+
+    let time = (time.seconds_since_startup() - recorder.start_seconds) as f32;
+
+    if let Some((_, state, inputs)) = cars.iter().min_by_key(|(number, _, _)| **number) {
+        recorder
+            .frames
+            .push(TelemetryFrame::new(time, state, inputs));
+    }
+}
+
+const REPLAY_CAMERA_SPEED: f32 = 30.0;
+const REPLAY_SCRUB_RATE: f32 = 4.0;
+const REPLAY_MIN_SPEED: f32 = 0.125;
+const REPLAY_MAX_SPEED: f32 = 8.0;
+
+/// The telemetry recordings which replay mode plays back side by side, one ghost each
+pub struct ReplaySettings {
+    pub recordings: Vec<String>,
+}
+
+impl Default for ReplaySettings {
+    fn default() -> Self {
+        Self {
+            recordings: vec![
+                TELEMETRY_PATH.to_string(),
+                PREVIOUS_TELEMETRY_PATH.to_string(),
+            ],
+        }
+    }
+}
+
+const GHOST_COLORS: usize = 4;
+
+fn ghost_color(index: usize) -> Color {
+    match index % GHOST_COLORS {
+        0 => Color::rgba(0.3, 0.6, 1.0, 0.5),
+        1 => Color::rgba(1.0, 0.6, 0.2, 0.5),
+        2 => Color::rgba(0.4, 0.9, 0.4, 0.5),
+        _ => Color::rgba(0.9, 0.4, 0.9, 0.5),
+    }
+}
+
+/// Every ghost takes one of a few shared materials by its index, rather than each having its own
+pub(crate) struct GhostMaterials(pub(crate) Vec<Handle<ColorMaterial>>);
+
+impl FromWorld for GhostMaterials {
+    fn from_world(world: &mut World) -> Self {
+        let mut materials = world.get_resource_mut::<Assets<ColorMaterial>>().unwrap();
+
+        Self(
+            (0..GHOST_COLORS)
+                .map(|index| materials.add(ColorMaterial::color(ghost_color(index))))
+                .collect(),
+        )
+    }
+}
+
+impl GhostMaterials {
+    pub(crate) fn get(&self, index: usize) -> Handle<ColorMaterial> {
+        self.0[index % self.0.len()].clone()
+    }
+}
+
+struct ReplayGhost {
+    frames: Vec<TelemetryFrame>,
+    entity: Entity,
+    /// The frame last found closest to the leader, which the next search starts from
+    matched: usize,
+}
+
+/// Plays back telemetry recordings as ghosts, while the live car is paused and the camera is
+/// flown freely
+pub(crate) struct Replay {
+    pub(crate) active: bool,
+    /// A slow-motion replay of a spin, which follows the car and ends by itself
+    highlight: bool,
+    playing: bool,
+    pub(crate) speed: f32,
+    pub(crate) time: f32,
+    pub(crate) ghosts: Vec<ReplayGhost>,
+    camera_position: Vec2,
+}
+
+impl Default for Replay {
+    fn default() -> Self {
+        Self {
+            active: false,
+            highlight: false,
+            playing: true,
+            speed: 1.0,
+            time: 0.0,
+            ghosts: Vec::new(),
+            camera_position: Vec2::ZERO,
+        }
+    }
+}
+
+/// Plays the moments before a player's car spins out back in slow motion, as a ghost, with the
+/// car paused and its controls ignored, then carries on from the spin
+pub struct SpinReplay {
+    pub enabled: bool,
+    pub toggle_key: KeyCode,
+    /// How many seconds before the spin to play back
+    pub duration: f32,
+    /// How fast the replay plays, relative to the spin itself
+    pub speed: f32,
+    pub(crate) history: std::collections::VecDeque<TelemetryFrame>,
+}
+
+impl Default for SpinReplay {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            toggle_key: KeyCode::Z,
+            duration: 2.0,
+            speed: 0.25,
+            history: std::collections::VecDeque::new(),
+        }
+    }
+}
+
+impl SpinReplay {
+    /// Keeps just enough of the car's recent frames to play back the last `duration` seconds
+    pub(crate) fn record(&mut self, frame: TelemetryFrame) {
+        self.history.push_back(frame);
+
+        while self
+            .history
+            .get(1)
+            .map_or(false, |next| next.time <= frame.time - self.duration)
+        {
+            self.history.pop_front();
+        }
+    }
+}
+
+fn replay_spin_outs(
+    mut commands: Commands,
+    sim_time: Res<SimTime>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut spin_replay: ResMut<SpinReplay>,
+    mut replay: ResMut<Replay>,
+    ghost_materials: Res<GhostMaterials>,
+    mut spun_out: EventReader<SpunOut>,
+    cars: Query<(Entity, &CarState, &CarInputs), With<PlayerControlled>>,
+) {
+    if keyboard_input.just_pressed(spin_replay.toggle_key) {
+        spin_replay.enabled = !spin_replay.enabled;
+        spin_replay.history.clear();
+    }
+
+    if !spin_replay.enabled || replay.active {
+        return;
+    }
+
+    let (car, state, inputs) = match cars.iter().next() {
+        Some(car) => car,
+        None => return,
+    };
+
+    spin_replay.record(TelemetryFrame::new(
+        sim_time.seconds_since_startup() as f32,
+        state,
+        inputs,
+    ));
+
+    if !spun_out.iter().any(|spin| spin.car == car) || spin_replay.history.len() < 2 {
+        return;
+    }
+
+    let frames = spin_replay.history.drain(..).collect::<Vec<_>>();
+
+    *replay = Replay {
+        active: true,
+        highlight: true,
+        speed: spin_replay.speed,
+        time: frames[0].time,
+        camera_position: frames[0].position,
+        ghosts: vec![ReplayGhost {
+            entity: commands
+                .spawn_bundle(SpriteBundle {
+                    sprite: Sprite {
+                        size: Vec2::new(3.4, 1.28),
+                        ..Default::default()
+                    },
+                    material: ghost_materials.get(0),
+                    transform: Transform::from_translation(frames[0].position.extend(Z_GHOST)),
+                    ..Default::default()
+                })
+                .id(),
+            frames,
+            matched: 0,
+        }],
+        ..Replay::default()
+    };
+}
+
+/// How many frames either side of the last match `ghost_time_delta` searches, enough to follow
+/// the leader while scrubbing without jumping to another part of a lap that passes nearby
+const GHOST_MATCH_WINDOW: usize = 60;
+
+/// How much later, in seconds, the `chaser` recording passed closest to where the `leader` is at
+/// `time`, searching only near the chaser's frame which `matched` last time, and moving it on
+pub(crate) fn ghost_time_delta(
+    leader: &[TelemetryFrame],
+    chaser: &[TelemetryFrame],
+    time: f32,
+    matched: &mut usize,
+) -> Option<f32> {
+    let (position, _) = sample_telemetry(leader, time)?;
+
+    let start = matched.saturating_sub(GHOST_MATCH_WINDOW).min(chaser.len());
+    let end = (*matched + GHOST_MATCH_WINDOW + 1).min(chaser.len());
+
+    let (index, closest) = chaser[start..end]
+        .iter()
+        .enumerate()
+        .min_by(|(_, a), (_, b)| {
+            a.position
+                .distance_squared(position)
+                .partial_cmp(&b.position.distance_squared(position))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })?;
+
+    *matched = start + index;
+
+    Some(closest.time - time)
+}
+
+/// The recorded position and heading at the given time, interpolating between frames
+pub(crate) fn sample_telemetry(frames: &[TelemetryFrame], time: f32) -> Option<(Vec2, f32)> {
+    let next = frames.iter().position(|frame| frame.time >= time);
+
+    match next {
+        Some(0) => frames.first().map(|frame| (frame.position, frame.heading)),
+        Some(next) => {
+            let (a, b) = (&frames[next - 1], &frames[next]);
+            let t = (time - a.time) / (b.time - a.time).max(f32::EPSILON);
+
+            Some((
+                a.position.lerp(b.position, t),
+                a.heading + (b.heading - a.heading) * t,
+            ))
+        }
+        None => frames.last().map(|frame| (frame.position, frame.heading)),
+    }
+}
+
+pub(crate) fn replay(
+    mut commands: Commands,
+    time: Res<Time>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut replay: ResMut<Replay>,
+    settings: Res<ReplaySettings>,
+    ghost_materials: Res<GhostMaterials>,
+    cars: Query<&CarState>,
+    mut ghosts: Query<&mut Transform, Without<MainCamera>>,
+    mut cameras: Query<&mut Transform, With<MainCamera>>,
+    mut text: Query<(&mut Text, &DebugText)>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F9) {
+        if replay.active {
+            replay.active = false;
+
+            for ghost in replay.ghosts.drain(..) {
+                commands.entity(ghost.entity).despawn();
+            }
+        } else {
+            let mut loaded = Vec::new();
+
+            for path in settings.recordings.iter() {
+                match std::fs::File::open(path)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|file| read_telemetry(std::io::BufReader::new(file)))
+                {
+                    Ok(frames) if !frames.is_empty() => loaded.push(frames),
+                    Ok(_) => warn!("The telemetry recording {} is empty", path),
+                    Err(err) => warn!("Failed to load telemetry from {}: {}", path, err),
+                }
+            }
+
+            if loaded.is_empty() {
+                error!("There are no telemetry recordings to replay");
+            } else {
+                let ghosts = loaded
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, frames)| ReplayGhost {
+                        frames,
+                        entity: commands
+                            .spawn_bundle(SpriteBundle {
+                                sprite: Sprite {
+                                    size: Vec2::new(3.4, 1.28),
+                                    ..Default::default()
+                                },
+                                material: ghost_materials.get(index),
+                                ..Default::default()
+                            })
+                            .id(),
+                        matched: 0,
+                    })
+                    .collect::<Vec<_>>();
+
+                *replay = Replay {
+                    active: true,
+                    time: ghosts
+                        .iter()
+                        .map(|ghost| ghost.frames[0].time)
+                        .fold(f32::INFINITY, f32::min),
+                    camera_position: cars
+                        .iter()
+                        .next()
+                        .map_or(Vec2::ZERO, |state| from_real(state.position)),
+                    ghosts,
+                    ..Replay::default()
+                };
+            }
+        }
+    }
+
+    if !replay.active {
+        return;
+    }
+
+    let dt_seconds = time.delta_seconds();
+
+    let held = |code: KeyCode| {
+        if keyboard_input.pressed(code) {
+            1.0
+        } else {
+            0.0
+        }
+    };
+
+    // The highlight plays through by itself, so the keys the players drive with mustn't steer it
+    let controls_replay = !replay.highlight;
+
+    if controls_replay && keyboard_input.just_pressed(KeyCode::Space) {
+        replay.playing = !replay.playing;
+    }
+
+    if controls_replay && keyboard_input.just_pressed(KeyCode::Up) {
+        replay.speed = (replay.speed * 2.0).min(REPLAY_MAX_SPEED);
+    }
+
+    if controls_replay && keyboard_input.just_pressed(KeyCode::Down) {
+        replay.speed = (replay.speed * 0.5).max(REPLAY_MIN_SPEED);
+    }
+
+    let scrub = if controls_replay {
+        held(KeyCode::Right) - held(KeyCode::Left)
+    } else {
+        0.0
+    };
+    let play = if replay.playing { replay.speed } else { 0.0 };
+
+    // Every ghost plays against the same clock, so runs recorded from their starts line up
+    let (start, end) =
+        replay
+            .ghosts
+            .iter()
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(start, end), ghost| {
+                (
+                    start.min(ghost.frames[0].time),
+                    end.max(ghost.frames[ghost.frames.len() - 1].time),
+                )
+            });
+
+    replay.time = (replay.time + (play + REPLAY_SCRUB_RATE * scrub) * dt_seconds).clamp(start, end);
+
+    replay.camera_position += REPLAY_CAMERA_SPEED
+        * dt_seconds
+        * Vec2::new(
+            held(KeyCode::L) - held(KeyCode::J),
+            held(KeyCode::I) - held(KeyCode::K),
+        );
+
+    if replay.highlight {
+        if replay.time >= end {
+            replay.active = false;
+
+            for ghost in replay.ghosts.drain(..) {
+                commands.entity(ghost.entity).despawn();
+            }
+
+            return;
+        }
+
+        if let Some((position, _)) = sample_telemetry(&replay.ghosts[0].frames, replay.time) {
+            replay.camera_position = position;
+        }
+    }
+
+    for ghost in replay.ghosts.iter() {
+        if let (Ok(mut transform), Some((position, heading))) = (
+            ghosts.get_mut(ghost.entity),
+            sample_telemetry(&ghost.frames, replay.time),
+        ) {
+            transform.translation = position.extend(Z_GHOST);
+            transform.rotation = Quat::from_rotation_z(heading);
+        }
+    }
+
+    // The live cars' readouts are paused, so show the replay's in the first player's instead
+    if let Some((mut text, _)) = text.iter_mut().min_by_key(|(_, readout)| readout.0) {
+        let replay = &mut *replay;
+        let time = replay.time;
+        let (leader, chasers) = replay.ghosts.split_first_mut().unwrap();
+
+        text.sections[0].value = chasers.iter_mut().fold(
+            format!(
+                "{}Replay: {:.2} s at x{}",
+                if replay.highlight { "REPLAY\n" } else { "" },
+                time,
+                replay.speed
+            ),
+            |readout, chaser| match ghost_time_delta(
+                &leader.frames,
+                &chaser.frames,
+                time,
+                &mut chaser.matched,
+            ) {
+                Some(delta) => format!("{}\nDelta: {:+.2} s", readout, delta),
+                None => readout,
+            },
+        );
+    }
+
+    for mut camera in cameras.iter_mut() {
+        camera.translation = replay.camera_position.extend(camera.translation.z);
+        camera.rotation = Quat::IDENTITY;
+    }
+}
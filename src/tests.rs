@@ -0,0 +1,1867 @@
+use crate::{
+    camera::{
+        shake_camera, CameraBounds, CameraFraming, CameraLookAhead, CameraPose, CameraShake,
+        CameraTransition,
+    },
+    car::{
+        read_player_inputs, CarNumber, CruiseControl, DriftScore, Odometer, PerfTest,
+        PlayerControlled, PreviousGlobalTransform, SpinDetector, StuckReset,
+    },
+    direction_sign,
+    effects::{despawn_timers, move_particles, DespawnTimer, Particle, RngResource},
+    hud::{car_drift_angle, write_config, DriftGauge},
+    physics::{
+        axle_load_shares, corner_stiffness, drag_force, gripping_weight, physics_step, AssistLevel,
+        Assists, AxleConfig, BoundsContact, CarConfig, CarInputs, CarState, CarStats,
+        IntegrationError, LaunchControlPhase, SimTime, SteerCorrectionCurve, TireStats, WallHit,
+        WorldBounds,
+    },
+    plugin::MyStages,
+    precision::{Real, RealVec2},
+    skids::{join_strips, skids_to_evict, strip_ranges, BakedStrip},
+    telemetry::{
+        find_car_snapshot, ghost_time_delta, read_input_recording, read_telemetry,
+        read_world_snapshot, write_input_recording, write_telemetry, write_world_snapshot,
+        CarSnapshot, InputRecording, ObstacleSnapshot, RecordedCar, RecordedFrame, Replay,
+        SkidSnapshot, SpinReplay, TelemetryFrame, WorldSnapshot, INPUT_RECORDING_VERSION,
+        TELEMETRY_FIELD_COUNT, WORLD_SNAPSHOT_VERSION,
+    },
+    track::{
+        ai_inputs, corner_radius, max_corner_speed, parse_track, read_best_laps, segments_cross,
+        write_best_laps, AiDifficulty, BestLap, GatePlacement, Gates, BEST_LAPS_VERSION,
+    },
+    Z_BACKDROP, Z_CAR, Z_CAR_DEBUG, Z_CAR_LIGHTS, Z_CAR_PARTS, Z_GATE, Z_GHOST, Z_GRID, Z_GROUND,
+    Z_LETTERBOX, Z_OBSTACLE, Z_PARTICLE, Z_SECOND_READOUT, Z_SKID, Z_STEER_PLOT, Z_TERRAIN_SHADING,
+};
+use bevy::prelude::*;
+
+/// Steps the car `steps` times at 60 Hz on flat ground, giving where it ends up and the stats
+/// from its last step
+fn drive(
+    config: &CarConfig,
+    mut state: CarState,
+    inputs: &CarInputs,
+    steps: usize,
+) -> (CarState, CarStats) {
+    let mut stats = None;
+
+    for _ in 0..steps {
+        stats = Some(physics_step(
+            1.0 / 60.0,
+            inputs,
+            config,
+            config.gravity,
+            RealVec2::ZERO,
+            &mut state,
+        ));
+    }
+
+    (
+        state,
+        stats.expect("the car must be driven for at least one step"),
+    )
+}
+
+fn launch(steer_angle: Real) -> CarState {
+    let config = CarConfig {
+        slip_speed_floor: 2.0,
+        ..CarConfig::default()
+    };
+
+    let inputs = CarInputs {
+        throttle: 1.0,
+        ..CarInputs::default()
+    };
+
+    let state = CarState {
+        steer_angle,
+        ..CarState::default()
+    };
+
+    drive(&config, state, &inputs, 120).0
+}
+
+/// Drives a scripted lap of accelerating, cornering and braking, recording the exact bits of
+/// the state after every step
+fn scripted_run() -> Vec<(Vec<u64>, LaunchControlPhase, Assists)> {
+    let config = CarConfig {
+        brake_heat_rate: 1.0,
+        brake_cool_rate: 0.1,
+        ..CarConfig::default()
+    };
+
+    let mut state = CarState::initial(&config);
+
+    (0..1200)
+        .map(|frame| {
+            let seconds = frame as f32 / 60.0;
+
+            let inputs = match seconds {
+                s if s < 4.0 => CarInputs {
+                    throttle: 1.0,
+                    ..CarInputs::default()
+                },
+                s if s < 8.0 => CarInputs {
+                    throttle: 0.6,
+                    steer: 1.0,
+                    ..CarInputs::default()
+                },
+                s if s < 12.0 => CarInputs {
+                    brake: 1.0,
+                    steer: -0.5,
+                    ..CarInputs::default()
+                },
+                s if s < 14.0 => CarInputs {
+                    throttle: 1.0,
+                    e_brake: 1.0,
+                    steer: -1.0,
+                    ..CarInputs::default()
+                },
+                _ => CarInputs {
+                    brake: 0.5,
+                    ..CarInputs::default()
+                },
+            };
+
+            // Steering is eased in outside the physics, so follow the input directly here
+            state.steer = inputs.steer as Real;
+            state.steer_angle = config.max_steer as Real * state.steer;
+            state = drive(&config, state.clone(), &inputs, 1).0;
+
+            let CarState {
+                heading,
+                position,
+                velocity,
+                acceleration,
+                local_acceleration,
+                yaw_rate,
+                steer_input,
+                steer,
+                steer_angle,
+                launch_progress,
+                e_brake,
+                brake_temperature,
+                launch_control,
+                assists,
+            } = state;
+
+            let bits = [
+                heading,
+                position.x,
+                position.y,
+                velocity.x,
+                velocity.y,
+                acceleration.x,
+                acceleration.y,
+                local_acceleration.x,
+                local_acceleration.y,
+                yaw_rate,
+                steer_input,
+                steer,
+                steer_angle,
+                launch_progress,
+                e_brake,
+                brake_temperature,
+            ]
+            .iter()
+            .map(|value| u64::from(value.to_bits()))
+            .collect();
+
+            (bits, launch_control, assists)
+        })
+        .collect()
+}
+
+#[test]
+fn physics_is_deterministic() {
+    let first = scripted_run();
+    let second = scripted_run();
+
+    for (frame, (first, second)) in first.iter().zip(second.iter()).enumerate() {
+        assert_eq!(first, second, "runs diverged at frame {}", frame);
+    }
+}
+
+fn turn_with_rear_steer(rear_steer_ratio: f32) -> CarState {
+    let config = CarConfig {
+        rear_steer_ratio,
+        ..CarConfig::default()
+    };
+
+    let state = CarState {
+        steer_angle: 0.3,
+        ..CarState::initial(&CarConfig {
+            initial_speed: 10.0,
+            ..CarConfig::default()
+        })
+    };
+
+    drive(&config, state, &CarInputs::default(), 30).0
+}
+
+/// Drives a sustained circle for a minute from `offset` meters away from the origin, and gives
+/// where the car ends up relative to where it started. With `store_as_f32`, the state is
+/// rounded to `f32` after every step, as the `f32` physics would keep it.
+#[cfg(feature = "f64-physics")]
+fn circle_from(offset: RealVec2, store_as_f32: bool) -> RealVec2 {
+    let config = CarConfig::default();
+
+    let mut state = CarState {
+        steer_angle: 0.2,
+        ..CarState::initial(&CarConfig {
+            initial_speed: 10.0,
+            ..CarConfig::default()
+        })
+    };
+    state.position += offset;
+
+    let inputs = CarInputs {
+        throttle: 0.3,
+        ..CarInputs::default()
+    };
+
+    let round = |value: Real| value as f32 as Real;
+    let round_vec = |value: RealVec2| RealVec2::new(round(value.x), round(value.y));
+
+    for _ in 0..3600 {
+        state = drive(&config, state, &inputs, 1).0;
+
+        if store_as_f32 {
+            state.heading = round(state.heading);
+            state.position = round_vec(state.position);
+            state.velocity = round_vec(state.velocity);
+            state.acceleration = round_vec(state.acceleration);
+            state.local_acceleration = round_vec(state.local_acceleration);
+            state.yaw_rate = round(state.yaw_rate);
+            state.launch_progress = round(state.launch_progress);
+            state.brake_temperature = round(state.brake_temperature);
+        }
+    }
+
+    state.position - offset
+}
+
+/// The same circle driven far from the origin should end in the same place relative to its
+/// start, but `f32` positions that far out are only good to a millimetre or so, and the
+/// rounding builds up lap after lap
+#[cfg(feature = "f64-physics")]
+#[test]
+fn f64_physics_drifts_less_over_a_long_circle() {
+    let far_away = RealVec2::new(10_000.0, 10_000.0);
+
+    let f64_drift = circle_from(far_away, false).distance(circle_from(RealVec2::ZERO, false));
+    let f32_drift = circle_from(far_away, true).distance(circle_from(RealVec2::ZERO, true));
+
+    assert!(f64_drift < 1e-6);
+    assert!(f32_drift > 1e-3);
+    assert!(f32_drift > 1000.0 * f64_drift);
+}
+
+fn corner_on_throttle(config: &CarConfig) -> (CarState, CarStats) {
+    let state = CarState {
+        steer_angle: 0.25,
+        ..CarState::initial(&CarConfig {
+            initial_speed: 12.0,
+            ..CarConfig::default()
+        })
+    };
+
+    let inputs = CarInputs {
+        throttle: 0.5,
+        ..CarInputs::default()
+    };
+
+    drive(config, state, &inputs, 120)
+}
+
+#[test]
+fn two_axles_share_the_load_exactly_as_the_front_and_rear_fields_did() {
+    let (to_front, to_rear): (Real, Real) = (1.3, 1.7);
+    let wheel_base = to_front + to_rear;
+    let transfer_x = 2.5;
+
+    // The lever rule, as it was written before axles could be configured
+    let front = (to_rear / wheel_base, -(transfer_x / wheel_base));
+    let rear = (to_front / wheel_base, transfer_x / wheel_base);
+
+    assert_eq!(
+        axle_load_shares(&[to_front, -to_rear], transfer_x, wheel_base, false),
+        vec![front, rear]
+    );
+    assert_eq!(
+        axle_load_shares(&[-to_rear, to_front], transfer_x, wheel_base, false),
+        vec![rear, front]
+    );
+}
+
+#[test]
+fn four_explicit_axle_tires_match_the_front_and_rear_fields() {
+    let baseline = CarConfig::default();
+
+    let explicit = CarConfig {
+        axles: baseline.resolve_axles(),
+        ..CarConfig::default()
+    };
+
+    let mut resolved = CarConfig::default();
+    resolved.resolve();
+
+    let (baseline_state, baseline_stats) = corner_on_throttle(&baseline);
+
+    assert_eq!(baseline_stats.tires.len(), 4);
+    assert!(baseline_state.yaw_rate > 0.0);
+
+    for config in &[explicit, resolved] {
+        let (state, stats) = corner_on_throttle(config);
+
+        assert_eq!(state.heading, baseline_state.heading);
+        assert_eq!(state.position, baseline_state.position);
+        assert_eq!(state.velocity, baseline_state.velocity);
+        assert_eq!(state.yaw_rate, baseline_state.yaw_rate);
+        assert_eq!(stats.tires.len(), baseline_stats.tires.len());
+
+        for (tire, baseline_tire) in stats.tires.iter().zip(baseline_stats.tires.iter()) {
+            assert_eq!(tire.active_weight, baseline_tire.active_weight);
+            assert_eq!(tire.friction, baseline_tire.friction);
+        }
+    }
+}
+
+#[test]
+fn axles_from_before_driven_drive_the_rear_most() {
+    let mut config = serde_json::from_str::<CarConfig>(
+        r#"{
+            "axles": [
+                { "position": 1.0, "steered": true },
+                { "position": -1.0 },
+                { "position": -0.5 }
+            ]
+        }"#,
+    )
+    .unwrap();
+    config.resolve();
+
+    let driven = config
+        .axles()
+        .iter()
+        .map(|axle| axle.driven)
+        .collect::<Vec<_>>();
+    assert_eq!(driven, vec![false, true, false]);
+
+    let inputs = CarInputs {
+        throttle: 1.0,
+        ..CarInputs::default()
+    };
+
+    let (state, _) = drive(&config, CarState::initial(&config), &inputs, 60);
+
+    assert!(state.velocity.x > 1.0);
+}
+
+#[test]
+fn resolved_axles_follow_changes_to_the_config() {
+    let mut config = CarConfig::default();
+    config.resolve();
+    assert!(!config.needs_resolving());
+
+    config.total_tire_grip_front = 1.0;
+    assert!(config.needs_resolving());
+
+    config.resolve();
+    assert_eq!(config.axles()[0].total_tire_grip, 1.0);
+}
+
+#[test]
+fn smaller_steps_integrate_more_accurately() {
+    let config = CarConfig::default();
+    let state = CarState {
+        steer: 0.5,
+        steer_angle: 0.3,
+        ..CarState::initial(&CarConfig {
+            initial_speed: 10.0,
+            ..CarConfig::default()
+        })
+    };
+
+    let error = |dt_seconds| {
+        IntegrationError::estimate(
+            dt_seconds,
+            &CarInputs::default(),
+            &config,
+            config.gravity,
+            RealVec2::ZERO,
+            &state,
+        )
+    };
+
+    let coarse = error(1.0 / 15.0);
+    let fine = error(1.0 / 240.0);
+
+    assert!(coarse.position > 0.0);
+    assert!(fine.position < coarse.position);
+    assert!(fine.heading <= coarse.heading);
+}
+
+#[test]
+fn steering_boost_only_applies_slow() {
+    let config = CarConfig {
+        max_steer: 0.5,
+        low_speed_steer_boost: 2.0,
+        low_speed_steer_boost_speed: 5.0,
+        ..CarConfig::default()
+    };
+
+    assert!((config.max_steer_at(0.0) - 1.0).abs() < 1e-6);
+    assert!((config.max_steer_at(2.5) - 0.75).abs() < 1e-6);
+    assert!((config.max_steer_at(5.0) - 0.5).abs() < 1e-6);
+    assert!((config.max_steer_at(30.0) - 0.5).abs() < 1e-6);
+
+    let unboosted = CarConfig {
+        max_steer: 0.5,
+        ..CarConfig::default()
+    };
+
+    assert!((unboosted.max_steer_at(0.0) - 0.5).abs() < 1e-6);
+}
+
+#[test]
+fn analog_inputs_have_a_deadzone_and_saturate() {
+    let config = CarConfig {
+        input_deadzone: 0.1,
+        input_saturation: 0.9,
+        ..CarConfig::default()
+    };
+
+    assert_eq!(config.shape_input(0.0), 0.0);
+    assert_eq!(config.shape_input(0.05), 0.0);
+    assert_eq!(config.shape_input(-0.1), 0.0);
+    assert!((config.shape_input(0.5) - 0.5).abs() < 1e-6);
+    assert!((config.shape_input(-0.3) + 0.25).abs() < 1e-6);
+    assert_eq!(config.shape_input(0.9), 1.0);
+    assert_eq!(config.shape_input(-1.0), -1.0);
+
+    let inputs = CarInputs {
+        throttle: 0.05,
+        brake: 0.5,
+        e_brake: 0.05,
+        steer: 0.05,
+    };
+
+    let shaped = config.shape_inputs(inputs);
+
+    assert_eq!(shaped.steer, 0.0);
+    assert_eq!(shaped.throttle, 0.05);
+
+    let shaped = CarConfig {
+        shape_pedal_inputs: true,
+        ..config
+    }
+    .shape_inputs(inputs);
+
+    assert_eq!(shaped.throttle, 0.0);
+    assert!((shaped.brake - 0.5).abs() < 1e-6);
+    assert_eq!(shaped.e_brake, 0.05);
+
+    // The keys' full inputs are untouched by the default shaping
+    let default = CarConfig::default();
+
+    assert_eq!(default.shape_input(1.0), 1.0);
+    assert_eq!(default.shape_input(-1.0), -1.0);
+}
+
+#[test]
+fn opposite_rear_steer_tightens_the_turn() {
+    let front_only = turn_with_rear_steer(0.0);
+    let opposite = turn_with_rear_steer(-0.5);
+    let same = turn_with_rear_steer(0.5);
+
+    assert!(opposite.yaw_rate > front_only.yaw_rate);
+    assert!(same.yaw_rate < front_only.yaw_rate);
+}
+
+#[test]
+fn near_zero_wheelbase_stays_finite() {
+    let config = CarConfig {
+        centre_of_gravity_to_front_axle: 1e-5,
+        centre_of_gravity_to_rear_axle: 1e-5,
+        half_width: 1e-5,
+        ..CarConfig::default()
+    };
+
+    let mut state = CarState::initial(&CarConfig {
+        initial_speed: 10.0,
+        ..config.clone()
+    });
+
+    let inputs = CarInputs {
+        throttle: 1.0,
+        steer: 1.0,
+        ..CarInputs::default()
+    };
+
+    for _ in 0..120 {
+        let (next, stats) = drive(&config, state, &inputs, 1);
+        state = next;
+
+        assert!(stats
+            .tires
+            .iter()
+            .all(|tire| tire.active_weight.is_finite()));
+    }
+
+    assert!(state.position.x.is_finite() && state.position.y.is_finite());
+    assert!(state.velocity.x.is_finite() && state.velocity.y.is_finite());
+    assert!(state.heading.is_finite());
+    assert!(state.yaw_rate.is_finite());
+}
+
+fn brake_with_tire_ratios(tire_brake_ratios: Vec<f32>) -> (CarState, CarStats) {
+    let config = CarConfig {
+        tire_brake_ratios,
+        ..CarConfig::default()
+    };
+
+    let state = CarState::initial(&CarConfig {
+        initial_speed: 20.0,
+        ..CarConfig::default()
+    });
+
+    let inputs = CarInputs {
+        brake: 0.5,
+        ..CarInputs::default()
+    };
+
+    drive(&config, state, &inputs, 30)
+}
+
+#[test]
+fn weak_front_left_brake_pulls_right() {
+    let (balanced, balanced_stats) = brake_with_tire_ratios(vec![1.0; 4]);
+    let (split, split_stats) = brake_with_tire_ratios(vec![0.5, 1.0, 1.0, 1.0]);
+
+    assert_eq!(balanced.heading, 0.0);
+    assert!(split.heading < 0.0);
+    assert!(split.velocity.length() > balanced.velocity.length());
+
+    assert_eq!(
+        balanced_stats.tires[0].brake_torque,
+        balanced_stats.tires[1].brake_torque
+    );
+    assert!(split_stats.tires[0].brake_torque < split_stats.tires[1].brake_torque);
+}
+
+fn brake_in_a_turn(config: &CarConfig) -> (CarState, CarStats) {
+    let config = CarConfig {
+        initial_speed: 20.0,
+        ..config.clone()
+    };
+
+    let state = CarState {
+        steer_angle: 0.25,
+        ..CarState::initial(&config)
+    };
+
+    let inputs = CarInputs {
+        brake: 1.0,
+        ..CarInputs::default()
+    };
+
+    drive(&config, state, &inputs, 10)
+}
+
+#[test]
+fn locked_wheels_only_slide_straight_when_asked() {
+    let (_, unlocked_stats) = brake_in_a_turn(&CarConfig::default());
+    assert!(unlocked_stats.tires.iter().all(|tire| !tire.is_locked));
+
+    let locking = CarConfig {
+        front_brake_lock_ratio: 0.01,
+        ..CarConfig::default()
+    };
+
+    let (steering, steering_stats) = brake_in_a_turn(&locking);
+    assert!(steering_stats.tires[0].is_locked);
+    assert!(steering_stats.tires[1].is_locked);
+
+    let (straight, _) = brake_in_a_turn(&CarConfig {
+        locked_wheels_slide_straight: true,
+        ..locking
+    });
+
+    assert!(straight.yaw_rate.abs() < steering.yaw_rate.abs());
+}
+
+#[test]
+fn zero_has_no_direction() {
+    assert_eq!(direction_sign(0.0), 0.0);
+    assert_eq!(direction_sign(-0.0), 0.0);
+    assert_eq!(direction_sign(Real::EPSILON / 2.0), 0.0);
+    assert_eq!(direction_sign(-Real::EPSILON / 2.0), 0.0);
+    assert_eq!(direction_sign(1e-3), 1.0);
+    assert_eq!(direction_sign(-1e-3), -1.0);
+}
+
+#[test]
+fn braking_and_steering_leave_a_car_at_rest() {
+    let config = CarConfig::default();
+
+    let inputs = CarInputs {
+        brake: 1.0,
+        steer: 1.0,
+        ..CarInputs::default()
+    };
+
+    for &velocity in [
+        RealVec2::ZERO,
+        RealVec2::new(-0.0, -0.0),
+        RealVec2::new(Real::EPSILON, 0.0),
+        RealVec2::new(-Real::EPSILON, Real::EPSILON),
+    ]
+    .iter()
+    {
+        let mut state = CarState {
+            velocity,
+            ..CarState::initial(&config)
+        };
+
+        let start = state.clone();
+
+        for _ in 0..10 {
+            let (next, stats) = drive(&config, state, &inputs, 1);
+            state = next;
+
+            assert!(stats.tires.iter().all(|tire| tire.slip_angle.is_finite()));
+        }
+
+        assert_eq!(state.velocity, RealVec2::ZERO);
+        assert_eq!(state.yaw_rate, 0.0);
+        assert_eq!(state.heading, start.heading);
+        assert_eq!(state.position, start.position);
+    }
+}
+
+#[test]
+fn sideways_air_resistance_scrubs_more_speed() {
+    let config = CarConfig {
+        air_resistance_y: Some(10.0),
+        ..CarConfig::default()
+    };
+
+    let forward = drag_force(&config, RealVec2::new(20.0, 0.0));
+    let sideways = drag_force(&config, RealVec2::new(0.0, 20.0));
+
+    assert!(sideways.y < forward.x);
+    assert_eq!(
+        forward,
+        drag_force(&CarConfig::default(), RealVec2::new(20.0, 0.0))
+    );
+}
+
+#[test]
+fn load_sensitivity_makes_grip_sublinear() {
+    let static_tire_weight = 3000.0;
+
+    let linear = gripping_weight(2.0 * static_tire_weight, static_tire_weight, 1.0);
+    assert_eq!(linear, 2.0 * static_tire_weight);
+
+    let at_rest = gripping_weight(static_tire_weight, static_tire_weight, 0.8);
+    let doubled = gripping_weight(2.0 * static_tire_weight, static_tire_weight, 0.8);
+
+    assert!((at_rest - static_tire_weight).abs() < 1e-3);
+    assert!(doubled > at_rest);
+    assert!(doubled < 2.0 * at_rest);
+}
+
+#[test]
+fn stiffness_follows_load_when_sensitive() {
+    let static_tire_weight = 3000.0;
+    let light = 0.5 * static_tire_weight;
+    let heavy = 1.5 * static_tire_weight;
+
+    assert_eq!(corner_stiffness(5.0, light, static_tire_weight, 0.0), 5.0);
+    assert_eq!(corner_stiffness(5.0, heavy, static_tire_weight, 0.0), 5.0);
+
+    assert!((corner_stiffness(5.0, light, static_tire_weight, 0.5) - 3.75).abs() < 1e-6);
+    assert!((corner_stiffness(5.0, heavy, static_tire_weight, 0.5) - 6.25).abs() < 1e-6);
+
+    assert_eq!(corner_stiffness(5.0, light, static_tire_weight, 4.0), 0.0);
+    assert_eq!(corner_stiffness(5.0, heavy, 0.0, 0.5), 5.0);
+}
+
+#[test]
+fn reseeding_repeats_the_sequence() {
+    let mut rng = RngResource::new(42);
+
+    let first = (0..8).map(|_| rng.next_u64()).collect::<Vec<_>>();
+
+    rng.reseed(42);
+
+    let second = (0..8).map(|_| rng.next_u64()).collect::<Vec<_>>();
+
+    assert_eq!(first, second);
+    assert!((0..1000).all(|_| (0.0..1.0).contains(&rng.next_f32())));
+}
+
+#[test]
+fn cautious_ai_brakes_earlier() {
+    let config = CarConfig::default();
+    let state = CarState {
+        velocity: RealVec2::new(25.0, 0.0),
+        ..CarState::default()
+    };
+
+    let inputs = |difficulty| {
+        ai_inputs(
+            difficulty,
+            &config,
+            config.gravity,
+            &state,
+            Vec2::new(-20.0, 0.0),
+            Vec2::new(20.0, 0.0),
+            Vec2::new(20.0, 20.0),
+        )
+    };
+
+    let cautious = inputs(AiDifficulty::Cautious);
+    let aggressive = inputs(AiDifficulty::Aggressive);
+
+    assert!(cautious.brake > 0.0);
+    assert_eq!(cautious.throttle, 0.0);
+    assert!(aggressive.throttle > 0.0);
+    assert_eq!(aggressive.brake, 0.0);
+    assert_eq!(cautious.steer, 0.0);
+}
+
+#[test]
+fn tighter_corners_must_be_taken_slower() {
+    let (a, b) = (Vec2::new(-10.0, 0.0), Vec2::new(0.0, 10.0));
+    let radius = corner_radius(a, b, Vec2::new(10.0, 0.0)).unwrap();
+
+    assert!((radius - 10.0).abs() < 1e-4);
+    assert!(corner_radius(a, b, Vec2::new(10.0, 20.0)).is_none());
+
+    let tight = max_corner_speed(1.0, 9.81, 10.0);
+    let wide = max_corner_speed(1.0, 9.81, 40.0);
+
+    assert!((tight - 9.81f32.sqrt() * 10.0f32.sqrt()).abs() < 1e-4);
+    assert!((wide - 2.0 * tight).abs() < 1e-4);
+}
+
+#[test]
+fn input_recordings_re_simulate_exactly() {
+    let config = CarConfig::default();
+
+    let run = |state: &mut CarState, frames: &[RecordedFrame]| {
+        for frame in frames {
+            physics_step(
+                frame.delta_seconds,
+                &frame.inputs(0),
+                &config,
+                config.gravity,
+                RealVec2::ZERO,
+                state,
+            );
+        }
+    };
+
+    let mut state = CarState::initial(&CarConfig {
+        initial_speed: 15.0,
+        ..CarConfig::default()
+    });
+    state.steer_angle = 0.2;
+    run(
+        &mut state,
+        &[RecordedFrame {
+            delta_seconds: 1.0 / 60.0,
+            inputs: vec![[0.3, 0.0, 0.0, 0.2]],
+        }],
+    );
+
+    let recording = InputRecording {
+        version: INPUT_RECORDING_VERSION,
+        seed: 42,
+        cars: vec![RecordedCar {
+            number: 0,
+            start: CarSnapshot::new(None, &state),
+        }],
+        frames: (0..120)
+            .map(|index| RecordedFrame {
+                delta_seconds: 1.0 / (50.0 + (index % 20) as f32),
+                inputs: vec![[0.7, 0.1 * (index % 3) as f32, 0.0, 0.013 * index as f32]],
+            })
+            .collect(),
+    };
+
+    run(&mut state, &recording.frames);
+
+    let mut bytes = Vec::new();
+    write_input_recording(&mut bytes, &recording).unwrap();
+    let played = read_input_recording(bytes.as_slice()).unwrap();
+
+    assert_eq!(played, recording);
+
+    let mut replayed = played.cars[0].start.state();
+    run(&mut replayed, &played.frames);
+
+    assert_eq!(
+        CarSnapshot::new(None, &replayed),
+        CarSnapshot::new(None, &state)
+    );
+
+    let newer = format!(r#"{{ "version": {} }}"#, INPUT_RECORDING_VERSION + 1);
+    assert!(read_input_recording(newer.as_bytes()).is_err());
+}
+
+#[test]
+fn telemetry_round_trips() {
+    let state = CarState::initial(&CarConfig {
+        initial_speed: 12.0,
+        ..CarConfig::default()
+    });
+
+    let inputs = CarInputs {
+        throttle: 0.5,
+        steer: -0.25,
+        ..CarInputs::default()
+    };
+
+    let frames = vec![
+        TelemetryFrame::new(0.0, &state, &inputs),
+        TelemetryFrame::new(1.0 / 60.0, &state, &CarInputs::default()),
+    ];
+
+    let mut bytes = Vec::new();
+    write_telemetry(&mut bytes, &frames).unwrap();
+
+    let read = read_telemetry(bytes.as_slice()).unwrap();
+
+    assert_eq!(
+        read.iter()
+            .map(|frame| frame.to_fields())
+            .collect::<Vec<_>>(),
+        frames
+            .iter()
+            .map(|frame| frame.to_fields())
+            .collect::<Vec<_>>()
+    );
+
+    assert!(read_telemetry(&bytes[..bytes.len() - 1]).is_err());
+    assert!(read_telemetry(&b"nope"[..]).is_err());
+}
+
+#[test]
+fn world_snapshots_round_trip() {
+    let snapshot = WorldSnapshot {
+        version: WORLD_SNAPSHOT_VERSION,
+        cars: vec![CarSnapshot {
+            number: Some(1),
+            config: Some(String::from("config.car")),
+            heading: 0.5,
+            position: [3.0, -4.0],
+            velocity: [10.0, 1.0],
+            launch_control: LaunchControlPhase::Armed { preload: 0.3 },
+            ..CarSnapshot::default()
+        }],
+        obstacles: vec![ObstacleSnapshot {
+            centre: [1.0, 2.0],
+            half_size: [0.4, 0.4],
+        }],
+        skids: vec![SkidSnapshot {
+            color: [0.0, 0.0, 0.0, 1.0],
+            positions: vec![[0.0, 0.0, Z_SKID], [0.0, 1.0, Z_SKID]],
+            uvs: vec![[0.0, 0.0], [1.0, 0.0]],
+        }],
+    };
+
+    let mut bytes = Vec::new();
+    write_world_snapshot(&mut bytes, &snapshot).unwrap();
+
+    assert_eq!(read_world_snapshot(bytes.as_slice()).unwrap(), snapshot);
+
+    let state = snapshot.cars[0].state();
+    assert_eq!(
+        CarSnapshot::new(Some(String::from("config.car")), &state),
+        snapshot.cars[0]
+    );
+}
+
+#[test]
+fn snapshots_match_cars_by_number() {
+    let snapshot = |number, heading| CarSnapshot {
+        number,
+        heading,
+        ..CarSnapshot::default()
+    };
+
+    let numbered = [snapshot(Some(2), 2.0), snapshot(Some(0), 0.0)];
+
+    assert_eq!(
+        find_car_snapshot(&numbered, CarNumber(0), 0)
+            .unwrap()
+            .heading,
+        0.0
+    );
+    assert_eq!(
+        find_car_snapshot(&numbered, CarNumber(2), 1)
+            .unwrap()
+            .heading,
+        2.0
+    );
+    assert!(find_car_snapshot(&numbered, CarNumber(1), 0).is_none());
+
+    let older = [snapshot(None, 0.0), snapshot(None, 1.0)];
+
+    assert_eq!(
+        find_car_snapshot(&older, CarNumber(5), 1).unwrap().heading,
+        1.0
+    );
+    assert!(find_car_snapshot(&older, CarNumber(0), 2).is_none());
+}
+
+#[test]
+fn world_snapshots_from_other_versions() {
+    let older = read_world_snapshot(r#"{ "version": 0, "cars": [{ "heading": 1.0 }] }"#.as_bytes())
+        .unwrap();
+
+    assert_eq!(older.cars.len(), 1);
+    assert_eq!(older.cars[0].launch_control, LaunchControlPhase::Off);
+    assert!(older.obstacles.is_empty());
+
+    let newer = format!(r#"{{ "version": {} }}"#, WORLD_SNAPSHOT_VERSION + 1);
+    assert!(read_world_snapshot(newer.as_bytes()).is_err());
+}
+
+#[test]
+fn ghost_delta_follows_the_lap_rather_than_the_closest_pass() {
+    // Two laps of a circle, passing the start at 0, 2 and 4 seconds
+    let frames = (0..=240)
+        .map(|frame| {
+            let time = frame as f32 / 60.0;
+            let angle = std::f32::consts::PI * time;
+
+            let mut fields = [0.0; TELEMETRY_FIELD_COUNT];
+            fields[0] = time;
+            fields[1] = 10.0 * angle.cos();
+            fields[2] = 10.0 * angle.sin();
+
+            TelemetryFrame::from_fields(fields)
+        })
+        .collect::<Vec<_>>();
+
+    let mut matched = 0;
+
+    for frame in frames.iter() {
+        let delta = ghost_time_delta(&frames, &frames, frame.time, &mut matched).unwrap();
+
+        assert!(delta.abs() < 1e-4, "{} s behind at {} s", delta, frame.time);
+    }
+
+    assert_eq!(matched, 240);
+}
+
+#[test]
+fn spin_replay_keeps_only_the_last_moments() {
+    let mut spin_replay = SpinReplay {
+        duration: 2.0,
+        ..SpinReplay::default()
+    };
+
+    for step in 0..=100 {
+        let mut fields = [0.0; TELEMETRY_FIELD_COUNT];
+        fields[0] = step as f32 * 0.1;
+
+        spin_replay.record(TelemetryFrame::from_fields(fields));
+    }
+
+    let first = spin_replay.history.front().unwrap().time;
+    let last = spin_replay.history.back().unwrap().time;
+
+    assert!((last - 10.0).abs() < 1e-4);
+    assert!(last - first > 2.0 - 1e-4);
+    assert!(last - first < 2.1 + 1e-4);
+}
+
+#[test]
+fn best_laps_survive_restarts() {
+    let laps = vec![BestLap {
+        config: "config.car".to_owned(),
+        gates: vec![[25.0, 0.0, 1.5, 5.0], [40.0, 25.0, 0.0, 5.0]],
+        seconds: 12.345,
+        ghost: vec![[0.1; TELEMETRY_FIELD_COUNT]; 3],
+    }];
+
+    let mut bytes = Vec::new();
+    write_best_laps(&mut bytes, &laps).unwrap();
+
+    assert_eq!(read_best_laps(bytes.as_slice()).unwrap(), laps);
+
+    let without_ghost = read_best_laps(
+        r#"{ "version": 1, "laps": [{ "config": "a.car", "gates": [], "seconds": 1.0 }] }"#
+            .as_bytes(),
+    )
+    .unwrap();
+    assert!(without_ghost[0].ghost.is_empty());
+
+    assert!(read_best_laps("not json".as_bytes()).is_err());
+
+    let newer = format!(r#"{{ "version": {} }}"#, BEST_LAPS_VERSION + 1);
+    assert!(read_best_laps(newer.as_bytes()).is_err());
+}
+
+#[test]
+fn segments_cross_only_within_both() {
+    let gate = (Vec2::new(0.0, -1.0), Vec2::new(0.0, 1.0));
+
+    assert!(segments_cross(
+        Vec2::new(-1.0, 0.0),
+        Vec2::new(1.0, 0.0),
+        gate.0,
+        gate.1
+    ));
+    assert!(!segments_cross(
+        Vec2::new(-1.0, 2.0),
+        Vec2::new(1.0, 2.0),
+        gate.0,
+        gate.1
+    ));
+    assert!(!segments_cross(
+        Vec2::new(-2.0, 0.0),
+        Vec2::new(-1.0, 0.0),
+        gate.0,
+        gate.1
+    ));
+}
+
+#[test]
+fn launch_without_steering_is_straight() {
+    let state = launch(0.0);
+
+    assert_eq!(state.heading, 0.0);
+    assert_eq!(state.position.y, 0.0);
+    assert!(state.position.x > 0.0);
+}
+
+#[test]
+fn launch_with_steering_held_turns() {
+    let state = launch(0.3);
+
+    assert!(state.heading > 0.0);
+    assert!(state.position.y > 0.0);
+}
+
+/// How far the drive force outstrips what the rear tires can grip with, summed over the
+/// first moments of a full throttle launch
+fn launch_rear_slip(launch_ramp_time: f32) -> Real {
+    let config = CarConfig {
+        engine_force: 20000.0,
+        launch_ramp_time,
+        ..CarConfig::default()
+    };
+
+    let rear_grip = (config.total_tire_grip_rear * config.mass * config.gravity / 2.0) as Real;
+
+    let mut state = CarState::initial(&config);
+
+    let inputs = CarInputs {
+        throttle: 1.0,
+        ..CarInputs::default()
+    };
+
+    (0..15)
+        .map(|_| {
+            state = drive(&config, state.clone(), &inputs, 1).0;
+
+            (state.local_acceleration.x * config.mass as Real - rear_grip).max(0.0)
+        })
+        .sum()
+}
+
+#[test]
+fn clutch_reduces_rear_slip_at_launch() {
+    let instant = launch_rear_slip(0.0);
+    let clutched = launch_rear_slip(1.0);
+
+    assert!(instant > 0.0);
+    assert!(clutched < instant);
+}
+
+#[test]
+fn abs_keeps_the_brakes_within_grip() {
+    let config = CarConfig {
+        brake_force: 60000.0,
+        initial_speed: 20.0,
+        ..CarConfig::default()
+    };
+
+    let inputs = CarInputs {
+        brake: 1.0,
+        ..CarInputs::default()
+    };
+
+    let brake = |assists| {
+        let state = CarState {
+            assists,
+            ..CarState::initial(&config)
+        };
+
+        drive(&config, state, &inputs, 1)
+    };
+
+    let (locked, locked_stats) = brake(Assists::default());
+    let (abs, abs_stats) = brake(AssistLevel::Low.assists());
+
+    // In brake torque, with two tires to each axle and grip in proportion to load
+    let grip_limit = |tire: &TireStats| {
+        let grip = if tire.steered {
+            config.total_tire_grip_front
+        } else {
+            config.total_tire_grip_rear
+        };
+
+        grip as Real * tire.active_weight / 2.0 * config.wheel_radius as Real
+    };
+
+    assert!(locked_stats
+        .tires
+        .iter()
+        .any(|tire| tire.brake_torque > grip_limit(tire) + 1e-3));
+    assert!(abs_stats
+        .tires
+        .iter()
+        .all(|tire| tire.brake_torque <= grip_limit(tire) + 1e-3));
+
+    assert!(abs.velocity.x < config.initial_speed as Real);
+    assert!(abs.velocity.x > locked.velocity.x);
+}
+
+#[test]
+fn traction_control_keeps_the_drive_within_grip() {
+    let config = CarConfig {
+        engine_force: 20000.0,
+        ..CarConfig::default()
+    };
+
+    let rear_grip = (config.total_tire_grip_rear * config.mass * config.gravity / 2.0) as Real;
+
+    let inputs = CarInputs {
+        throttle: 1.0,
+        ..CarInputs::default()
+    };
+
+    let launch = |assists| {
+        let state = CarState {
+            assists,
+            ..CarState::initial(&config)
+        };
+
+        let (state, _) = drive(&config, state, &inputs, 1);
+
+        state.local_acceleration.x * config.mass as Real
+    };
+
+    assert!(launch(Assists::default()) > rear_grip + 1.0);
+    assert!(launch(AssistLevel::Low.assists()) > rear_grip + 1.0);
+    assert!(launch(AssistLevel::Full.assists()) <= rear_grip + 1.0);
+    assert!(launch(AssistLevel::Full.assists()) > 0.0);
+}
+
+#[test]
+fn assist_levels_only_add_assists() {
+    assert_eq!(AssistLevel::None.assists(), Assists::default());
+
+    let low = AssistLevel::Low.assists();
+    let full = AssistLevel::Full.assists();
+
+    assert!(low.abs && full.abs);
+    assert!(!low.traction_control && full.traction_control);
+    assert!(full.yaw_damping >= low.yaw_damping);
+    assert!(full.steer_settle_time >= low.steer_settle_time);
+}
+
+fn pull_e_brake(e_brake_ramp_time: f32) -> CarState {
+    let config = CarConfig {
+        e_brake_ramp_time,
+        ..CarConfig::default()
+    };
+
+    let inputs = CarInputs {
+        e_brake: 1.0,
+        ..CarInputs::default()
+    };
+
+    let state = CarState::initial(&CarConfig {
+        initial_speed: 20.0,
+        ..CarConfig::default()
+    });
+
+    drive(&config, state, &inputs, 1).0
+}
+
+#[test]
+fn e_brake_is_instant_without_ramp() {
+    assert_eq!(pull_e_brake(0.0).e_brake, 1.0);
+}
+
+#[test]
+fn e_brake_ramps_in() {
+    let e_brake = pull_e_brake(0.5).e_brake;
+
+    assert!(e_brake > 0.0 && e_brake < 1.0);
+}
+
+fn spin(yaw_damping: f32) -> (CarState, CarStats) {
+    let config = CarConfig {
+        yaw_damping,
+        ..CarConfig::default()
+    };
+
+    let state = CarState {
+        yaw_rate: 1.0,
+        steer_angle: 0.1,
+        ..CarState::initial(&CarConfig {
+            initial_speed: 20.0,
+            ..CarConfig::default()
+        })
+    };
+
+    drive(&config, state, &CarInputs::default(), 1)
+}
+
+#[test]
+fn yaw_damping_opposes_rotation() {
+    let (undamped, undamped_stats) = spin(0.0);
+    let (damped, damped_stats) = spin(2.0);
+
+    assert_eq!(undamped_stats.yaw_damping_torque, 0.0);
+    assert!(damped_stats.yaw_damping_torque < 0.0);
+    assert!(damped.yaw_rate < undamped.yaw_rate);
+}
+
+/// The yaw rate through three seconds of a step steer at speed, without drag slowing the car
+fn step_steer(yaw_damping: f32) -> Vec<Real> {
+    let config = CarConfig {
+        yaw_damping,
+        air_resistance: 0.0,
+        roll_resistance: 0.0,
+        initial_speed: 20.0,
+        ..CarConfig::default()
+    };
+
+    let mut state = CarState::initial(&config);
+
+    let inputs = CarInputs {
+        steer: 0.3,
+        ..CarInputs::default()
+    };
+
+    (0..180)
+        .map(|_| {
+            state = drive(&config, state.clone(), &inputs, 1).0;
+
+            state.yaw_rate
+        })
+        .collect()
+}
+
+/// How far the yaw rate strays from where it settles within each second of a step steer
+fn yaw_amplitudes(yaw_rates: &[Real]) -> Vec<Real> {
+    let settled = *yaw_rates.last().unwrap();
+
+    yaw_rates
+        .chunks(60)
+        .map(|second| {
+            second
+                .iter()
+                .map(|yaw_rate| (yaw_rate - settled).abs())
+                .fold(0.0, Real::max)
+        })
+        .collect()
+}
+
+#[test]
+fn yaw_damping_settles_a_step_steer() {
+    let undamped = yaw_amplitudes(&step_steer(0.0));
+    let damped = yaw_amplitudes(&step_steer(2.0));
+
+    assert!(damped[2] <= damped[1]);
+    assert!(damped[1] < damped[0]);
+    assert!(damped[1] <= undamped[1]);
+}
+
+#[test]
+fn only_player_controlled_cars_follow_the_keyboard() {
+    let mut world = World::default();
+
+    let mut keyboard_input = Input::<KeyCode>::default();
+    keyboard_input.press(KeyCode::Up);
+    keyboard_input.press(KeyCode::Left);
+    world.insert_resource(keyboard_input);
+    world.insert_resource(Replay::default());
+    world.insert_resource(StuckReset::default());
+
+    let car = || {
+        (
+            CarInputs::default(),
+            CarState::default(),
+            DriftScore::default(),
+            PerfTest::default(),
+            CruiseControl::default(),
+            Odometer::default(),
+        )
+    };
+
+    let player = world
+        .spawn()
+        .insert_bundle(car())
+        .insert(PlayerControlled::default())
+        .id();
+    let other = world.spawn().insert_bundle(car()).id();
+
+    let mut stage = SystemStage::single_threaded();
+    stage.add_system(read_player_inputs.system());
+    stage.run(&mut world);
+
+    let player_inputs = world.get::<CarInputs>(player).unwrap();
+    assert_eq!(player_inputs.throttle, 1.0);
+    assert_eq!(player_inputs.steer, 1.0);
+
+    let other_inputs = world.get::<CarInputs>(other).unwrap();
+    assert_eq!(other_inputs.throttle, 0.0);
+    assert_eq!(other_inputs.steer, 0.0);
+}
+
+#[derive(Default)]
+struct SeenTransforms(Vec<(Vec3, Vec3)>);
+
+fn see_transforms(
+    tires: Query<(&PreviousGlobalTransform, &GlobalTransform)>,
+    mut seen: ResMut<SeenTransforms>,
+) {
+    for (previous, current) in tires.iter() {
+        seen.0.push((previous.0.translation, current.translation));
+    }
+}
+
+#[test]
+fn skids_see_the_previous_frame_and_new_tires_start_where_they_are() {
+    let mut world = World::default();
+    world.insert_resource(SeenTransforms::default());
+
+    let start = Vec3::new(3.0, 4.0, 0.0);
+    let tire = world
+        .spawn()
+        .insert(GlobalTransform::from_translation(start))
+        .insert(PreviousGlobalTransform(GlobalTransform::identity()))
+        .id();
+
+    // Added in reverse, so only the labels can put them in order
+    let mut stage = SystemStage::single_threaded();
+    stage.add_system(
+        update_previous_global_transform
+            .system()
+            .label(MyStages::UpdatePreviousGlobalTransform),
+    );
+    stage.add_system(
+        see_transforms
+            .system()
+            .before(MyStages::UpdatePreviousGlobalTransform)
+            .after(MyStages::InitPreviousGlobalTransform),
+    );
+    stage.add_system(
+        init_previous_global_transform
+            .system()
+            .label(MyStages::InitPreviousGlobalTransform),
+    );
+
+    stage.run(&mut world);
+
+    let end = Vec3::new(5.0, 4.0, 0.0);
+    *world.get_mut::<GlobalTransform>(tire).unwrap() = GlobalTransform::from_translation(end);
+
+    stage.run(&mut world);
+
+    assert_eq!(
+        world.get_resource::<SeenTransforms>().unwrap().0,
+        vec![(start, start), (start, end)]
+    );
+}
+
+#[test]
+fn wall_hits_jolt_the_camera_by_impact_speed() {
+    let jolt = |speed| {
+        let mut world = World::default();
+        world.insert_resource(Time::default());
+        world.insert_resource(Input::<KeyCode>::default());
+        world.insert_resource(CameraShake::default());
+        world.insert_resource(RngResource::new(0));
+        world.insert_resource(Replay::default());
+        world.insert_resource(Events::<WallHit>::default());
+
+        let car = world.spawn().id();
+        world
+            .get_resource_mut::<Events<WallHit>>()
+            .unwrap()
+            .send(WallHit { car, speed });
+
+        let mut stage = SystemStage::single_threaded();
+        stage.add_system(shake_camera.system());
+        stage.run(&mut world);
+
+        world.get_resource::<CameraShake>().unwrap().intensity
+    };
+
+    assert!(jolt(2.0) > 0.0);
+    assert!(jolt(8.0) > jolt(2.0));
+    assert_eq!(jolt(100.0), 1.0);
+}
+
+#[test]
+fn particles_stand_still_while_the_simulation_is_paused() {
+    let mut world = World::default();
+    world.insert_resource(SimTime::default());
+
+    let particle = world
+        .spawn()
+        .insert(Particle {
+            velocity: Vec2::new(1.0, 0.0),
+        })
+        .insert(Transform::default())
+        .insert(DespawnTimer(Timer::from_seconds(0.5, false)))
+        .id();
+
+    let mut stage = SystemStage::single_threaded();
+    stage.add_system(move_particles.system());
+    stage.add_system(despawn_timers.system());
+
+    world.get_resource_mut::<SimTime>().unwrap().hold();
+    stage.run(&mut world);
+    assert_eq!(
+        world.get::<Transform>(particle).unwrap().translation,
+        Vec3::ZERO
+    );
+
+    world.get_resource_mut::<SimTime>().unwrap().advance(0.25);
+    stage.run(&mut world);
+    assert_eq!(
+        world.get::<Transform>(particle).unwrap().translation,
+        Vec3::new(0.25, 0.0, 0.0)
+    );
+
+    world.get_resource_mut::<SimTime>().unwrap().advance(0.25);
+    stage.run(&mut world);
+    assert!(world.get_entity(particle).is_none());
+}
+
+#[test]
+fn camera_shake_follows_the_seed() {
+    let shake = CameraShake {
+        intensity: 1.0,
+        ..CameraShake::default()
+    };
+    let offsets = |seed| {
+        let mut rng = RngResource::new(seed);
+
+        (0..4).map(|_| shake.offset(&mut rng)).collect::<Vec<_>>()
+    };
+
+    assert_eq!(offsets(1), offsets(1));
+    assert_ne!(offsets(1), offsets(2));
+    assert!(offsets(1)
+        .iter()
+        .all(|offset| offset.abs().max_element() <= shake.max_offset));
+    assert_eq!(
+        CameraShake::default().offset(&mut RngResource::new(1)),
+        Vec2::ZERO
+    );
+}
+
+#[test]
+fn camera_transitions_ease_between_views() {
+    let from = CameraPose {
+        position: Vec2::ZERO,
+        rotation: Quat::IDENTITY,
+        pixels_per_meter: 10.0,
+    };
+    let to = CameraPose {
+        position: Vec2::new(10.0, 0.0),
+        rotation: Quat::from_rotation_z(1.0),
+        pixels_per_meter: 20.0,
+    };
+
+    let mut transition = CameraTransition {
+        duration: 1.0,
+        ..CameraTransition::default()
+    };
+
+    assert_eq!(transition.blend(to), to);
+
+    transition.start(from);
+    assert_eq!(transition.blend(to), from);
+
+    transition.advance(0.5);
+    let halfway = transition.blend(to);
+    assert!((halfway.position.x - 5.0).abs() < 1e-4);
+    assert!((halfway.pixels_per_meter - 15.0).abs() < 1e-4);
+
+    // Switching again part of the way through starts from where the camera has got to
+    transition.start(halfway);
+    assert_eq!(transition.blend(from), halfway);
+
+    transition.advance(1.0);
+    assert_eq!(transition.blend(from), from);
+
+    let mut instant = CameraTransition {
+        duration: 0.0,
+        ..CameraTransition::default()
+    };
+
+    instant.start(from);
+    assert_eq!(instant.blend(to), to);
+}
+
+#[test]
+fn look_ahead_stops_short_of_the_view() {
+    let look_ahead = CameraLookAhead {
+        steer_distance: 4.0,
+        velocity_time: 0.5,
+    };
+
+    let state = CarState {
+        steer: 0.5,
+        velocity: RealVec2::new(10.0, 0.0),
+        ..CarState::default()
+    };
+
+    assert!(look_ahead
+        .offset(&state, 100.0)
+        .abs_diff_eq(Vec2::new(5.0, 2.0), 1e-5));
+    assert!((look_ahead.offset(&state, 3.0).length() - 3.0).abs() < 1e-5);
+    assert_eq!(CameraLookAhead::default().offset(&state, 100.0), Vec2::ZERO);
+}
+
+#[test]
+fn drift_angle_is_between_heading_and_velocity() {
+    let state = CarState {
+        heading: 0.1,
+        velocity: RealVec2::new(10.0, 10.0),
+        ..CarState::default()
+    };
+
+    let drift_angle = car_drift_angle(&state, 2.0).unwrap();
+    assert!((drift_angle - (std::f32::consts::FRAC_PI_4 - 0.1)).abs() < 1e-5);
+
+    let gauge = DriftGauge::default();
+    assert!(gauge.in_sweet_spot(drift_angle));
+    assert!(gauge.in_sweet_spot(-drift_angle));
+    assert!(!gauge.in_sweet_spot(0.1));
+
+    let wrapped = CarState {
+        heading: 3.0,
+        velocity: RealVec2::new(-10.0, -1.0),
+        ..CarState::default()
+    };
+    assert!(car_drift_angle(&wrapped, 2.0).unwrap().abs() < std::f32::consts::PI);
+
+    assert_eq!(car_drift_angle(&CarState::default(), 2.0), None);
+}
+
+#[test]
+fn spins_are_noticed_from_heading_turning_away_from_velocity() {
+    let state = |heading: Real, velocity: RealVec2| CarState {
+        heading,
+        velocity,
+        ..CarState::default()
+    };
+
+    // Turning a full circle with the car pointing the way it goes is no spin
+    let mut detector = SpinDetector::default();
+    for step in 0..=100 {
+        let heading = step as Real * 0.07;
+        let velocity = RealVec2::new(heading.cos(), heading.sin()) * 10.0;
+        assert!(!detector.update(&state(heading, velocity)));
+    }
+
+    // Swapping ends while sliding along is, and is noticed only once
+    let mut detector = SpinDetector::default();
+    let spins = (0..=40)
+        .filter(|&step| detector.update(&state(step as Real * 0.1, RealVec2::new(10.0, 0.0))))
+        .count();
+    assert_eq!(spins, 1);
+
+    // A slow turn on the spot is no spin either
+    let mut detector = SpinDetector::default();
+    for step in 0..=40 {
+        assert!(!detector.update(&state(step as Real * 0.1, RealVec2::new(1.0, 0.0))));
+    }
+}
+
+#[test]
+fn wrapping_ignores_axes_with_no_size() {
+    let bounds = WorldBounds::Wrap {
+        half_size: Vec2::new(10.0, 0.0),
+    };
+
+    let mut position = RealVec2::new(12.0, 5.0);
+    let mut velocity = RealVec2::new(1.0, 1.0);
+
+    assert_eq!(
+        bounds.apply(&mut position, &mut velocity),
+        BoundsContact::Wrapped
+    );
+    assert_eq!(position, RealVec2::new(-8.0, 5.0));
+    assert_eq!(velocity, RealVec2::new(1.0, 1.0));
+
+    let mut position = RealVec2::new(3.0, -4.0);
+
+    assert_eq!(
+        bounds.apply(&mut position, &mut velocity),
+        BoundsContact::None
+    );
+    assert_eq!(position, RealVec2::new(3.0, -4.0));
+}
+
+#[test]
+fn camera_stays_within_world_bounds() {
+    let bounds = CameraBounds::default();
+    let world = WorldBounds::Clamp {
+        half_size: Vec2::new(50.0, 30.0),
+    };
+    let half_view = Vec2::new(20.0, 10.0);
+
+    let clamped = bounds.clamp(&world, Vec2::new(45.0, -25.0), Quat::IDENTITY, half_view);
+    assert!(clamped.abs_diff_eq(Vec2::new(30.0, -20.0), 1e-5));
+
+    let turned = Quat::from_rotation_z(std::f32::consts::FRAC_PI_2);
+    let clamped = bounds.clamp(&world, Vec2::new(45.0, -25.0), turned, half_view);
+    assert!(clamped.abs_diff_eq(Vec2::new(40.0, -10.0), 1e-5));
+
+    // A view wider than the world stays centred on it
+    let clamped = bounds.clamp(
+        &world,
+        Vec2::new(45.0, 0.0),
+        Quat::IDENTITY,
+        3.0 * half_view,
+    );
+    assert!(clamped.abs_diff_eq(Vec2::new(0.0, 0.0), 1e-5));
+
+    let unbounded = bounds.clamp(
+        &WorldBounds::Unbounded,
+        Vec2::new(45.0, -25.0),
+        Quat::IDENTITY,
+        half_view,
+    );
+    assert_eq!(unbounded, Vec2::new(45.0, -25.0));
+}
+
+#[test]
+fn sim_time_mirrors_real_time_by_default() {
+    let mut sim_time = SimTime::default();
+    sim_time.advance(0.02);
+    sim_time.advance(0.03);
+
+    assert_eq!(sim_time.delta_seconds(), 0.03);
+    assert!((sim_time.seconds_since_startup() - 0.05).abs() < 1e-6);
+
+    let mut sim_time = SimTime {
+        fixed_delta: Some(0.01),
+        time_scale: 0.5,
+        ..SimTime::default()
+    };
+    sim_time.advance(0.1);
+
+    assert_eq!(sim_time.delta_seconds(), 0.005);
+}
+
+#[test]
+fn skid_budget_evicts_the_oldest_first() {
+    let sizes = [100, 200, 300, 400];
+
+    assert_eq!(skids_to_evict(&sizes, None, None), 0);
+    assert_eq!(skids_to_evict(&sizes, Some(3), None), 1);
+    assert_eq!(skids_to_evict(&sizes, None, Some(700)), 2);
+    assert_eq!(skids_to_evict(&sizes, Some(3), Some(1000)), 1);
+    assert_eq!(skids_to_evict(&sizes, None, Some(0)), 4);
+}
+
+#[test]
+fn joined_skids_are_bridged_by_degenerate_triangles() {
+    let first = ([[0.0, 0.0, 0.0], [1.0, 0.0, 0.0]], [[0.0, 0.0], [1.0, 0.0]]);
+    let second = ([[0.0, 5.0, 0.0], [1.0, 5.0, 0.0]], [[0.0, 0.5], [1.0, 0.5]]);
+
+    let (positions, uvs) = join_strips(vec![
+        (&first.0[..], &first.1[..]),
+        (&[][..], &[][..]),
+        (&second.0[..], &second.1[..]),
+    ]);
+
+    assert_eq!(
+        positions,
+        vec![
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 5.0, 0.0],
+            [0.0, 5.0, 0.0],
+            [1.0, 5.0, 0.0],
+        ]
+    );
+    assert_eq!(uvs.len(), positions.len());
+    assert_eq!(uvs[3], [0.0, 0.5]);
+}
+
+#[test]
+fn baked_strips_are_found_between_their_joins() {
+    let first = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0]];
+    let second = [
+        [0.0, 5.0, 0.0],
+        [1.0, 5.0, 0.0],
+        [0.0, 6.0, 0.0],
+        [1.0, 6.0, 0.0],
+    ];
+    let third = [[0.0, 9.0, 0.0], [1.0, 9.0, 0.0]];
+    let uvs = [[0.0, 0.0]; 4];
+
+    let (positions, _) = join_strips(vec![
+        (&first[..], &uvs[..2]),
+        (&second[..], &uvs[..]),
+        (&third[..], &uvs[..2]),
+    ]);
+
+    let strips = [2, 4, 2]
+        .iter()
+        .map(|&len| BakedStrip {
+            started_seconds: 0.0,
+            len,
+        })
+        .collect::<Vec<_>>();
+    let ranges = strip_ranges(&strips).collect::<Vec<_>>();
+
+    assert_eq!(&positions[ranges[0].clone()], &first[..]);
+    assert_eq!(&positions[ranges[1].clone()], &second[..]);
+    assert_eq!(&positions[ranges[2].clone()], &third[..]);
+    assert_eq!(ranges[2].end, positions.len());
+}
+
+#[test]
+fn empty_config_is_default() {
+    let config = serde_json::from_str::<CarConfig>("{}").unwrap();
+
+    assert_eq!(config, CarConfig::default());
+}
+
+#[test]
+fn partial_config_overrides_only_given_fields() {
+    let config = serde_json::from_str::<CarConfig>(
+        r#"{
+            "mass": 1200.0,
+            "speed_limiter": 30.0,
+            "speed_steer_curve": { "smooth": { "shape": 2.0 } },
+            "axles": [{ "position": 1.0, "steered": true }]
+        }"#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        config,
+        CarConfig {
+            mass: 1200.0,
+            speed_limiter: Some(30.0),
+            speed_steer_curve: SteerCorrectionCurve::Smooth { shape: 2.0 },
+            axles: vec![AxleConfig {
+                position: 1.0,
+                steered: true,
+                ..AxleConfig::default()
+            }],
+            ..CarConfig::default()
+        }
+    );
+}
+
+#[test]
+fn config_round_trips() {
+    let config = CarConfig {
+        mass: 1200.0,
+        speed_limiter: Some(30.0),
+        speed_steer_curve: SteerCorrectionCurve::Smooth { shape: 2.0 },
+        axles: vec![AxleConfig::default(), AxleConfig::default()],
+        initial_position: [1.0, -2.0],
+        ..CarConfig::default()
+    };
+
+    let mut json = Vec::new();
+    write_config(&mut json, &config).unwrap();
+
+    assert_eq!(serde_json::from_slice::<CarConfig>(&json).unwrap(), config);
+}
+
+#[test]
+fn bundled_config_round_trips() {
+    let config = serde_json::from_str::<CarConfig>(include_str!("../assets/config.car")).unwrap();
+
+    let mut json = Vec::new();
+    write_config(&mut json, &config).unwrap();
+
+    assert_eq!(serde_json::from_slice::<CarConfig>(&json).unwrap(), config);
+}
+
+#[test]
+fn nothing_on_a_car_reaches_the_next_layer_up() {
+    let world = [
+        Z_BACKDROP,
+        Z_GROUND,
+        Z_GRID,
+        Z_TERRAIN_SHADING,
+        Z_SKID,
+        Z_OBSTACLE,
+        Z_CAR,
+        Z_GATE,
+        Z_PARTICLE,
+        Z_GHOST,
+    ];
+    assert!(world.windows(2).all(|pair| pair[0] < pair[1]));
+
+    let car = [
+        Z_CAR,
+        Z_CAR + Z_CAR_PARTS,
+        Z_CAR + Z_CAR_LIGHTS,
+        Z_CAR + Z_CAR_DEBUG,
+        Z_GATE,
+    ];
+    assert!(car.windows(2).all(|pair| pair[0] < pair[1]));
+
+    let camera = [Z_STEER_PLOT, Z_LETTERBOX, Z_SECOND_READOUT];
+    assert!(camera.windows(2).all(|pair| pair[0] < pair[1]));
+}
+
+#[test]
+fn bundled_track_places_the_default_gates() {
+    let track = parse_track(include_bytes!("../assets/default.track")).unwrap();
+
+    let placements = track
+        .checkpoints
+        .iter()
+        .map(|checkpoint| GatePlacement {
+            position: Vec2::from(checkpoint.position),
+            angle: checkpoint.angle,
+            width: checkpoint.width,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(placements, Gates::default().placements);
+
+    assert!(parse_track(b"{ \"walls\": [] }").is_err());
+}
+
+#[test]
+fn framing_pulls_back_only_as_far_as_the_cars_need() {
+    let framing = CameraFraming {
+        margin: 5.0,
+        zoom_out: 2.0,
+    };
+
+    // Pulled back twice as far, so it reaches 20 by 15 meters at the plain zoom
+    let half_view = Vec2::new(40.0, 30.0);
+
+    assert_eq!(framing.zoom_out_to_fit(Vec2::new(5.0, 5.0), half_view), 1.0);
+    assert!((framing.zoom_out_to_fit(Vec2::new(35.0, 5.0), half_view) - 2.0).abs() < 1e-6);
+    assert!((framing.zoom_out_to_fit(Vec2::new(5.0, 25.0), half_view) - 2.0).abs() < 1e-6);
+}
@@ -0,0 +1,1096 @@
+//! Tracks, surfaces and obstacles, the gates and lap times, and the AI drivers
+
+use crate::{
+    car::{ConfigSource, PaletteMaterials, PlayerControlled},
+    from_real,
+    physics::{CarConfig, CarInputs, CarState, CheckpointPassed, Gravity, SimTime, WorldBounds},
+    telemetry::{sample_telemetry, GhostMaterials, Replay, TelemetryFrame, TELEMETRY_FIELD_COUNT},
+    Z_GATE, Z_GHOST, Z_GROUND, Z_OBSTACLE,
+};
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Surface {
+    Tarmac,
+    Grass,
+    Dirt,
+}
+
+impl Surface {
+    pub(crate) fn color(self) -> Color {
+        match self {
+            Surface::Tarmac => Color::GRAY,
+            Surface::Grass => Color::rgb(0.25, 0.45, 0.2),
+            Surface::Dirt => Color::rgb(0.45, 0.33, 0.2),
+        }
+    }
+}
+
+pub(crate) struct SurfaceZone {
+    pub(crate) surface: Surface,
+    pub(crate) half_size: Vec2,
+}
+
+impl SurfaceZone {
+    pub(crate) fn contains(&self, transform: &GlobalTransform, point: Vec2) -> bool {
+        let offset = (point - transform.translation.truncate()).abs();
+
+        offset.x <= self.half_size.x && offset.y <= self.half_size.y
+    }
+}
+
+#[derive(Bundle)]
+struct SurfaceZoneBundle {
+    #[bundle]
+    sprite: SpriteBundle,
+    zone: SurfaceZone,
+}
+
+impl SurfaceZoneBundle {
+    pub(crate) fn new(
+        materials: &mut Assets<ColorMaterial>,
+        surface: Surface,
+        centre: Vec2,
+        half_size: Vec2,
+    ) -> Self {
+        Self {
+            sprite: SpriteBundle {
+                sprite: Sprite {
+                    size: 2.0 * half_size,
+                    ..Default::default()
+                },
+                material: materials.add(ColorMaterial::color(surface.color())),
+                transform: Transform::from_translation(centre.extend(Z_GROUND)),
+                ..Default::default()
+            },
+            zone: SurfaceZone { surface, half_size },
+        }
+    }
+}
+
+pub(crate) struct Obstacle {
+    pub(crate) half_size: Vec2,
+}
+
+impl Obstacle {
+    pub(crate) fn contains(&self, transform: &Transform, point: Vec2) -> bool {
+        let offset = (point - transform.translation.truncate()).abs();
+
+        offset.x <= self.half_size.x && offset.y <= self.half_size.y
+    }
+}
+
+#[derive(Bundle)]
+pub(crate) struct ObstacleBundle {
+    #[bundle]
+    pub(crate) sprite: SpriteBundle,
+    pub(crate) obstacle: Obstacle,
+}
+
+impl ObstacleBundle {
+    pub(crate) fn new(material: Handle<ColorMaterial>, centre: Vec2, half_size: Vec2) -> Self {
+        Self {
+            sprite: SpriteBundle {
+                sprite: Sprite {
+                    size: 2.0 * half_size,
+                    ..Default::default()
+                },
+                material,
+                transform: Transform::from_translation(centre.extend(Z_OBSTACLE)),
+                ..Default::default()
+            },
+            obstacle: Obstacle { half_size },
+        }
+    }
+}
+
+pub(crate) fn drop_obstacles(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    palette_materials: Res<PaletteMaterials>,
+    cars: Query<&CarState>,
+    obstacles: Query<(Entity, &Obstacle, &Transform)>,
+    dropped_obstacles: Query<Entity, (With<Obstacle>, Without<TrackEntity>)>,
+) {
+    if keyboard_input.just_pressed(KeyCode::O) {
+        for state in cars.iter() {
+            let position = from_real(state.position);
+
+            // Don't stack obstacles on top of each other while the car is parked
+            if obstacles
+                .iter()
+                .any(|(_, obstacle, transform)| obstacle.contains(transform, position))
+            {
+                continue;
+            }
+
+            commands.spawn_bundle(ObstacleBundle::new(
+                palette_materials.obstacle.clone(),
+                position,
+                0.4 * Vec2::ONE,
+            ));
+        }
+    }
+
+    if keyboard_input.just_pressed(KeyCode::P) {
+        for obstacle in dropped_obstacles.iter() {
+            commands.entity(obstacle).despawn();
+        }
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct TrackSurface {
+    surface: Surface,
+    centre: [f32; 2],
+    half_size: [f32; 2],
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct TrackObstacle {
+    centre: [f32; 2],
+    half_size: [f32; 2],
+}
+
+/// Walls round the edge of the track, which the car stops dead against
+#[derive(Debug, serde::Deserialize)]
+struct TrackWalls {
+    half_size: [f32; 2],
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct TrackCheckpoint {
+    position: [f32; 2],
+    angle: f32,
+    width: f32,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct TrackWaypoint {
+    position: [f32; 2],
+    /// How close, in meters, an AI driver must get before heading for the next waypoint
+    radius: f32,
+}
+
+/// Every section is optional. Without walls the world keeps its bounds, without checkpoints the
+/// gates keep their placements, and without waypoints the AI drivers follow the gates
+#[derive(Debug, Default, serde::Deserialize, TypeUuid)]
+#[uuid = "f390716c-aec5-478f-88e3-caf154450d78"]
+#[serde(default)]
+pub(crate) struct TrackConfig {
+    walls: Option<TrackWalls>,
+    surfaces: Vec<TrackSurface>,
+    pub(crate) obstacles: Vec<TrackObstacle>,
+    pub(crate) checkpoints: Vec<TrackCheckpoint>,
+    waypoints: Vec<TrackWaypoint>,
+}
+
+pub(crate) fn parse_track(bytes: &[u8]) -> anyhow::Result<TrackConfig> {
+    Ok(serde_json::from_str(std::str::from_utf8(bytes)?)?)
+}
+
+#[derive(Default)]
+pub struct TrackConfigLoader;
+
+impl AssetLoader for TrackConfigLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let track = match parse_track(bytes) {
+                Ok(track) => track,
+                Err(err) => {
+                    error!(
+                        "Failed to load the track from {}: {}",
+                        load_context.path().display(),
+                        err
+                    );
+                    return Err(err);
+                }
+            };
+            load_context.set_default_asset(LoadedAsset::new(track));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["track"]
+    }
+}
+
+pub(crate) struct TrackHandle(pub(crate) Handle<TrackConfig>);
+
+/// Marks everything spawned from the track, so that it can be replaced when the track reloads
+pub(crate) struct TrackEntity;
+
+pub(crate) fn spawn_track(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<TrackConfig>>,
+    tracks: Res<Assets<TrackConfig>>,
+    track: Res<TrackHandle>,
+    mut world_bounds: ResMut<WorldBounds>,
+    mut gates: ResMut<Gates>,
+    mut ai_route: ResMut<AiRoute>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    palette_materials: Res<PaletteMaterials>,
+    track_entities: Query<Entity, With<TrackEntity>>,
+) {
+    let has_changed = events.iter().any(|event| match event {
+        AssetEvent::Created { handle } | AssetEvent::Modified { handle } => *handle == track.0,
+        AssetEvent::Removed { .. } => false,
+    });
+
+    if !has_changed {
+        return;
+    }
+
+    // A track which fails to parse is reported by its loader and leaves the old one in place
+    let config = match tracks.get(&track.0) {
+        Some(config) => config,
+        None => return,
+    };
+
+    for entity in track_entities.iter() {
+        commands.entity(entity).despawn();
+    }
+
+    for zone in config.surfaces.iter() {
+        commands
+            .spawn_bundle(SurfaceZoneBundle::new(
+                &mut materials,
+                zone.surface,
+                Vec2::from(zone.centre),
+                Vec2::from(zone.half_size),
+            ))
+            .insert(TrackEntity);
+    }
+
+    for obstacle in config.obstacles.iter() {
+        commands
+            .spawn_bundle(ObstacleBundle::new(
+                palette_materials.obstacle.clone(),
+                Vec2::from(obstacle.centre),
+                Vec2::from(obstacle.half_size),
+            ))
+            .insert(TrackEntity);
+    }
+
+    if let Some(walls) = &config.walls {
+        *world_bounds = WorldBounds::Clamp {
+            half_size: Vec2::from(walls.half_size),
+        };
+    }
+
+    if !config.checkpoints.is_empty() {
+        gates.placements = config
+            .checkpoints
+            .iter()
+            .map(|checkpoint| GatePlacement {
+                position: Vec2::from(checkpoint.position),
+                angle: checkpoint.angle,
+                width: checkpoint.width,
+            })
+            .collect();
+        gates.current = 0;
+    }
+
+    ai_route.waypoints = config
+        .waypoints
+        .iter()
+        .map(|waypoint| (Vec2::from(waypoint.position), waypoint.radius))
+        .collect();
+
+    info!(
+        "Loaded track with {} surfaces, {} obstacles, {} checkpoints and {} waypoints{}",
+        config.surfaces.len(),
+        config.obstacles.len(),
+        config.checkpoints.len(),
+        config.waypoints.len(),
+        if config.walls.is_some() {
+            ", inside walls"
+        } else {
+            ""
+        }
+    );
+}
+
+/// Where a gate is, and how wide it is, in meters, with its posts either side of `position`
+/// along `angle`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GatePlacement {
+    pub position: Vec2,
+    pub angle: f32,
+    pub width: f32,
+}
+
+impl GatePlacement {
+    fn posts(&self) -> (Vec2, Vec2) {
+        let half_span = 0.5 * self.width * Vec2::new(self.angle.cos(), self.angle.sin());
+
+        (self.position - half_span, self.position + half_span)
+    }
+}
+
+/// A gate to drive between, which moves on to the next placement each time it's passed
+pub struct Gates {
+    pub enabled: bool,
+    pub toggle_key: KeyCode,
+    pub placements: Vec<GatePlacement>,
+    current: usize,
+    score: u32,
+    passed_seconds: Option<f64>,
+    car_position: Option<Vec2>,
+}
+
+impl Default for Gates {
+    fn default() -> Self {
+        let gate = |x: f32, y: f32, angle: f32| GatePlacement {
+            position: Vec2::new(x, y),
+            angle,
+            width: 5.0,
+        };
+
+        Self {
+            enabled: false,
+            toggle_key: KeyCode::N,
+            placements: vec![
+                gate(25.0, 0.0, std::f32::consts::FRAC_PI_2),
+                gate(40.0, 25.0, 0.0),
+                gate(0.0, 35.0, std::f32::consts::FRAC_PI_2),
+                gate(-30.0, 10.0, 0.0),
+            ],
+            current: 0,
+            score: 0,
+            passed_seconds: None,
+            car_position: None,
+        }
+    }
+}
+
+/// How long "Passed!" is shown for, in seconds
+const GATE_PASSED_CUE_TIME: f64 = 1.0;
+
+/// Moves further than this, in meters, in a single frame are resets or wrapping around the world,
+/// rather than driving
+const GATE_MAX_STEP: f32 = 5.0;
+
+/// Whether the segments from `a0` to `a1` and from `b0` to `b1` cross each other
+pub(crate) fn segments_cross(a0: Vec2, a1: Vec2, b0: Vec2, b1: Vec2) -> bool {
+    let side = |from: Vec2, to: Vec2, point: Vec2| {
+        let (along, offset) = (to - from, point - from);
+
+        along.x * offset.y - along.y * offset.x
+    };
+
+    side(b0, b1, a0) * side(b0, b1, a1) < 0.0 && side(a0, a1, b0) * side(a0, a1, b1) < 0.0
+}
+
+/// Warns, with "BRAKE" beside the gate score, when the car is too fast to make the turn through
+/// the next gate on to the one after within its tires' grip
+pub struct CornerAdvisory {
+    pub enabled: bool,
+    /// The fraction of the estimated cornering speed above which the warning shows, so below one
+    /// warns early and above one only once the car is well over the limit
+    pub threshold: f32,
+}
+
+impl Default for CornerAdvisory {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            threshold: 0.9,
+        }
+    }
+}
+
+/// The radius of the circle through all three points, or `None` if they lie in a line
+pub(crate) fn corner_radius(a: Vec2, b: Vec2, c: Vec2) -> Option<f32> {
+    let (ab, ac) = (b - a, c - a);
+    let twice_area = (ab.x * ac.y - ab.y * ac.x).abs();
+
+    if twice_area < f32::EPSILON {
+        return None;
+    }
+
+    Some(a.distance(b) * b.distance(c) * c.distance(a) / (2.0 * twice_area))
+}
+
+/// The fastest a car can take a corner of the given radius before the sideways force needed to
+/// turn exceeds its tires' grip
+pub(crate) fn max_corner_speed(grip: f32, gravity: f32, radius: f32) -> f32 {
+    (grip * gravity * radius).max(0.0).sqrt()
+}
+
+pub(crate) struct GatePost {
+    side: usize,
+}
+
+pub(crate) struct GateText;
+
+pub(crate) fn spawn_gate_posts(mut commands: Commands, palette_materials: Res<PaletteMaterials>) {
+    for side in 0..2 {
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    size: 0.5 * Vec2::ONE,
+                    ..Default::default()
+                },
+                material: palette_materials.obstacle.clone(),
+                visible: Visible {
+                    is_visible: false,
+                    is_transparent: false,
+                },
+                ..Default::default()
+            })
+            .insert(GatePost { side });
+    }
+}
+
+pub(crate) fn drive_through_gates(
+    time: Res<SimTime>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut gates: ResMut<Gates>,
+    corner_advisory: Res<CornerAdvisory>,
+    lap_timer: Res<LapTimer>,
+    configs: Res<Assets<CarConfig>>,
+    gravity: Option<Res<Gravity>>,
+    mut checkpoints: EventWriter<CheckpointPassed>,
+    cars: Query<(Entity, &Transform, &CarState, &Handle<CarConfig>), With<PlayerControlled>>,
+    mut posts: Query<(&GatePost, &mut Transform, &mut Visible), Without<PlayerControlled>>,
+    mut text: Query<&mut Text, With<GateText>>,
+) {
+    if keyboard_input.just_pressed(gates.toggle_key) {
+        gates.enabled = !gates.enabled;
+        gates.score = 0;
+        gates.passed_seconds = None;
+    }
+
+    let gate = gates.placements.get(gates.current).copied();
+    let is_visible = gates.enabled && gate.is_some();
+
+    let car = cars.iter().next();
+    let car_position = car.map(|(_, car, _, _)| car.translation.truncate());
+    let previous_car_position = std::mem::replace(&mut gates.car_position, car_position);
+
+    if let (true, Some(gate), Some((car, _, _, _)), Some(from), Some(to)) =
+        (is_visible, gate, car, previous_car_position, car_position)
+    {
+        let (left, right) = gate.posts();
+
+        if from.distance(to) < GATE_MAX_STEP && segments_cross(from, to, left, right) {
+            checkpoints.send(CheckpointPassed {
+                car,
+                gate: gates.current,
+            });
+
+            gates.current = (gates.current + 1) % gates.placements.len();
+            gates.score += 1;
+            gates.passed_seconds = Some(time.seconds_since_startup());
+        }
+    }
+
+    let gate = gates.placements.get(gates.current).copied();
+
+    let over_corner_speed = match (corner_advisory.enabled, gate, car, car_position) {
+        (true, Some(gate), Some((_, _, state, config)), Some(car_position)) => {
+            let next_gate = gates.placements[(gates.current + 1) % gates.placements.len()];
+            let radius = corner_radius(car_position, gate.position, next_gate.position);
+
+            match (radius, configs.get(config)) {
+                (Some(radius), Some(config)) => {
+                    let gravity = gravity.as_ref().map_or(config.gravity, |gravity| gravity.0);
+                    let grip = config
+                        .axles()
+                        .iter()
+                        .map(|axle| axle.total_tire_grip)
+                        .fold(f32::INFINITY, f32::min);
+                    let max_speed = max_corner_speed(grip, gravity, radius);
+
+                    state.velocity.length() as f32 > corner_advisory.threshold * max_speed
+                }
+                _ => false,
+            }
+        }
+        _ => false,
+    };
+
+    for (post, mut transform, mut visible) in posts.iter_mut() {
+        if let Some(gate) = gate {
+            let (left, right) = gate.posts();
+            let position = if post.side == 0 { left } else { right };
+
+            transform.translation = position.extend(Z_GATE);
+        }
+
+        if visible.is_visible != is_visible {
+            visible.is_visible = is_visible;
+        }
+    }
+
+    for mut text in text.iter_mut() {
+        text.sections[0].value = if is_visible {
+            let just_passed = gates.passed_seconds.map_or(false, |passed_seconds| {
+                time.seconds_since_startup() - passed_seconds < GATE_PASSED_CUE_TIME
+            });
+
+            let lap_time = |label, seconds: Option<f32>| {
+                seconds.map_or_else(String::new, |seconds| {
+                    format!("  {}: {:.2}s", label, seconds)
+                })
+            };
+
+            format!(
+                "Gates: {}{}{}{}{}",
+                gates.score,
+                lap_time("Lap", lap_timer.last),
+                lap_time("Best", lap_timer.best),
+                if just_passed { "  Passed!" } else { "" },
+                if over_corner_speed { "  BRAKE" } else { "" }
+            )
+        } else {
+            String::new()
+        };
+    }
+}
+
+pub(crate) const BEST_LAPS_VERSION: u32 = 1;
+
+/// The best lap of the gates with one car config and layout of gates, and the drive which set it
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub(crate) struct BestLap {
+    pub(crate) config: String,
+    /// Each gate's position, angle and width
+    pub(crate) gates: Vec<[f32; 4]>,
+    pub(crate) seconds: f32,
+    #[serde(default)]
+    pub(crate) ghost: Vec<[f32; TELEMETRY_FIELD_COUNT]>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+struct BestLapsFile {
+    version: u32,
+    #[serde(default)]
+    laps: Vec<BestLap>,
+}
+
+pub(crate) fn write_best_laps(
+    mut writer: impl std::io::Write,
+    laps: &[BestLap],
+) -> anyhow::Result<()> {
+    serde_json::to_writer(
+        &mut writer,
+        &BestLapsFile {
+            version: BEST_LAPS_VERSION,
+            laps: laps.to_vec(),
+        },
+    )?;
+
+    writer.write_all(b"\n")?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+pub(crate) fn read_best_laps(reader: impl std::io::Read) -> anyhow::Result<Vec<BestLap>> {
+    let file = serde_json::from_reader::<_, BestLapsFile>(reader)?;
+
+    if file.version > BEST_LAPS_VERSION {
+        anyhow::bail!(
+            "Best laps version {} is newer than the supported version {}",
+            file.version,
+            BEST_LAPS_VERSION
+        );
+    }
+
+    Ok(file.laps)
+}
+
+/// Where the best laps of the gates are kept between sessions, one for each car config and
+/// layout of gates
+pub struct BestLaps {
+    pub path: String,
+    /// Whether to keep the drive which set each best lap, to race against as a ghost
+    pub record_ghost: bool,
+    /// Read from the file the first time a lap is timed
+    laps: Option<Vec<BestLap>>,
+}
+
+impl Default for BestLaps {
+    fn default() -> Self {
+        Self {
+            path: "best_laps.json".to_owned(),
+            record_ghost: true,
+            laps: None,
+        }
+    }
+}
+
+impl BestLaps {
+    /// A missing file starts afresh silently, while one which can't be read starts afresh with a
+    /// warning, and is replaced by the next best lap
+    pub(crate) fn load(&mut self) -> &mut Vec<BestLap> {
+        let path = &self.path;
+
+        self.laps.get_or_insert_with(|| {
+            if !std::path::Path::new(path).exists() {
+                return Vec::new();
+            }
+
+            std::fs::File::open(path)
+                .map_err(anyhow::Error::from)
+                .and_then(|file| read_best_laps(std::io::BufReader::new(file)))
+                .unwrap_or_else(|err| {
+                    warn!("Failed to read the best laps from {}: {}", path, err);
+
+                    Vec::new()
+                })
+        })
+    }
+}
+
+/// Times laps of the gates, from passing the first gate to passing it again, for the first
+/// player's car
+#[derive(Default)]
+pub struct LapTimer {
+    pub last: Option<f32>,
+    pub best: Option<f32>,
+    pub(crate) started_seconds: Option<f64>,
+    /// The config and gates which the best lap was set with
+    key: Option<(String, Vec<[f32; 4]>)>,
+    lap_frames: Vec<TelemetryFrame>,
+    pub(crate) ghost: Vec<TelemetryFrame>,
+}
+
+pub(crate) struct LapGhost;
+
+/// The most frames recorded of a lap with no best to beat, ten minutes at 60 frames a second, so
+/// that driving round without ever finishing doesn't record forever
+const MAX_LAP_FRAMES: usize = 10 * 60 * 60;
+
+pub(crate) fn time_laps(
+    mut commands: Commands,
+    time: Res<SimTime>,
+    asset_server: Res<AssetServer>,
+    gates: Res<Gates>,
+    replay: Res<Replay>,
+    mut best_laps: ResMut<BestLaps>,
+    mut timer: ResMut<LapTimer>,
+    ghost_materials: Res<GhostMaterials>,
+    mut checkpoints: EventReader<CheckpointPassed>,
+    cars: Query<
+        (
+            Entity,
+            &CarState,
+            &CarInputs,
+            &Handle<CarConfig>,
+            Option<&ConfigSource>,
+        ),
+        With<PlayerControlled>,
+    >,
+    mut ghosts: Query<(&mut Transform, &mut Visible), With<LapGhost>>,
+) {
+    let (car, state, inputs, config, config_source) = match cars.iter().next() {
+        Some(car) => car,
+        None => return,
+    };
+
+    // A car whose reloads are held back runs on an unnamed copy of its file
+    let handle = config_source.map_or(config.clone(), |source| source.handle.clone());
+    let config = asset_server
+        .get_handle_path(handle)
+        .and_then(|path| path.path().to_str().map(String::from))
+        .unwrap_or_default();
+    let placements = gates
+        .placements
+        .iter()
+        .map(|gate| [gate.position.x, gate.position.y, gate.angle, gate.width])
+        .collect::<Vec<_>>();
+    let key = (config, placements);
+
+    if timer.key.as_ref() != Some(&key) {
+        let best = best_laps
+            .load()
+            .iter()
+            .find(|lap| lap.config == key.0 && lap.gates == key.1)
+            .cloned();
+
+        *timer = LapTimer {
+            best: best.as_ref().map(|lap| lap.seconds),
+            key: Some(key.clone()),
+            ghost: best.map_or_else(Vec::new, |lap| {
+                lap.ghost
+                    .into_iter()
+                    .map(TelemetryFrame::from_fields)
+                    .collect()
+            }),
+            ..LapTimer::default()
+        };
+    }
+
+    if !gates.enabled || replay.active {
+        timer.started_seconds = None;
+    }
+
+    let now = time.seconds_since_startup();
+
+    for _ in checkpoints
+        .iter()
+        .filter(|checkpoint| checkpoint.car == car && checkpoint.gate == 0)
+    {
+        if let Some(started_seconds) = timer.started_seconds {
+            let seconds = (now - started_seconds) as f32;
+
+            timer.last = Some(seconds);
+
+            if timer.best.map_or(true, |best| seconds < best) {
+                info!("New best lap: {:.2}s", seconds);
+
+                timer.best = Some(seconds);
+                timer.ghost = std::mem::take(&mut timer.lap_frames);
+
+                let lap = BestLap {
+                    config: key.0.clone(),
+                    gates: key.1.clone(),
+                    seconds,
+                    ghost: if best_laps.record_ghost {
+                        timer.ghost.iter().map(|frame| frame.to_fields()).collect()
+                    } else {
+                        Vec::new()
+                    },
+                };
+
+                let laps = best_laps.load();
+                laps.retain(|best| best.config != lap.config || best.gates != lap.gates);
+                laps.push(lap);
+
+                let laps = laps.clone();
+
+                match std::fs::File::create(&best_laps.path)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|file| write_best_laps(std::io::BufWriter::new(file), &laps))
+                {
+                    Ok(()) => info!("Saved the best lap to {}", best_laps.path),
+                    Err(err) => error!("Failed to save the best lap: {}", err),
+                }
+            } else {
+                info!("Lap: {:.2}s", seconds);
+            }
+        }
+
+        timer.started_seconds = Some(now);
+        timer.lap_frames.clear();
+    }
+
+    let elapsed = timer
+        .started_seconds
+        .map(|started_seconds| (now - started_seconds) as f32);
+
+    match elapsed.filter(|_| best_laps.record_ghost) {
+        Some(elapsed) if timer.best.map_or(true, |best| elapsed < best) => {
+            if timer.lap_frames.len() < MAX_LAP_FRAMES {
+                timer
+                    .lap_frames
+                    .push(TelemetryFrame::new(elapsed, state, inputs));
+            }
+        }
+        // A lap already slower than the best can't replace its ghost, and one that isn't being
+        // timed has nothing to record
+        _ => {
+            if timer.lap_frames.capacity() > 0 {
+                timer.lap_frames = Vec::new();
+            }
+        }
+    }
+
+    if timer.ghost.is_empty() {
+        for (_, mut visible) in ghosts.iter_mut() {
+            if visible.is_visible {
+                visible.is_visible = false;
+            }
+        }
+
+        return;
+    }
+
+    if ghosts.iter_mut().next().is_none() {
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    size: Vec2::new(3.4, 1.28),
+                    ..Default::default()
+                },
+                material: ghost_materials.get(0),
+                visible: Visible {
+                    is_visible: false,
+                    is_transparent: true,
+                },
+                ..Default::default()
+            })
+            .insert(LapGhost);
+    }
+
+    // The ghost sets off with the car each lap, and waits at the start in between
+    let sample = elapsed.and_then(|elapsed| sample_telemetry(&timer.ghost, elapsed));
+
+    for (mut transform, mut visible) in ghosts.iter_mut() {
+        if let Some((position, heading)) = sample {
+            transform.translation = position.extend(Z_GHOST);
+            transform.rotation = Quat::from_rotation_z(heading);
+        }
+
+        if visible.is_visible != sample.is_some() {
+            visible.is_visible = sample.is_some();
+        }
+    }
+}
+
+/// How hard an AI driver pushes, from leaving a wide margin below the limit to driving on it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AiDifficulty {
+    Cautious,
+    Moderate,
+    Aggressive,
+}
+
+impl Default for AiDifficulty {
+    fn default() -> Self {
+        Self::Moderate
+    }
+}
+
+impl AiDifficulty {
+    pub(crate) fn next(self) -> Self {
+        match self {
+            Self::Cautious => Self::Moderate,
+            Self::Moderate => Self::Aggressive,
+            Self::Aggressive => Self::Cautious,
+        }
+    }
+
+    /// The fraction of the tires' grip the driver counts on, both in corners and braking for them
+    pub(crate) fn grip_ratio(self) -> f32 {
+        match self {
+            Self::Cautious => 0.6,
+            Self::Moderate => 0.8,
+            Self::Aggressive => 0.95,
+        }
+    }
+
+    /// How much of the throttle the driver is willing to use
+    fn throttle_ratio(self) -> f32 {
+        match self {
+            Self::Cautious => 0.6,
+            Self::Moderate => 0.8,
+            Self::Aggressive => 1.0,
+        }
+    }
+
+    /// The time constant, in seconds, of the driver's hands and feet catching up with what it
+    /// decides to do
+    fn reaction_time(self) -> f32 {
+        match self {
+            Self::Cautious => 0.3,
+            Self::Moderate => 0.15,
+            Self::Aggressive => 0.05,
+        }
+    }
+
+    /// The fastest the driver will go, in m/s, even on a straight
+    fn top_speed(self) -> f32 {
+        match self {
+            Self::Cautious => 20.0,
+            Self::Moderate => 30.0,
+            Self::Aggressive => 40.0,
+        }
+    }
+}
+
+/// The route the AI drivers follow, as waypoints and how close to get to each, in meters. With
+/// none, they follow the gates' placements instead
+#[derive(Default)]
+pub struct AiRoute {
+    pub waypoints: Vec<(Vec2, f32)>,
+}
+
+/// Drives a car round the AI route in order, slowing for each corner to within its tires' grip
+pub struct AiDriver {
+    pub difficulty: AiDifficulty,
+    waypoint: usize,
+    pub(crate) inputs: CarInputs,
+}
+
+impl AiDriver {
+    pub fn new(difficulty: AiDifficulty) -> Self {
+        Self {
+            difficulty,
+            waypoint: 0,
+            inputs: CarInputs::default(),
+        }
+    }
+}
+
+/// The keys for the AI drivers, who all change difficulty together
+pub struct AiControls {
+    /// Moves every AI driver on to its next difficulty at once, rather than any one car's
+    pub cycle_difficulty_key: KeyCode,
+}
+
+impl Default for AiControls {
+    fn default() -> Self {
+        Self {
+            cycle_difficulty_key: KeyCode::Y,
+        }
+    }
+}
+
+/// The throttle or brake applied per m/s off the speed an AI driver is aiming for
+const AI_SPEED_GAIN: f32 = 0.5;
+
+/// What an AI driver decides to do, heading for `target` and then on to `next`, having come from
+/// `previous`
+pub(crate) fn ai_inputs(
+    difficulty: AiDifficulty,
+    config: &CarConfig,
+    gravity: f32,
+    state: &CarState,
+    previous: Vec2,
+    target: Vec2,
+    next: Vec2,
+) -> CarInputs {
+    let position = from_real(state.position);
+    let to_target = target - position;
+
+    let heading_error = (to_target.y.atan2(to_target.x) - state.heading as f32
+        + std::f32::consts::PI)
+        .rem_euclid(2.0 * std::f32::consts::PI)
+        - std::f32::consts::PI;
+    let steer = (heading_error / config.max_steer.max(f32::EPSILON))
+        .max(-1.0)
+        .min(1.0);
+
+    let grip = difficulty.grip_ratio()
+        * config
+            .axles()
+            .iter()
+            .map(|axle| axle.total_tire_grip)
+            .fold(f32::INFINITY, f32::min);
+    let top_speed = difficulty.top_speed();
+    let corner_speed = corner_radius(previous, target, next)
+        .map_or(top_speed, |radius| max_corner_speed(grip, gravity, radius));
+
+    // Slow enough to brake down to the corner speed by the time the car reaches it
+    let target_speed = (corner_speed * corner_speed + 2.0 * grip * gravity * to_target.length())
+        .sqrt()
+        .min(top_speed);
+    let speed_error = target_speed - state.velocity.length() as f32;
+
+    CarInputs {
+        throttle: difficulty.throttle_ratio() * (AI_SPEED_GAIN * speed_error).max(0.0).min(1.0),
+        brake: (-AI_SPEED_GAIN * speed_error).max(0.0).min(1.0),
+        e_brake: 0.0,
+        steer,
+    }
+}
+
+pub(crate) struct AiText;
+
+fn drive_ai_cars(
+    time: Res<SimTime>,
+    keyboard_input: Res<Input<KeyCode>>,
+    controls: Res<AiControls>,
+    gates: Res<Gates>,
+    ai_route: Res<AiRoute>,
+    configs: Res<Assets<CarConfig>>,
+    gravity: Option<Res<Gravity>>,
+    mut cars: Query<(&mut AiDriver, &mut CarInputs, &CarState, &Handle<CarConfig>)>,
+    mut text: Query<&mut Text, With<AiText>>,
+) {
+    let cycle_difficulty = keyboard_input.just_pressed(controls.cycle_difficulty_key);
+    let waypoints = if ai_route.waypoints.is_empty() {
+        gates
+            .placements
+            .iter()
+            .map(|placement| (placement.position, placement.width))
+            .collect()
+    } else {
+        ai_route.waypoints.clone()
+    };
+    let dt_seconds = time.delta_seconds();
+
+    for (mut driver, mut inputs, state, config) in cars.iter_mut() {
+        if cycle_difficulty {
+            driver.difficulty = driver.difficulty.next();
+        }
+
+        let config = match configs.get(config) {
+            Some(config) => config,
+            None => continue,
+        };
+
+        let count = waypoints.len();
+
+        let decided = if count == 0 {
+            CarInputs {
+                brake: 1.0,
+                ..CarInputs::default()
+            }
+        } else {
+            let (position, radius) = waypoints[driver.waypoint % count];
+
+            if from_real(state.position).distance(position) < radius {
+                driver.waypoint = (driver.waypoint + 1) % count;
+            }
+
+            let waypoint = driver.waypoint % count;
+            let gravity = gravity.as_ref().map_or(config.gravity, |gravity| gravity.0);
+
+            ai_inputs(
+                driver.difficulty,
+                config,
+                gravity,
+                state,
+                waypoints[(waypoint + count - 1) % count].0,
+                waypoints[waypoint].0,
+                waypoints[(waypoint + 1) % count].0,
+            )
+        };
+
+        let reaction_time = driver.difficulty.reaction_time();
+        let blend = if reaction_time > f32::EPSILON {
+            dt_seconds / (reaction_time + dt_seconds)
+        } else {
+            1.0
+        };
+        let ease = |from: f32, to: f32| from + (to - from) * blend;
+
+        let reacted = CarInputs {
+            throttle: ease(driver.inputs.throttle, decided.throttle),
+            brake: ease(driver.inputs.brake, decided.brake),
+            e_brake: ease(driver.inputs.e_brake, decided.e_brake),
+            steer: ease(driver.inputs.steer, decided.steer),
+        };
+
+        driver.inputs = reacted;
+        *inputs = reacted;
+    }
+
+    let value = cars
+        .iter_mut()
+        .map(|(driver, _, _, _)| format!("AI: {:?}", driver.difficulty))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    for mut text in text.iter_mut() {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}